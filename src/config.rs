@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -13,13 +14,16 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::scripting::db_config;
+use crate::stats::assertion::Assertion;
+use crate::stats::errors::ErrorCategory;
+use crate::stats::percentiles::DEFAULT_PERCENTILES;
 
 /// Limit of retry errors to be kept and then printed in scope of a sampling interval
 pub const PRINT_RETRY_ERROR_LIMIT: u64 = 5;
 
 /// Parses a duration string. Supports humantime format (e.g. "5s", "1m 30s") and
 /// bare numbers which are interpreted as seconds for backward compatibility.
-fn parse_duration(s: &str) -> Result<Duration, String> {
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, String> {
     humantime::parse_duration(s).or_else(|_| {
         s.parse::<f64>()
             .map(Duration::from_secs_f64)
@@ -27,6 +31,34 @@ fn parse_duration(s: &str) -> Result<Duration, String> {
     })
 }
 
+fn parse_socket_addr(s: &str) -> Result<SocketAddr, String> {
+    match s.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::from(([0, 0, 0, 0], port))),
+        Err(_) => s.parse().map_err(|e| format!("Invalid address {s}: {e}")),
+    }
+}
+
+/// Like `parse_socket_addr`, but a bare port listens on the loopback interface only
+fn parse_loopback_socket_addr(s: &str) -> Result<SocketAddr, String> {
+    match s.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::from(([127, 0, 0, 1], port))),
+        Err(_) => s.parse().map_err(|e| format!("Invalid address {s}: {e}")),
+    }
+}
+
+fn parse_percentile(s: &str) -> Result<f64, String> {
+    let value = match s.trim().to_lowercase().as_str() {
+        "min" => 0.0,
+        "max" => 100.0,
+        s => s.parse().map_err(|_| format!("Invalid percentile: {s}"))?,
+    };
+    if (0.0..=100.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err("Percentile must be between 0 and 100".to_string())
+    }
+}
+
 fn parse_f64(s: &str) -> Result<f64, String> {
     let parsed_value: f64 = s.parse().map_err(|_| format!("Invalid float: {s}"))?;
     if (0.0..=1.0).contains(&parsed_value) {
@@ -53,7 +85,7 @@ where
 /// Controls how long the benchmark should run.
 /// We can specify either a time-based duration or a number of calls to perform.
 /// It is also used for controlling sampling.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Interval {
     Count(u64),
     Time(tokio::time::Duration),
@@ -189,6 +221,34 @@ pub struct ConnectionConf {
     )]
     pub retry_interval: RetryInterval,
 
+    /// Shape of the growth of the interval between the retries of a request.
+    #[clap(
+        long("retry-backoff"),
+        default_value = "exponential",
+        value_name = "SHAPE"
+    )]
+    #[serde(default)]
+    pub retry_backoff: RetryBackoff,
+
+    /// Action taken when a request fails with an error of given category.
+    /// Can be given multiple times, e.g. `--on-error validation=fail --on-error timeout=ignore`.
+    /// Categories: timeout, unavailable, overloaded, write_failure, read_failure, throttling,
+    /// validation, connection, other.
+    /// Actions: retry (with backoff, up to the retry number), ignore (count the failed cycle
+    /// and continue), fail (stop the benchmark).
+    /// By default, all errors are retried and validation errors are handled according to
+    /// the validation strategy.
+    #[clap(long("on-error"), value_name = "CATEGORY=ACTION")]
+    #[serde(default)]
+    pub error_rules: Vec<ErrorRule>,
+
+    /// Sends the request once again if it doesn't complete within given time
+    /// and takes the response that comes first.
+    /// Requests that are not idempotent are never sent twice.
+    #[clap(long("speculative-retry"), value_name = "DURATION", value_parser = parse_duration)]
+    #[serde(default)]
+    pub speculative_retry: Option<Duration>,
+
     /// Validation strategy is used in the following cases:
     /// - Defines the strategy for 'select' queries validation errors.
     ///   Gets applied when 'execute_prepared_with_validation'
@@ -216,20 +276,113 @@ pub enum ValidationStrategy {
     Ignore, // Ignore validation errors - face, print, go on.
 }
 
+impl ValidationStrategy {
+    /// Returns the action taken on validation errors if not configured explicitly
+    pub fn error_action(&self) -> ErrorAction {
+        match self {
+            ValidationStrategy::Retry => ErrorAction::Retry,
+            ValidationStrategy::FailFast => ErrorAction::Fail,
+            ValidationStrategy::Ignore => ErrorAction::Ignore,
+        }
+    }
+}
+
+/// Controls how the interval between the retries of a request grows with the attempt number.
+/// The interval never exceeds the max retry interval.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum RetryBackoff {
+    /// Doubles with each attempt, starting from the min retry interval
+    #[default]
+    Exponential,
+    /// Grows by the min retry interval with each attempt
+    Linear,
+    /// Always equal to the min retry interval
+    Constant,
+}
+
+/// What to do with a failed request
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum ErrorAction {
+    /// Retry the request after the backoff interval
+    Retry,
+    /// Don't retry; count the cycle as failed and continue the benchmark
+    Ignore,
+    /// Stop the benchmark
+    Fail,
+}
+
+/// Sets the action taken on errors of a category
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ErrorRule {
+    pub category: ErrorCategory,
+    pub action: ErrorAction,
+}
+
+/// Parses `CATEGORY=ACTION`
+impl FromStr for ErrorRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((category, action)) = s.split_once('=') else {
+            return Err("Expected <CATEGORY>=<ACTION>, e.g. 'timeout=ignore'".to_string());
+        };
+        Ok(ErrorRule {
+            category: category.trim().parse()?,
+            action: ErrorAction::from_str(action.trim(), true)?,
+        })
+    }
+}
+
+impl Display for ErrorRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let action = self.action.to_possible_value().unwrap();
+        write!(f, "{}={}", self.category.name(), action.get_name())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WeightedFunction {
     pub name: String,
     pub weight: f64,
+    /// If set, the function is invoked at its own rate, in calls per second,
+    /// instead of sharing the main stream of cycles with other functions
+    #[serde(default)]
+    pub rate: Option<f64>,
 }
 
+impl WeightedFunction {
+    /// Returns the same function with its own rate multiplied by `factor`.
+    /// Used to split the load between clients.
+    pub fn scale_rate(&self, factor: f64) -> WeightedFunction {
+        WeightedFunction {
+            rate: self.rate.map(|r| r * factor),
+            ..self.clone()
+        }
+    }
+}
+
+/// Parses `NAME[:WEIGHT]` or `NAME@RATE`
 impl FromStr for WeightedFunction {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.contains(':') {
+        if let Some((name, rate)) = s.split_once('@') {
+            let rate: f64 = rate
+                .parse()
+                .map_err(|e| format!("Invalid rate value: {e}"))?;
+            if rate <= 0.0 {
+                return Err("Function rate must be greater than 0".to_string());
+            }
+            Ok(Self {
+                name: name.to_string(),
+                weight: 0.0,
+                rate: Some(rate),
+            })
+        } else if !s.contains(':') {
             Ok(Self {
                 name: s.to_string(),
                 weight: 1.0,
+                rate: None,
             })
         } else if let Some((name, weight)) = s.split(':').collect_tuple() {
             let weight: f64 = weight
@@ -241,16 +394,288 @@ impl FromStr for WeightedFunction {
             Ok(Self {
                 name: name.to_string(),
                 weight,
+                rate: None,
             })
         } else {
-            Err("Failed to parse function specification. Expected <NAME>[:WEIGHT]".to_string())
+            Err(
+                "Failed to parse function specification. Expected <NAME>[:WEIGHT] or <NAME>@<RATE>"
+                    .to_string(),
+            )
         }
     }
 }
 
 impl Display for WeightedFunction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.name, self.weight)
+        match self.rate {
+            Some(rate) => write!(f, "{}@{}", self.name, rate),
+            None => write!(f, "{}:{}", self.name, self.weight),
+        }
+    }
+}
+
+/// Distribution of the pause a virtual user takes between consecutive cycles
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ThinkTime {
+    /// Always the same pause
+    Fixed(Duration),
+    /// Pause drawn uniformly from the range
+    Uniform(Duration, Duration),
+    /// Exponentially distributed pause with the given mean
+    Exponential(Duration),
+}
+
+/// Parses `DURATION`, `fixed:DURATION`, `uniform:MIN..MAX` or `exp:MEAN`
+impl FromStr for ThinkTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':').unwrap_or(("fixed", s));
+        match kind.trim() {
+            "fixed" => Ok(ThinkTime::Fixed(parse_duration(value.trim())?)),
+            "uniform" => {
+                let (min, max) = value
+                    .split_once("..")
+                    .ok_or_else(|| "Expected uniform:MIN..MAX".to_string())?;
+                let min = parse_duration(min.trim())?;
+                let max = parse_duration(max.trim())?;
+                if min > max {
+                    return Err("Minimum think time must not exceed the maximum".to_string());
+                }
+                Ok(ThinkTime::Uniform(min, max))
+            }
+            "exp" | "exponential" => Ok(ThinkTime::Exponential(parse_duration(value.trim())?)),
+            other => Err(format!(
+                "Unknown think time distribution `{other}`. Expected one of: fixed, uniform, exp"
+            )),
+        }
+    }
+}
+
+impl Display for ThinkTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use humantime::format_duration;
+        match self {
+            ThinkTime::Fixed(d) => write!(f, "fixed, {}", format_duration(*d)),
+            ThinkTime::Uniform(min, max) => {
+                write!(
+                    f,
+                    "uniform, {} .. {}",
+                    format_duration(*min),
+                    format_duration(*max)
+                )
+            }
+            ThinkTime::Exponential(mean) => {
+                write!(f, "exponential, mean {}", format_duration(*mean))
+            }
+        }
+    }
+}
+
+/// A single phase of a multi-stage benchmark run.
+///
+/// Stages are executed one after another on the same workload.
+/// Any setting not given for a stage is inherited from the main run options.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunStage {
+    pub name: Option<String>,
+    pub duration: Interval,
+    pub rate: Option<f64>,
+    /// If set, the rate changes linearly from `rate` to `rate_end` over the stage duration.
+    pub rate_end: Option<f64>,
+    pub concurrency: Option<NonZeroUsize>,
+    pub functions: Vec<WeightedFunction>,
+}
+
+impl RunStage {
+    /// Returns the stage name or a default name derived from the stage position.
+    pub fn name_or_default(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("stage-{}", index + 1))
+    }
+}
+
+/// Parses a comma separated list of `KEY=VALUE` pairs, e.g.
+/// `name=ramp,duration=5m,rate=1000..20000,concurrency=256,functions=read:0.8;write:0.2`.
+/// The `duration` key is required. Rate given as `FROM..TO` defines a linear ramp.
+/// Functions are separated by semicolons.
+impl FromStr for RunStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut stage = RunStage {
+            name: None,
+            duration: Interval::Unbounded,
+            rate: None,
+            rate_end: None,
+            concurrency: None,
+            functions: Vec::new(),
+        };
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid stage option `{part}`. Expected KEY=VALUE"))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => stage.name = Some(value.to_string()),
+                "duration" => stage.duration = value.parse()?,
+                "rate" => {
+                    let parse_rate = |r: &str| {
+                        r.trim()
+                            .parse::<f64>()
+                            .map_err(|e| format!("Invalid stage rate `{r}`: {e}"))
+                    };
+                    match value.split_once("..") {
+                        Some((from, to)) => {
+                            stage.rate = Some(parse_rate(from)?);
+                            stage.rate_end = Some(parse_rate(to)?);
+                        }
+                        None => stage.rate = Some(parse_rate(value)?),
+                    }
+                    if stage.rate.iter().chain(&stage.rate_end).any(|r| *r <= 0.0) {
+                        return Err("Stage rate must be greater than 0".to_string());
+                    }
+                }
+                "concurrency" => {
+                    stage.concurrency = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid stage concurrency `{value}`: {e}"))?,
+                    )
+                }
+                "functions" | "function" => {
+                    stage.functions = value
+                        .split(';')
+                        .map(WeightedFunction::from_str)
+                        .try_collect()?
+                }
+                other => return Err(format!("Unknown stage option `{other}`")),
+            }
+        }
+        if !stage.duration.is_bounded() {
+            return Err("Stage duration is required, e.g. duration=60s".to_string());
+        }
+        if stage.rate_end.is_some() && stage.duration.period().is_none() {
+            return Err("Rate ramp requires a time-based stage duration".to_string());
+        }
+        Ok(stage)
+    }
+}
+
+impl Display for RunStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.duration {
+            Interval::Count(count) => write!(f, "{count} op")?,
+            Interval::Time(d) => write!(f, "{} s", d.as_secs_f64())?,
+            Interval::Unbounded => {}
+        }
+        match (self.rate, self.rate_end) {
+            (Some(from), Some(to)) => write!(f, ", {from}..{to} op/s")?,
+            (Some(rate), None) => write!(f, ", {rate} op/s")?,
+            _ => {}
+        }
+        if let Some(concurrency) = self.concurrency {
+            write!(f, ", {concurrency} req")?;
+        }
+        if !self.functions.is_empty() {
+            write!(f, ", {}", self.functions.iter().join(";"))?;
+        }
+        Ok(())
+    }
+}
+
+/// A change of the weights of the functions during a run.
+///
+/// The weights change linearly from the weights in effect at `from` to the given weights at `to`.
+/// If `from` and `to` are equal, the weights switch at once.
+/// The positions are given either as time elapsed since the start of the phase or as cycle numbers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MixChange {
+    pub from: Interval,
+    pub to: Interval,
+    pub functions: Vec<WeightedFunction>,
+}
+
+/// Parses a comma separated list of `KEY=VALUE` pairs, e.g.
+/// `at=0..10m,functions=read:0.5;write:0.5`.
+/// Both keys are required. Position given as `FROM..TO` defines a linear transition.
+impl FromStr for MixChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut position = None;
+        let mut functions = Vec::new();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid mix option `{part}`. Expected KEY=VALUE"))?;
+            let value = value.trim();
+            match key.trim() {
+                "at" => {
+                    let (from, to) = value.split_once("..").unwrap_or((value, value));
+                    let from: Interval = from.trim().parse()?;
+                    let to: Interval = to.trim().parse()?;
+                    // Plain 0 is a valid start of both a time and a cycle range
+                    let from = match (from, to) {
+                        (Interval::Count(0), Interval::Time(_)) => Interval::Time(Duration::ZERO),
+                        _ => from,
+                    };
+                    position = Some((from, to));
+                }
+                "functions" | "function" => {
+                    functions = value
+                        .split(';')
+                        .map(WeightedFunction::from_str)
+                        .try_collect()?
+                }
+                other => return Err(format!("Unknown mix option `{other}`")),
+            }
+        }
+        let (from, to) =
+            position.ok_or_else(|| "Mix position is required, e.g. at=10m".to_string())?;
+        match (from, to) {
+            (Interval::Count(from), Interval::Count(to)) if from <= to => {}
+            (Interval::Time(from), Interval::Time(to)) if from <= to => {}
+            _ => {
+                return Err(
+                    "Mix transition must start before it ends and use the same unit".to_string(),
+                )
+            }
+        }
+        if functions.is_empty() {
+            return Err(
+                "Mix functions are required, e.g. functions=read:0.5;write:0.5".to_string(),
+            );
+        }
+        if functions.iter().any(|f| f.rate.is_some()) {
+            return Err("Functions with own rates cannot be used in a mix".to_string());
+        }
+        if functions.iter().all(|f| f.weight <= 0.0) {
+            return Err("At least one mix function must have a weight greater than 0".to_string());
+        }
+        Ok(MixChange {
+            from,
+            to,
+            functions,
+        })
+    }
+}
+
+impl Display for MixChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let position = |i: &Interval| match i {
+            Interval::Count(count) => format!("{count}"),
+            Interval::Time(d) => format!("{}", humantime::format_duration(*d)),
+            Interval::Unbounded => String::new(),
+        };
+        let (from, to) = (position(&self.from), position(&self.to));
+        if from == to {
+            write!(f, "at {from}")?;
+        } else {
+            write!(f, "{from}..{to}")?;
+        }
+        write!(f, ", {}", self.functions.iter().join(";"))
     }
 }
 
@@ -281,6 +706,519 @@ pub struct RateConf {
         value_parser = parse_duration,
     )]
     pub rate_sine_period: Duration,
+
+    /// Model of the arrival times of the cycles when the rate is limited.
+    ///
+    /// `uniform` starts the cycles at equal intervals.
+    /// `poisson` draws the intervals from the exponential distribution, forming a Poisson process.
+    /// `burst,rate=RATE,length=DURATION,period=DURATION` raises the rate to RATE for LENGTH
+    /// at the beginning of every period.
+    /// `step,rate=RATE,period=DURATION` switches between the main rate and RATE
+    /// every half of the period, forming a square wave.
+    /// Requires the "rate" option to be defined.
+    #[clap(long, default_value = "uniform", value_name = "MODEL")]
+    #[serde(default)]
+    pub arrival: Arrival,
+
+    /// Replays the rate changes recorded in a CSV file.
+    ///
+    /// Each line of the file contains a time offset from the beginning of the main phase
+    /// and the rate in cycles per second, e.g. `90s,2500`. The rate is interpolated linearly
+    /// between the points and stays at the last value after the last point.
+    /// The header line is optional.
+    #[clap(
+        long("rate-profile"),
+        value_name = "PATH",
+        value_parser = RateProfile::load,
+        conflicts_with_all = ["rate", "rate_sine_amplitude"],
+    )]
+    #[serde(default)]
+    pub rate_profile: Option<RateProfile>,
+
+    /// Runs a closed-loop model of virtual users instead of starting the cycles at a fixed rate.
+    ///
+    /// Each of the `concurrency` slots of each thread acts as a user that waits for
+    /// the given think time after completing a cycle before starting the next one.
+    /// The think time is given as `DURATION` or `fixed:DURATION`, `uniform:MIN..MAX`
+    /// or `exp:MEAN` for exponentially distributed pauses.
+    #[clap(
+        long("think-time"),
+        value_name = "DISTRIBUTION",
+        conflicts_with_all = ["rate", "rate_profile"],
+    )]
+    #[serde(default)]
+    pub think_time: Option<ThinkTime>,
+}
+
+/// Target rate changing in time, defined by a series of points interpolated linearly.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateProfile {
+    /// Time offsets in seconds and the rates in cycles per second at those times
+    points: Vec<(f64, f64)>,
+}
+
+impl RateProfile {
+    /// Reads the profile from a CSV file
+    pub fn load(path: &str) -> Result<RateProfile, String> {
+        let csv = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read rate profile {path}: {e}"))?;
+        csv.parse()
+            .map_err(|e| format!("Invalid rate profile {path}: {e}"))
+    }
+
+    /// Returns the target rate at the given number of seconds from the beginning
+    pub fn rate_at(&self, time_s: f64) -> f64 {
+        let i = self.points.partition_point(|(t, _)| *t <= time_s);
+        match (i.checked_sub(1).map(|i| self.points[i]), self.points.get(i)) {
+            (Some((t0, r0)), Some((t1, r1))) => r0 + (r1 - r0) * (time_s - t0) / (t1 - t0),
+            (Some((_, r)), None) | (None, Some(&(_, r))) => r,
+            (None, None) => unreachable!("rate profile must not be empty"),
+        }
+    }
+
+    /// Returns the same profile with all rates multiplied by `factor`.
+    /// Used to split the load between threads or clients.
+    pub fn scale_rate(&self, factor: f64) -> RateProfile {
+        RateProfile {
+            points: self.points.iter().map(|(t, r)| (*t, r * factor)).collect(),
+        }
+    }
+}
+
+/// Parses comma separated `TIME,RATE` lines.
+/// Time is given in seconds or as a duration with a unit, e.g. `1h 30m`.
+impl FromStr for RateProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        let mut header = false;
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (time, rate) = line
+                .split_once(',')
+                .ok_or_else(|| format!("Expected TIME,RATE in line {}", i + 1))?;
+            let time = parse_duration(time.trim()).map(|t| t.as_secs_f64());
+            let rate = rate.trim().parse::<f64>();
+            let (time, rate) = match (time, rate) {
+                (Ok(time), Ok(rate)) => (time, rate),
+                _ if points.is_empty() && !header => {
+                    header = true;
+                    continue;
+                }
+                _ => return Err(format!("Invalid time or rate in line {}", i + 1)),
+            };
+            if !(rate > 0.0 && rate.is_finite()) {
+                return Err(format!("Rate must be greater than 0 in line {}", i + 1));
+            }
+            if points.last().is_some_and(|(t, _)| *t >= time) {
+                return Err(format!(
+                    "Time offsets must increase, but line {} goes back",
+                    i + 1
+                ));
+            }
+            points.push((time, rate));
+        }
+        if points.is_empty() {
+            return Err("No rate points found".to_string());
+        }
+        Ok(RateProfile { points })
+    }
+}
+
+impl Display for RateProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (min, max) = self
+            .points
+            .iter()
+            .map(|(_, r)| *r)
+            .minmax()
+            .into_option()
+            .unwrap_or_default();
+        let end = self.points.last().map(|(t, _)| *t).unwrap_or_default();
+        write!(
+            f,
+            "{} points, {min:.0}..{max:.0} op/s over {}",
+            self.points.len(),
+            humantime::format_duration(Duration::from_secs_f64(end))
+        )
+    }
+}
+
+/// Controls when the cycles get started, if the rate is limited.
+/// The rates of the bursts and steps are given in cycles per second of the whole run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Arrival {
+    /// Cycles are started at equal intervals
+    #[default]
+    Uniform,
+    /// Intervals between the cycles are exponentially distributed
+    Poisson,
+    /// The rate is raised to `rate` for `length` at the beginning of each `period`
+    Burst {
+        rate: f64,
+        length: Duration,
+        period: Duration,
+    },
+    /// The rate is raised to `rate` for the second half of each `period`
+    Step { rate: f64, period: Duration },
+}
+
+impl Arrival {
+    /// Returns the same model with the rates of the bursts or steps multiplied by `factor`.
+    /// Used to split the load between threads or clients.
+    pub fn scale_rate(self, factor: f64) -> Arrival {
+        match self {
+            Arrival::Burst {
+                rate,
+                length,
+                period,
+            } => Arrival::Burst {
+                rate: rate * factor,
+                length,
+                period,
+            },
+            Arrival::Step { rate, period } => Arrival::Step {
+                rate: rate * factor,
+                period,
+            },
+            other => other,
+        }
+    }
+}
+
+/// Parses the arrival model name followed by a comma separated list of `KEY=VALUE` options,
+/// e.g. `burst,rate=20000,length=1s,period=10s`.
+impl FromStr for Arrival {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim).filter(|p| !p.is_empty());
+        let model = parts.next().unwrap_or_default();
+        let mut rate = None;
+        let mut length = None;
+        let mut period = None;
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid arrival option `{part}`. Expected KEY=VALUE"))?;
+            let value = value.trim();
+            match key.trim() {
+                "rate" => {
+                    let r = value
+                        .parse::<f64>()
+                        .map_err(|e| format!("Invalid arrival rate `{value}`: {e}"))?;
+                    if r <= 0.0 {
+                        return Err("Arrival rate must be greater than 0".to_string());
+                    }
+                    rate = Some(r)
+                }
+                "length" => length = Some(parse_duration(value)?),
+                "period" => period = Some(parse_duration(value)?),
+                other => return Err(format!("Unknown arrival option `{other}`")),
+            }
+        }
+        let missing = |key: &str| format!("Arrival model `{model}` requires the `{key}` option");
+        let arrival = match model {
+            "uniform" => Arrival::Uniform,
+            "poisson" => Arrival::Poisson,
+            "burst" => Arrival::Burst {
+                rate: rate.ok_or_else(|| missing("rate"))?,
+                length: length.ok_or_else(|| missing("length"))?,
+                period: period.ok_or_else(|| missing("period"))?,
+            },
+            "step" => Arrival::Step {
+                rate: rate.ok_or_else(|| missing("rate"))?,
+                period: period.ok_or_else(|| missing("period"))?,
+            },
+            other => {
+                return Err(format!(
+                "Unknown arrival model `{other}`. Expected one of: uniform, poisson, burst, step"
+            ))
+            }
+        };
+        match arrival {
+            Arrival::Uniform | Arrival::Poisson
+                if rate.is_some() || length.is_some() || period.is_some() =>
+            {
+                Err(format!("Arrival model `{model}` takes no options"))
+            }
+            Arrival::Step { .. } if length.is_some() => {
+                Err("Arrival model `step` takes no `length` option".to_string())
+            }
+            Arrival::Burst { length, period, .. } if length > period => {
+                Err("Burst length must not exceed the period".to_string())
+            }
+            _ if period.is_some_and(|p| p.is_zero()) => {
+                Err("Arrival period must be greater than 0".to_string())
+            }
+            arrival => Ok(arrival),
+        }
+    }
+}
+
+impl Display for Arrival {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arrival::Uniform => write!(f, "uniform"),
+            Arrival::Poisson => write!(f, "poisson"),
+            Arrival::Burst {
+                rate,
+                length,
+                period,
+            } => write!(
+                f,
+                "burst, {rate} op/s for {} every {}",
+                humantime::format_duration(*length),
+                humantime::format_duration(*period)
+            ),
+            Arrival::Step { rate, period } => write!(
+                f,
+                "step, {rate} op/s every other {}",
+                humantime::format_duration(*period / 2)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum SampleLogFormat {
+    /// One JSON object per line
+    Json,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+/// Controls how the next rate gets chosen when searching for the maximum rate.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum SearchMode {
+    /// Doubles the rate until the limits are exceeded, then bisects the last interval.
+    #[default]
+    Binary,
+    /// Increases the rate by a fixed step until the limits are exceeded.
+    Step,
+}
+
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct SearchConf {
+    /// Searches for the maximum rate at which the latency and error limits are met,
+    /// instead of running a single benchmark phase.
+    ///
+    /// Runs a sequence of probes, each at a fixed rate, and reports every probe.
+    /// The summary statistics are reported for the highest passing probe.
+    #[clap(long("find-max-rate"))]
+    pub find_max_rate: bool,
+
+    /// Strategy of choosing the rate of the next probe.
+    #[clap(long("search-mode"), default_value = "binary", value_name = "MODE")]
+    pub mode: SearchMode,
+
+    /// Rate of the first probe, in cycles per second.
+    #[clap(
+        long("search-start-rate"),
+        default_value = "1000",
+        value_name = "COUNT"
+    )]
+    pub start_rate: f64,
+
+    /// Rate increment between the probes in the step mode.
+    /// Defaults to the start rate.
+    #[clap(long("search-step"), value_name = "COUNT")]
+    pub step: Option<f64>,
+
+    /// Upper bound of the probed rate.
+    #[clap(long("search-max-rate"), value_name = "COUNT")]
+    pub max_rate: Option<f64>,
+
+    /// Duration of each probe.
+    #[clap(
+        long("search-probe-duration"),
+        default_value = "30s",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+    )]
+    pub probe_duration: Duration,
+
+    /// Percentile of the cycle latency checked against the latency limit.
+    #[clap(
+        long("search-percentile"),
+        default_value = "99",
+        value_name = "PERCENTILE"
+    )]
+    pub percentile: f64,
+
+    /// Maximum value of the checked latency percentile for a probe to pass.
+    #[clap(
+        long("search-latency-limit"),
+        default_value = "10ms",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+    )]
+    pub latency_limit: Duration,
+
+    /// Maximum percentage of failed cycles for a probe to pass.
+    #[clap(
+        long("search-error-limit"),
+        default_value = "0",
+        value_name = "PERCENT"
+    )]
+    pub error_limit: f64,
+
+    /// Relative width of the rate interval at which the binary search stops.
+    #[clap(
+        long("search-precision"),
+        default_value = "0.05",
+        value_parser = parse_f64,
+        value_name = "RATE-MULTIPLIER"
+    )]
+    pub precision: f64,
+}
+
+impl Display for SearchConf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mode = match self.mode {
+            SearchMode::Binary => "binary",
+            SearchMode::Step => "step",
+        };
+        write!(f, "{mode}, from {} op/s", self.start_rate)?;
+        if let Some(max_rate) = self.max_rate {
+            write!(f, " to {max_rate} op/s")?;
+        }
+        write!(
+            f,
+            ", {} s probes, P{} <= {} ms, errors <= {}%",
+            self.probe_duration.as_secs_f64(),
+            self.percentile,
+            self.latency_limit.as_secs_f64() * 1000.0,
+            self.error_limit
+        )
+    }
+}
+
+impl Default for SearchConf {
+    fn default() -> Self {
+        SearchConf {
+            find_max_rate: false,
+            mode: SearchMode::Binary,
+            start_rate: 1000.0,
+            step: None,
+            max_rate: None,
+            probe_duration: Duration::from_secs(30),
+            percentile: 99.0,
+            latency_limit: Duration::from_millis(10),
+            error_limit: 0.0,
+            precision: 0.05,
+        }
+    }
+}
+
+/// Controls how the latencies are recorded and which latency percentiles get reported.
+#[derive(Parser, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LatencyConf {
+    /// Comma-separated list of the latency percentiles to report, e.g. `50,99,99.9,99.999`.
+    ///
+    /// Defaults to min, 25, 50, 75, 90, 95, 98, 99, 99.9, 99.99, 99.999, 99.9999 and max.
+    /// Percentiles used by the progress log and other outputs are computed regardless
+    /// of this setting.
+    #[clap(
+        long("percentiles"),
+        value_delimiter = ',',
+        value_name = "LIST",
+        value_parser = parse_percentile
+    )]
+    #[serde(default)]
+    pub percentiles: Vec<f64>,
+
+    /// Number of significant decimal digits of the recorded latencies, from 1 to 5.
+    ///
+    /// Higher precision makes the percentiles more accurate at the cost of more memory
+    /// and CPU time spent on computing the statistics.
+    #[clap(
+        long("histogram-precision"),
+        default_value = "3",
+        value_name = "DIGITS",
+        value_parser = clap::value_parser!(u8).range(1..=5)
+    )]
+    pub histogram_precision: u8,
+
+    /// The lowest latency distinguishable from zero.
+    #[clap(
+        long("histogram-min"),
+        default_value = "1ns",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    pub histogram_min: Duration,
+
+    /// The highest recorded latency. Longer latencies are recorded as this value.
+    /// If not given, the latency histograms grow as needed.
+    #[clap(long("histogram-max"), value_name = "DURATION", value_parser = parse_duration)]
+    pub histogram_max: Option<Duration>,
+
+    /// Cycles started later than their scheduled time by more than this value are counted
+    /// as late. Many late cycles mean that the client cannot keep up with the requested rate.
+    #[clap(
+        long("late-threshold"),
+        default_value = "10ms",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    #[serde(default = "default_late_threshold")]
+    pub late_threshold: Duration,
+}
+
+fn default_late_threshold() -> Duration {
+    Duration::from_millis(10)
+}
+
+fn default_drain_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+impl LatencyConf {
+    /// Returns the sorted percentiles chosen to be reported
+    pub fn percentiles(&self) -> Vec<f64> {
+        if self.percentiles.is_empty() {
+            DEFAULT_PERCENTILES.iter().map(|p| p.value()).collect()
+        } else {
+            self.percentiles
+                .iter()
+                .copied()
+                .sorted_by(f64::total_cmp)
+                .dedup()
+                .collect()
+        }
+    }
+}
+
+impl Default for LatencyConf {
+    fn default() -> Self {
+        LatencyConf {
+            percentiles: Vec::new(),
+            histogram_precision: 3,
+            histogram_min: Duration::from_nanos(1),
+            histogram_max: None,
+            late_threshold: default_late_threshold(),
+        }
+    }
+}
+
+impl Display for LatencyConf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} digits, {} .. {}",
+            self.histogram_precision,
+            humantime::format_duration(self.histogram_min),
+            match self.histogram_max {
+                Some(max) => humantime::format_duration(max).to_string(),
+                None => "unbounded".to_string(),
+            }
+        )
+    }
 }
 
 #[derive(Parser, Debug, Serialize, Deserialize)]
@@ -354,14 +1292,48 @@ pub struct RunCommand {
     pub warmup_duration: Interval,
 
     /// Number of cycles or duration of the main benchmark phase.
+    /// Cannot be used with `--stage`, because each stage has its own duration.
     #[clap(
         short('d'),
         long("duration"),
         default_value = "60s",
-        value_name = "TIME | COUNT"
+        value_name = "TIME | COUNT",
+        conflicts_with = "stages"
     )]
     pub run_duration: Interval,
 
+    /// Stages of the main benchmark phase. Can be used multiple times.
+    ///
+    /// If given, the main phase runs the stages one after another instead of
+    /// a single phase controlled by `--duration`. Each stage is defined by a comma separated
+    /// list of options: `name`, `duration` (required), `rate` (a single value or a linear ramp
+    /// given as `FROM..TO`), `concurrency` and `functions` (separated by semicolons).
+    /// Options not given for a stage are inherited from the main run options.
+    /// Each stage continues with the cycles following the ones executed by the previous stage.
+    ///
+    /// Example: `--stage name=ramp,duration=5m,rate=1000..20000
+    ///           --stage 'name=soak,duration=1h,rate=20000,functions=read:0.8;write:0.2'`
+    #[clap(long("stage"), value_name = "STAGE", number_of_values = 1)]
+    #[serde(default)]
+    pub stages: Vec<RunStage>,
+
+    /// Changes of the function weights during the run. Can be used multiple times.
+    ///
+    /// Each change is defined by a comma separated list of options: `at` (required) and
+    /// `functions` (required, separated by semicolons). The position `at` is a time since the
+    /// start of the phase or a cycle number. A position given as `FROM..TO` changes the weights
+    /// linearly over the range instead of switching them at once.
+    /// Functions not listed get the weight 0.
+    ///
+    /// Example: `-f read:0.9,write:0.1 --mix 'at=0..10m,functions=read:0.5;write:0.5'`
+    #[clap(long("mix"), value_name = "CHANGE", number_of_values = 1)]
+    #[serde(default)]
+    pub function_mix: Vec<MixChange>,
+
+    #[clap(flatten)]
+    #[serde(default)]
+    pub search: SearchConf,
+
     /// The initial value of the cycle counter.
     ///
     /// Normally the cycle counter starts from 0, but you can start from a different value.
@@ -383,6 +1355,18 @@ pub struct RunCommand {
     #[clap(short('p'), long, default_value = "128", value_name = "COUNT")]
     pub concurrency: NonZeroUsize,
 
+    /// How long to wait for the pending requests to complete after the run is interrupted
+    /// with Ctrl-C. The results gathered so far are reported and saved.
+    /// Press Ctrl-C again to abort without waiting.
+    #[clap(
+        long("drain-timeout"),
+        default_value = "10s",
+        value_name = "DURATION",
+        value_parser = parse_duration
+    )]
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout: Duration,
+
     /// Sampling period, in seconds.
     ///
     /// While running the workload, periodically takes a snapshot of the statistics
@@ -405,6 +1389,10 @@ pub struct RunCommand {
     )]
     pub sampling_interval: Interval,
 
+    #[clap(flatten)]
+    #[serde(default)]
+    pub latency: LatencyConf,
+
     /// Label that will be added to the report to help identifying the test
     #[clap(long("tag"), value_delimiter = ',')]
     pub tags: Vec<String>,
@@ -423,15 +1411,67 @@ pub struct RunCommand {
     )]
     pub hdrfile: Option<PathBuf>,
 
+    /// Path to a file the samples are written to as soon as they are recorded,
+    /// one JSON object or one CSV row per sample.
+    ///
+    /// Unlike the sampling log saved in the report, the samples are not kept in memory
+    /// and all samples recorded so far are available even if the run is interrupted.
+    #[clap(long("sample-log"), value_name = "PATH")]
+    #[serde(default)]
+    pub sample_log: Option<PathBuf>,
+
+    /// Format of the sample log. If not given, it is chosen by the file extension:
+    /// `.csv` files get CSV, all other files get JSON Lines.
+    #[clap(long("sample-log-format"), value_name = "FORMAT")]
+    #[serde(default)]
+    pub sample_log_format: Option<SampleLogFormat>,
+
     /// Path to an output file or directory where the JSON report should be written to.
     #[clap(short('o'), long)]
     #[serde(skip)]
     pub output: Option<PathBuf>,
 
+    /// Address to serve the live statistics of the run on, in the Prometheus format.
+    /// The statistics are available at the `/metrics` path and updated after each sample.
+    /// If only the port is given, listens on all interfaces.
+    #[clap(long("metrics-addr"), value_name = "[HOST:]PORT", value_parser = parse_socket_addr)]
+    #[serde(default)]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Addresses of the agents to run the benchmark on, started with `latte agent`.
+    ///
+    /// If given, the benchmark is not run locally. Instead, each agent gets an equal part of
+    /// the cycle range, of the rate and of the cycle count, and all agents start
+    /// the main phase at the same moment. The samples sent by the agents are merged into
+    /// a single report. The clocks of the machines should be synchronized.
+    #[clap(long("agents"), value_name = "HOST:PORT", value_delimiter = ',')]
+    #[serde(default)]
+    pub agents: Vec<String>,
+
     /// Path to a report from another earlier run that should be compared to side-by-side
     #[clap(short('b'), long, value_name = "PATH")]
     pub baseline: Option<PathBuf>,
 
+    /// Condition the results of the run must satisfy. Can be used multiple times.
+    ///
+    /// A condition has the form `METRIC OP VALUE`, where `OP` is one of `<`, `<=`, `>`, `>=`.
+    /// Supported metrics: `cycle_throughput`, `req_throughput`, `row_throughput`,
+    /// `error_count`, `errors_ratio` (fraction of failed cycles), `mean`, `min`, `max`
+    /// and percentiles like `p99` or `p99.9` of the cycle latency. Prefix the latency metrics
+    /// with `req_` to check the request latency instead. Latencies can be given with
+    /// a time unit, bare numbers are milliseconds.
+    ///
+    /// If a baseline is given, `change(METRIC)` checks the relative change from the baseline
+    /// in percent and `regression(METRIC)` checks the p-value of the metric getting worse
+    /// (1.0 if it did not get worse).
+    ///
+    /// If any condition is not met, latte exits with code 3.
+    ///
+    /// Example: `--assert 'p99<20ms' --assert 'regression(cycle_throughput)>0.01'`
+    #[clap(long("assert"), value_name = "CONDITION", number_of_values = 1)]
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+
     /// Path to the workload definition file.
     #[clap(name = "workload", required = true, value_name = "PATH")]
     pub workload: PathBuf,
@@ -516,13 +1556,37 @@ pub struct ListCommand {
 
 #[derive(Parser, Debug)]
 pub struct ShowCommand {
-    /// Path to the JSON report file
-    #[clap(value_name = "PATH")]
-    pub report: PathBuf,
+    /// Path to the JSON report file(s).
+    ///
+    /// If more than one report is given, all of them are compared in one table,
+    /// with one column per report.
+    #[clap(value_name = "PATH", required = true)]
+    pub reports: Vec<PathBuf>,
 
-    /// Optional path to another JSON report file
+    /// Optional path to another JSON report file.
+    /// The baseline report is listed first and used as the reference.
     #[clap(short('b'), long, value_name = "PATH")]
     pub baseline: Option<PathBuf>,
+
+    /// Position of the report the other reports are compared to, starting from 1.
+    /// Defaults to the first report.
+    #[clap(short('r'), long, value_name = "N", conflicts_with = "baseline")]
+    pub reference: Option<NonZeroUsize>,
+
+    /// Maximum number of the slowest traced requests listed for each report
+    #[clap(long, value_name = "COUNT", default_value = "10")]
+    pub traces: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct MergeCommand {
+    /// Paths to the JSON reports of the runs executed at the same time by different clients.
+    #[clap(value_name = "PATH", required = true, num_args = 2..)]
+    pub reports: Vec<PathBuf>,
+
+    /// Path to the merged JSON report.
+    #[clap(short('o'), long, value_name = "PATH")]
+    pub output: PathBuf,
 }
 
 #[derive(Parser, Debug)]
@@ -547,6 +1611,8 @@ pub struct PlotCommand {
     pub reports: Vec<PathBuf>,
 
     /// Plot given response time percentiles. Can be used multiple times.
+    /// If neither percentiles nor throughput are selected, plots the percentiles
+    /// reported by the first run.
     #[clap(short, long("percentile"), number_of_values = 1)]
     pub percentiles: Vec<f64>,
 
@@ -554,11 +1620,105 @@ pub struct PlotCommand {
     #[clap(short, long("throughput"))]
     pub throughput: bool,
 
+    /// Plot the rate of the given counter of each function separately, in addition to
+    /// the total throughput, e.g. `--by-function=errors`. Can be used multiple times.
+    /// Plots the calls per second if the counter is not given.
+    #[clap(
+        long("by-function"),
+        value_enum,
+        value_name = "COUNTER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "calls"
+    )]
+    pub by_function: Vec<FnCounter>,
+
+    /// Plot the rate of errors of each category, e.g. timeouts per second.
+    #[clap(long("errors"))]
+    pub errors: bool,
+
     /// Write output to the given file.
     #[clap(short('o'), long, value_name = "PATH")]
     pub output: Option<PathBuf>,
 }
 
+/// Counter of each function that can be plotted as a rate
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, ValueEnum)]
+pub enum FnCounter {
+    /// Calls per second
+    Calls,
+    /// Failed calls per second
+    Errors,
+    /// Retried requests per second
+    Retries,
+    /// Returned rows per second
+    Rows,
+}
+
+impl FnCounter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FnCounter::Calls => "calls",
+            FnCounter::Errors => "errors",
+            FnCounter::Retries => "retries",
+            FnCounter::Rows => "rows",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// Summary of each report as comma separated values
+    Csv,
+    /// Samples of each report as comma separated values
+    CsvLog,
+    /// Summary and per-function latency tables in Markdown
+    Markdown,
+    /// Self-contained HTML page with summary tables and plots
+    Html,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExportCommand {
+    /// Path to the input JSON report file(s)
+    #[clap(value_name = "PATH", required = true)]
+    pub reports: Vec<PathBuf>,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value = "markdown")]
+    pub format: ExportFormat,
+
+    /// Response time percentiles plotted in the HTML page. Can be used multiple times.
+    /// Defaults to P50 and P99.
+    #[clap(short, long("percentile"), number_of_values = 1)]
+    pub percentiles: Vec<f64>,
+
+    /// Write output to the given file instead of the standard output.
+    #[clap(short('o'), long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct AgentCommand {
+    /// Address to accept the coordinator connections on.
+    /// If only the port is given, listens on the loopback interface only.
+    /// The connections are not authenticated and anyone who can connect may run any workload
+    /// script with the environment of the agent, so listen on a public interface,
+    /// e.g. `0.0.0.0:9190`, only in a trusted network.
+    #[clap(
+        long,
+        default_value = "127.0.0.1:9190",
+        value_name = "[HOST:]PORT",
+        value_parser = parse_loopback_socket_addr
+    )]
+    pub listen: SocketAddr,
+
+    /// Number of worker threads running the assigned workload.
+    /// Defaults to the number of CPUs.
+    #[clap(short('t'), long, value_name = "COUNT")]
+    pub threads: Option<NonZeroUsize>,
+}
+
 #[derive(Parser, Debug)]
 pub struct VersionCommand {
     /// Whether to output version info in json format or not
@@ -610,6 +1770,13 @@ pub enum Command {
     /// Can compare two runs.
     Show(ShowCommand),
 
+    /// Merges the reports of runs executed at the same time by different clients into one report.
+    ///
+    /// The samples of the runs are aligned by their wall-clock time. Counts and throughputs
+    /// are added up and the latency histograms are merged, so the latency percentiles
+    /// of the merged report are exact.
+    Merge(MergeCommand),
+
     /// Exports histograms as a compressed HDR interval log.
     ///
     /// To be used with HdrHistogram (https://github.com/HdrHistogram/HdrHistogram).
@@ -620,6 +1787,20 @@ pub enum Command {
     /// Plots recorded samples. Saves output in SVG format.
     Plot(PlotCommand),
 
+    /// Exports report(s) as CSV, a Markdown table or a self-contained HTML page.
+    ///
+    /// Markdown output is meant to be pasted into pull request descriptions.
+    /// The HTML page contains the summary, the per-function latencies and the plots.
+    Export(ExportCommand),
+
+    /// Runs the benchmarks requested by a coordinator, that is `latte run` with `--agents`.
+    ///
+    /// The coordinator sends the workload script and the run settings. The agent connects
+    /// to the database, warms up, runs its part of the main phase and sends the samples back.
+    /// Secrets such as the database password are not sent by the coordinator and must be set
+    /// in the environment of the agent.
+    Agent(AgentCommand),
+
     /// Outputs Latte and Scylla driver versions
     Version(VersionCommand),
 }
@@ -786,6 +1967,190 @@ mod tests {
         }
     }
 
+    mod run_stage_tests {
+        use super::*;
+
+        #[test]
+        fn full_stage_definition() {
+            let stage: RunStage =
+                "name=ramp,duration=5m,rate=100..2000,concurrency=64,functions=read:0.8;write:0.2"
+                    .parse()
+                    .unwrap();
+            assert_eq!(stage.name.as_deref(), Some("ramp"));
+            assert_eq!(stage.duration.period(), Some(Duration::from_secs(300)));
+            assert_eq!(stage.rate, Some(100.0));
+            assert_eq!(stage.rate_end, Some(2000.0));
+            assert_eq!(stage.concurrency, NonZeroUsize::new(64));
+            assert_eq!(stage.functions.len(), 2);
+            assert_eq!(stage.functions[1].name, "write");
+            assert_eq!(stage.functions[1].weight, 0.2);
+        }
+
+        #[test]
+        fn count_based_stage_with_defaults() {
+            let stage: RunStage = "duration=1000".parse().unwrap();
+            assert_eq!(stage.duration.count(), Some(1000));
+            assert_eq!(stage.name_or_default(2), "stage-3");
+            assert!(stage.rate.is_none());
+            assert!(stage.concurrency.is_none());
+            assert!(stage.functions.is_empty());
+        }
+
+        #[test]
+        fn missing_duration_is_error() {
+            assert!("rate=100".parse::<RunStage>().is_err());
+        }
+
+        #[test]
+        fn ramp_with_count_duration_is_error() {
+            assert!("duration=1000,rate=1..10".parse::<RunStage>().is_err());
+        }
+
+        #[test]
+        fn unknown_option_is_error() {
+            assert!("duration=1s,foo=bar".parse::<RunStage>().is_err());
+        }
+
+        #[test]
+        fn duration_conflicts_with_stages() {
+            let stages = ["run", "--stage", "duration=1s", "w.rn"];
+            assert!(RunCommand::try_parse_from(stages).is_ok());
+            let stages_and_duration = ["run", "--stage", "duration=1s", "-d", "5s", "w.rn"];
+            assert!(RunCommand::try_parse_from(stages_and_duration).is_err());
+        }
+    }
+
+    mod rate_profile_tests {
+        use super::*;
+
+        #[test]
+        fn interpolates_rate_between_points() {
+            let profile: RateProfile = "time,rate\n10s,100\n1m 10s,700\n".parse().unwrap();
+            assert_eq!(profile.rate_at(0.0), 100.0);
+            assert_eq!(profile.rate_at(10.0), 100.0);
+            assert_eq!(profile.rate_at(40.0), 400.0);
+            assert_eq!(profile.rate_at(70.0), 700.0);
+            assert_eq!(profile.rate_at(1000.0), 700.0);
+            assert_eq!(profile.scale_rate(0.5).rate_at(40.0), 200.0);
+        }
+
+        #[test]
+        fn invalid_profiles() {
+            assert!("".parse::<RateProfile>().is_err());
+            assert!("0,100\n0,200".parse::<RateProfile>().is_err());
+            assert!("0,100\n10,0".parse::<RateProfile>().is_err());
+            assert!("0,100\nfoo,200".parse::<RateProfile>().is_err());
+        }
+    }
+
+    mod arrival_tests {
+        use super::*;
+
+        #[test]
+        fn simple_models() {
+            assert_eq!("uniform".parse::<Arrival>(), Ok(Arrival::Uniform));
+            assert_eq!("poisson".parse::<Arrival>(), Ok(Arrival::Poisson));
+            assert!("poisson,rate=10".parse::<Arrival>().is_err());
+            assert!("exponential".parse::<Arrival>().is_err());
+        }
+
+        #[test]
+        fn burst_definition() {
+            let arrival: Arrival = "burst,rate=20000,length=1s,period=10s".parse().unwrap();
+            assert_eq!(
+                arrival,
+                Arrival::Burst {
+                    rate: 20000.0,
+                    length: Duration::from_secs(1),
+                    period: Duration::from_secs(10),
+                }
+            );
+            assert!("burst,rate=20000,period=10s".parse::<Arrival>().is_err());
+            assert!("burst,rate=20000,length=20s,period=10s"
+                .parse::<Arrival>()
+                .is_err());
+        }
+
+        #[test]
+        fn step_definition() {
+            let arrival: Arrival = "step, rate=500, period=1m".parse().unwrap();
+            assert_eq!(
+                arrival.scale_rate(0.5),
+                Arrival::Step {
+                    rate: 250.0,
+                    period: Duration::from_secs(60),
+                }
+            );
+            assert!("step,rate=0,period=1m".parse::<Arrival>().is_err());
+            assert!("step,rate=10,period=0s".parse::<Arrival>().is_err());
+        }
+    }
+
+    mod mix_change_tests {
+        use super::*;
+
+        #[test]
+        fn transition_over_time() {
+            let change: MixChange = "at=0..10m,functions=read:0.5;write:0.5".parse().unwrap();
+            assert!(matches!(change.from, Interval::Time(d) if d.is_zero()));
+            assert!(matches!(change.to, Interval::Time(d) if d == Duration::from_secs(600)));
+            assert_eq!(change.functions.len(), 2);
+            assert_eq!(change.to_string(), "0s..10m, read:0.5;write:0.5");
+        }
+
+        #[test]
+        fn switch_at_cycle() {
+            let change: MixChange = "at=100000,functions=write".parse().unwrap();
+            assert!(matches!(change.from, Interval::Count(100000)));
+            assert!(matches!(change.to, Interval::Count(100000)));
+            assert_eq!(change.to_string(), "at 100000, write:1");
+        }
+
+        #[test]
+        fn invalid_changes() {
+            assert!("functions=read".parse::<MixChange>().is_err());
+            assert!("at=10m".parse::<MixChange>().is_err());
+            assert!("at=10m..1m,functions=read".parse::<MixChange>().is_err());
+            assert!("at=10..1m,functions=read".parse::<MixChange>().is_err());
+            assert!("at=1m,functions=read@100".parse::<MixChange>().is_err());
+            assert!("at=1m,functions=read:0".parse::<MixChange>().is_err());
+        }
+    }
+
+    mod think_time_tests {
+        use super::*;
+
+        #[test]
+        fn distributions() {
+            let fixed = Ok(ThinkTime::Fixed(Duration::from_millis(100)));
+            assert_eq!("100ms".parse::<ThinkTime>(), fixed);
+            assert_eq!("fixed:100ms".parse::<ThinkTime>(), fixed);
+            assert_eq!(
+                "uniform:1s..3s".parse::<ThinkTime>(),
+                Ok(ThinkTime::Uniform(
+                    Duration::from_secs(1),
+                    Duration::from_secs(3)
+                ))
+            );
+            assert_eq!(
+                "exp:2s".parse::<ThinkTime>(),
+                Ok(ThinkTime::Exponential(Duration::from_secs(2)))
+            );
+            assert!("uniform:3s..1s".parse::<ThinkTime>().is_err());
+            assert!("normal:1s".parse::<ThinkTime>().is_err());
+        }
+
+        #[test]
+        fn function_with_own_rate() {
+            let f: WeightedFunction = "write@250".parse().unwrap();
+            assert_eq!(f.name, "write");
+            assert_eq!(f.rate, Some(250.0));
+            assert_eq!(f.to_string(), "write@250");
+            assert_eq!("read:2".parse::<WeightedFunction>().unwrap().rate, None);
+            assert!("write@0".parse::<WeightedFunction>().is_err());
+        }
+    }
+
     mod retry_interval_tests {
         use super::*;
 
@@ -832,4 +2197,56 @@ mod tests {
             assert!(RetryInterval::new("").is_none());
         }
     }
+
+    mod error_rule_tests {
+        use super::*;
+
+        #[test]
+        fn category_and_action() {
+            let rule: ErrorRule = "write-failure=ignore".parse().unwrap();
+            assert_eq!(rule.category, ErrorCategory::WriteFailure);
+            assert_eq!(rule.action, ErrorAction::Ignore);
+            assert_eq!(rule.to_string(), "write_failure=ignore");
+        }
+
+        #[test]
+        fn invalid_rules() {
+            assert!("timeout".parse::<ErrorRule>().is_err());
+            assert!("slowness=retry".parse::<ErrorRule>().is_err());
+            assert!("timeout=panic".parse::<ErrorRule>().is_err());
+        }
+    }
+
+    mod agent_tests {
+        use super::*;
+
+        #[test]
+        fn listen_on_loopback_by_default() {
+            let conf = AgentCommand::try_parse_from(["agent"]).unwrap();
+            assert_eq!(conf.listen, "127.0.0.1:9190".parse().unwrap());
+            let conf = AgentCommand::try_parse_from(["agent", "--listen", "9191"]).unwrap();
+            assert_eq!(conf.listen, "127.0.0.1:9191".parse().unwrap());
+            let conf = AgentCommand::try_parse_from(["agent", "--listen", "0.0.0.0:9191"]).unwrap();
+            assert_eq!(conf.listen, "0.0.0.0:9191".parse().unwrap());
+        }
+    }
+
+    mod plot_tests {
+        use super::*;
+
+        #[test]
+        fn by_function_counters() {
+            let conf = PlotCommand::try_parse_from(["plot", "--by-function", "a.json"]).unwrap();
+            assert_eq!(conf.by_function, vec![FnCounter::Calls]);
+            assert_eq!(conf.reports, vec![PathBuf::from("a.json")]);
+            let args = [
+                "plot",
+                "--by-function=errors",
+                "--by-function=rows",
+                "a.json",
+            ];
+            let conf = PlotCommand::try_parse_from(args).unwrap();
+            assert_eq!(conf.by_function, vec![FnCounter::Errors, FnCounter::Rows]);
+        }
+    }
 }
@@ -3,6 +3,7 @@ use hdrhistogram::serialization::interval_log::IntervalLogWriterError;
 use hdrhistogram::serialization::V2DeflateSerializeError;
 use rune::alloc;
 use rune::runtime::{AccessError, RuntimeError, VmError};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -41,12 +42,21 @@ pub enum LatteError {
     #[error("Error writing HDR log: {0}")]
     HdrLogWrite(#[from] IntervalLogWriterError<V2DeflateSerializeError>),
 
+    #[error("Failed to start metrics server on {0}: {1}")]
+    MetricsServer(SocketAddr, std::io::Error),
+
+    #[error("Distributed run failed on {0}: {1}")]
+    Distributed(String, String),
+
     #[error("Failed to launch external editor {0}: {1}")]
     ExternalEditorLaunch(String, std::io::Error),
 
     #[error("Invalid configuration: {0}")]
     Configuration(String),
 
+    #[error("Interrupted before the main benchmark phase")]
+    Interrupted,
+
     #[error("Memory allocation failure: {0}")]
     OutOfMemory(#[from] alloc::Error),
 
@@ -57,6 +57,7 @@ impl CycleCounter {
 /// Decides when to stop the benchmark execution.
 pub struct BoundedCycleCounter {
     pub duration: config::Interval,
+    first_cycle: u64,
     cycle_start: i64,
     cycle_range_size: u64,
     start_time: Instant,
@@ -70,18 +71,30 @@ impl BoundedCycleCounter {
         BoundedCycleCounter {
             duration,
             start_time: Instant::now(),
+            first_cycle: 0,
             cycle_counter: CycleCounter::new(0),
             cycle_start: cycle_range.0,
             cycle_range_size: cycle_range.1.saturating_sub(cycle_range.0) as u64,
         }
     }
 
+    /// Skips the given number of cycles at the start of the cycle range,
+    /// e.g. to continue after the cycles executed by the previous stage of the run.
+    /// The cycle count of the duration doesn't include the skipped cycles.
+    pub fn starting_at(self, first_cycle: u64) -> Self {
+        BoundedCycleCounter {
+            first_cycle,
+            cycle_counter: CycleCounter::new(first_cycle),
+            ..self
+        }
+    }
+
     /// Returns the next cycle number or `None` if deadline or cycle count was exceeded.
     pub fn next(&mut self) -> Option<i64> {
         match self.duration {
             Interval::Count(count) => {
                 let result = self.cycle_counter.next();
-                if result < count {
+                if result - self.first_cycle < count {
                     Some(self.cycle_number(result))
                 } else {
                     None
@@ -117,7 +130,8 @@ impl BoundedCycleCounter {
 
 #[cfg(test)]
 mod test {
-    use crate::exec::cycle::{CycleCounter, BATCH_SIZE};
+    use crate::config::Interval;
+    use crate::exec::cycle::{BoundedCycleCounter, CycleCounter, BATCH_SIZE};
     use itertools::Itertools;
     use std::collections::BTreeSet;
 
@@ -145,4 +159,11 @@ mod test {
             Vec::<u64>::new()
         )
     }
+    #[test]
+    pub fn bounded_cycle_counter_starting_at_continues_the_cycles() {
+        let duration = Interval::Count(3);
+        let mut counter = BoundedCycleCounter::new(duration, (100, 200)).starting_at(98);
+        let cycles = std::iter::from_fn(|| counter.next()).collect_vec();
+        assert_eq!(cycles, vec![198, 199, 100]);
+    }
 }
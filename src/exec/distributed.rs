@@ -0,0 +1,776 @@
+//! Running a benchmark on many client machines at once.
+//!
+//! The coordinator (`latte run --agents ...`) connects to the agents (`latte agent`)
+//! and sends each of them the workload script, the run settings and the part of the workload
+//! the agent is responsible for: a slice of the cycle range, a share of the rate
+//! and a share of the cycle count. When all agents are ready, the coordinator tells them
+//! to start the main phase at the same moment. The agents send back the statistics
+//! collected by each of their threads in every sampling period, and the coordinator
+//! records them together as if all threads ran in a single process,
+//! so the histograms are merged exactly.
+//!
+//! Messages are exchanged as JSON objects, one per line.
+
+use std::fs;
+#[cfg(feature = "cql")]
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::channel::mpsc::channel;
+use futures::StreamExt;
+use rune::Source;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::{AgentCommand, Arrival, Interval, RateProfile, RunCommand, WeightedFunction};
+use crate::error::{LatteError, Result};
+use crate::exec::interrupt::Interrupt;
+use crate::exec::par_execute_forward;
+use crate::exec::run::{
+    connect_and_prepare, create_sample_writers, find_workload, load_workload_script,
+    main_phase_options, resolve_function_mix, resolve_functions, serve_metrics, warmup,
+};
+use crate::exec::workload::{FnRef, FnStats, Program, Workload, WorkloadStats};
+use crate::report;
+use crate::report::{prometheus, Report, RunConfigCmp};
+use crate::stats::latency::{self, LatencyDistributionRecorder};
+use crate::stats::session::SessionStats;
+use crate::stats::{BenchmarkStats, Recorder};
+
+/// How long before the start of the main phase the agents are told to start it.
+/// Must be long enough for the start message to reach all agents.
+const START_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// Sent by the coordinator to set up the run
+    Setup {
+        conf: serde_json::Value,
+        script: String,
+        lib_script: Option<String>,
+        assignment: Assignment,
+    },
+    /// Sent by the agent after it has connected to the database and warmed up
+    Ready {
+        cluster_name: Option<String>,
+        db_version: Option<String>,
+    },
+    /// Sent by the coordinator to start the main phase at the given time
+    Start { start_time: SystemTime },
+    /// Sent by the coordinator when it gets interrupted with Ctrl-C, to stop the main phase early
+    Interrupt,
+    /// Sent by the agent at the end of each sampling period
+    Sample { threads: Vec<ThreadStats> },
+    /// Sent by the agent after the main phase has finished
+    Done,
+    /// Sent by the agent if the run failed
+    Error { message: String },
+}
+
+/// The part of the workload executed by a single agent
+#[derive(Debug, Serialize, Deserialize)]
+struct Assignment {
+    start_cycle: i64,
+    end_cycle: i64,
+    warmup_duration: Interval,
+    run_duration: Interval,
+    rate: Option<f64>,
+    arrival: Arrival,
+    rate_profile: Option<RateProfile>,
+    functions: Vec<WeightedFunction>,
+}
+
+impl Assignment {
+    /// Splits the workload evenly between `count` agents and returns the part of the agent `index`
+    fn new(conf: &RunCommand, index: usize, count: usize) -> Assignment {
+        let (start_cycle, end_cycle) = split(conf.start_cycle, conf.end_cycle, index, count);
+        Assignment {
+            start_cycle,
+            end_cycle,
+            warmup_duration: split_interval(conf.warmup_duration, index, count),
+            run_duration: split_interval(conf.run_duration, index, count),
+            rate: conf.rate.rate.map(|r| r / count as f64),
+            arrival: conf.rate.arrival.scale_rate(1.0 / count as f64),
+            rate_profile: conf
+                .rate
+                .rate_profile
+                .as_ref()
+                .map(|p| p.scale_rate(1.0 / count as f64)),
+            functions: conf
+                .functions
+                .iter()
+                .map(|f| f.scale_rate(1.0 / count as f64))
+                .collect(),
+        }
+    }
+
+    fn apply(&self, conf: &mut RunCommand) {
+        conf.start_cycle = self.start_cycle;
+        conf.end_cycle = self.end_cycle;
+        conf.warmup_duration = self.warmup_duration;
+        conf.run_duration = self.run_duration;
+        conf.rate.rate = self.rate;
+        conf.rate.arrival = self.arrival;
+        conf.rate.rate_profile = self.rate_profile.clone();
+        conf.functions = self.functions.clone();
+        // The trace log path of the coordinator may not exist on the agent machine,
+        // so each agent saves the traces of its requests in its working directory
+        #[cfg(feature = "cql")]
+        {
+            conf.connection.db.trace_log = conf
+                .id
+                .as_ref()
+                .map(|id| PathBuf::from(format!("latte-{id}.traces.jsonl")));
+        }
+    }
+}
+
+/// Returns the `index`-th of `count` consecutive, equally long parts of the range `start..end`
+fn split(start: i64, end: i64, index: usize, count: usize) -> (i64, i64) {
+    let len = end as i128 - start as i128;
+    let bound = |i: usize| (start as i128 + len * i as i128 / count as i128) as i64;
+    (bound(index), bound(index + 1))
+}
+
+/// Splits the cycle count evenly. Time-based intervals are not split.
+fn split_interval(interval: Interval, index: usize, count: usize) -> Interval {
+    match interval {
+        Interval::Count(n) => {
+            let (start, end) = split(0, n as i64, index, count);
+            Interval::Count((end - start) as u64)
+        }
+        other => other,
+    }
+}
+
+/// Statistics collected by a single thread of an agent in one sampling period.
+/// Times are relative to the start of the main phase.
+#[derive(Debug, Serialize, Deserialize)]
+struct ThreadStats {
+    start_time: Duration,
+    end_time: Duration,
+    function_stats: Vec<FunctionStats>,
+    session_stats: SessionStats,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FunctionStats {
+    name: String,
+    call_count: u64,
+    error_count: u64,
+    call_latency: LatencyDistributionRecorder,
+    start_delay: LatencyDistributionRecorder,
+    late_count: u64,
+    req_count: u64,
+    req_retry_count: u64,
+    row_count: u64,
+}
+
+impl ThreadStats {
+    fn new(start: Instant, stats: WorkloadStats) -> ThreadStats {
+        ThreadStats {
+            start_time: stats.start_time.saturating_duration_since(start),
+            end_time: stats.end_time.saturating_duration_since(start),
+            function_stats: stats
+                .function_stats
+                .into_iter()
+                .map(|fs| FunctionStats {
+                    name: fs.function.name,
+                    call_count: fs.call_count,
+                    error_count: fs.error_count,
+                    call_latency: fs.call_latency,
+                    start_delay: fs.start_delay,
+                    late_count: fs.late_count,
+                    req_count: fs.req_count,
+                    req_retry_count: fs.req_retry_count,
+                    row_count: fs.row_count,
+                })
+                .collect(),
+            session_stats: stats.session_stats,
+        }
+    }
+
+    fn into_workload_stats(self, start: Instant) -> WorkloadStats {
+        WorkloadStats {
+            start_time: start + self.start_time,
+            end_time: start + self.end_time,
+            function_stats: self
+                .function_stats
+                .into_iter()
+                .map(|fs| FnStats {
+                    function: FnRef::new(&fs.name),
+                    call_count: fs.call_count,
+                    error_count: fs.error_count,
+                    call_latency: fs.call_latency,
+                    start_delay: fs.start_delay,
+                    late_count: fs.late_count,
+                    late_threshold: latency::late_threshold(),
+                    req_count: fs.req_count,
+                    req_retry_count: fs.req_retry_count,
+                    row_count: fs.row_count,
+                })
+                .collect(),
+            session_stats: self.session_stats,
+        }
+    }
+}
+
+/// A connection between the coordinator and an agent
+struct Connection {
+    incoming: Incoming,
+    outgoing: Outgoing,
+}
+
+/// The receiving half of a connection
+struct Incoming {
+    peer: String,
+    reader: BufReader<OwnedReadHalf>,
+}
+
+/// The sending half of a connection
+struct Outgoing {
+    peer: String,
+    writer: OwnedWriteHalf,
+}
+
+impl Connection {
+    async fn connect(addr: &str) -> Result<Connection> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| LatteError::Distributed(addr.to_string(), e.to_string()))?;
+        Ok(Connection::new(addr.to_string(), stream))
+    }
+
+    fn new(peer: String, stream: TcpStream) -> Connection {
+        let _ = stream.set_nodelay(true);
+        let (reader, writer) = stream.into_split();
+        Connection {
+            incoming: Incoming {
+                peer: peer.clone(),
+                reader: BufReader::new(reader),
+            },
+            outgoing: Outgoing { peer, writer },
+        }
+    }
+
+    /// Splits the connection, so that messages can be received and sent at the same time
+    fn into_split(self) -> (Incoming, Outgoing) {
+        (self.incoming, self.outgoing)
+    }
+
+    async fn send(&mut self, message: &Message) -> Result<()> {
+        self.outgoing.send(message).await
+    }
+
+    async fn receive(&mut self) -> Result<Message> {
+        self.incoming.receive().await
+    }
+
+    fn error(&self, e: impl ToString) -> LatteError {
+        self.incoming.error(e)
+    }
+
+    fn unexpected(&self, message: Message) -> LatteError {
+        self.incoming.unexpected(message)
+    }
+}
+
+impl Incoming {
+    /// Receives the next message.
+    /// Returns an error if the connection was closed or the peer reported an error.
+    async fn receive(&mut self) -> Result<Message> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line).await {
+            Ok(0) => Err(self.error("Connection closed")),
+            Ok(_) => match serde_json::from_str(&line) {
+                Ok(Message::Error { message }) => Err(self.error(message)),
+                Ok(message) => Ok(message),
+                Err(e) => Err(self.error(e)),
+            },
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    fn error(&self, e: impl ToString) -> LatteError {
+        LatteError::Distributed(self.peer.clone(), e.to_string())
+    }
+
+    fn unexpected(&self, message: Message) -> LatteError {
+        self.error(format!("Unexpected message: {message:?}"))
+    }
+}
+
+impl Outgoing {
+    async fn send(&mut self, message: &Message) -> Result<()> {
+        let mut line = serde_json::to_string(message).map_err(|e| self.error(e))?;
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    fn error(&self, e: impl ToString) -> LatteError {
+        LatteError::Distributed(self.peer.clone(), e.to_string())
+    }
+}
+
+async fn sleep_until(time: SystemTime) {
+    if let Ok(delay) = time.duration_since(SystemTime::now()) {
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Runs the main phase of the benchmark on the agents listed in the configuration.
+/// Returns the statistics merged from all agents.
+pub async fn coordinate(conf: &mut RunCommand, compare: Option<&Report>) -> Result<BenchmarkStats> {
+    // Catch errors in the script before sending it out
+    let program = load_workload_script(&conf.workload, &conf.params)?;
+    resolve_functions(&program, &conf.functions);
+    resolve_function_mix(&program, &conf.function_mix);
+
+    let path = find_workload(&conf.workload)
+        .canonicalize()
+        .unwrap_or_else(|_| conf.workload.clone());
+    let script =
+        fs::read_to_string(&path).map_err(|e| LatteError::ScriptRead(path.clone(), e.into()))?;
+    let lib_path = path.with_file_name("lib.rn");
+    let lib_script = if lib_path.is_file() {
+        let lib_script = fs::read_to_string(&lib_path)
+            .map_err(|e| LatteError::ScriptRead(lib_path.clone(), e.into()))?;
+        Some(lib_script)
+    } else {
+        None
+    };
+
+    let count = conf.agents.len();
+    if (conf.end_cycle as i128 - conf.start_cycle as i128) < count as i128 {
+        return Err(LatteError::Configuration(format!(
+            "Cycle range {}..{} is too small to be split between {} agents",
+            conf.start_cycle, conf.end_cycle, count
+        )));
+    }
+    let conf_json = serde_json::to_value(&*conf)
+        .map_err(|e| LatteError::Configuration(format!("Failed to serialize settings: {e}")))?;
+
+    let mut agents = Vec::with_capacity(count);
+    for (index, addr) in conf.agents.iter().enumerate() {
+        eprintln!("info: Connecting to agent {addr}...");
+        let mut agent = Connection::connect(addr).await?;
+        let setup = Message::Setup {
+            conf: conf_json.clone(),
+            script: script.clone(),
+            lib_script: lib_script.clone(),
+            assignment: Assignment::new(conf, index, count),
+        };
+        agent.send(&setup).await?;
+        agents.push(agent);
+    }
+    eprintln!("info: Waiting for the agents to warm up...");
+    for agent in &mut agents {
+        match agent.receive().await? {
+            Message::Ready {
+                cluster_name,
+                db_version,
+            } => {
+                conf.cluster_name = conf.cluster_name.take().or(cluster_name);
+                conf.db_version = conf.db_version.take().or(db_version);
+            }
+            other => return Err(agent.unexpected(other)),
+        }
+    }
+    serve_metrics(conf).await?;
+
+    eprintln!("info: Running benchmark on {count} agents...");
+    println!("{}", RunConfigCmp::new(conf, compare.map(|c| &c.conf)));
+
+    let interrupt = Interrupt::ctrl_c();
+    let start_time = SystemTime::now() + START_DELAY;
+    for agent in &mut agents {
+        agent.send(&Message::Start { start_time }).await?;
+    }
+    let (mut incoming, mut outgoing): (Vec<_>, Vec<_>) =
+        agents.into_iter().map(Connection::into_split).unzip();
+
+    report::print_log_header();
+    let (mut writers, _hdrh_guard) = create_sample_writers(conf)?;
+    sleep_until(start_time).await;
+    let mut recorder = Recorder::start(
+        conf.rate.rate,
+        conf.concurrency,
+        conf.generate_report,
+        &mut writers,
+    )
+    .with_rate_profile(conf.rate.rate_profile.clone());
+    tokio::select! {
+        result = collect_samples(
+            &mut incoming,
+            Instant::now(),
+            conf.sampling_interval,
+            &mut recorder,
+        ) => result?,
+        () = forward_interrupt(interrupt, &mut outgoing) => unreachable!(),
+    }
+    Ok(recorder.finish())
+}
+
+/// Tells the agents to stop the main phase when the coordinator gets interrupted.
+/// The agents then send the samples of the pending requests and finish as usual.
+/// Never completes.
+async fn forward_interrupt(interrupt: Interrupt, agents: &mut [Outgoing]) {
+    interrupt.wait().await;
+    for agent in agents {
+        if let Err(e) = agent.send(&Message::Interrupt).await {
+            eprintln!("warning: Failed to interrupt the agent: {e}");
+        }
+    }
+    std::future::pending().await
+}
+
+/// Receives the samples from all agents, one sampling period at a time,
+/// and records the statistics of all agent threads together.
+/// Returns when all agents have finished.
+async fn collect_samples(
+    agents: &mut [Incoming],
+    start: Instant,
+    sampling: Interval,
+    recorder: &mut Recorder<'_>,
+) -> Result<()> {
+    let mut running = vec![true; agents.len()];
+    loop {
+        let mut partial_stats = Vec::new();
+        for (agent, running) in agents.iter_mut().zip(running.iter_mut()) {
+            if !*running {
+                continue;
+            }
+            match agent.receive().await? {
+                Message::Sample { threads } => {
+                    partial_stats.extend(threads.into_iter().map(|t| t.into_workload_stats(start)))
+                }
+                Message::Done => *running = false,
+                other => return Err(agent.unexpected(other)),
+            }
+        }
+        if partial_stats.is_empty() {
+            break Ok(());
+        }
+
+        let aggregate = recorder.record(&partial_stats);
+        prometheus::publish(aggregate);
+        if sampling.is_bounded() {
+            println!("{aggregate}");
+        }
+    }
+}
+
+/// Waits for the coordinators and runs the benchmarks they request, one at a time.
+pub async fn agent(conf: AgentCommand) -> Result<()> {
+    let listener = TcpListener::bind(conf.listen)
+        .await
+        .map_err(|e| LatteError::Distributed(conf.listen.to_string(), e.to_string()))?;
+    if !conf.listen.ip().is_loopback() {
+        eprintln!(
+            "warning: Listening on {} without authentication. \
+            Anyone who can connect may run workload scripts on this machine.",
+            conf.listen
+        );
+    }
+    loop {
+        eprintln!(
+            "info: Waiting for the coordinator on {}...",
+            listener.local_addr().unwrap_or(conf.listen)
+        );
+        let (stream, addr) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("error: Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        eprintln!("info: Accepted connection from coordinator {addr}");
+        let mut connection = Connection::new(addr.to_string(), stream);
+        match serve(&mut connection).await {
+            Ok(()) => eprintln!("info: Finished the run of coordinator {addr}"),
+            Err(e) => {
+                eprintln!("error: {e}");
+                let message = Message::Error {
+                    message: e.to_string(),
+                };
+                let _ = connection.send(&message).await;
+            }
+        }
+    }
+}
+
+/// Runs a single benchmark requested by the coordinator
+async fn serve(coordinator: &mut Connection) -> Result<()> {
+    Interrupt::reset();
+    let (conf, script, lib_script, assignment) = match coordinator.receive().await? {
+        Message::Setup {
+            conf,
+            script,
+            lib_script,
+            assignment,
+        } => (conf, script, lib_script, assignment),
+        other => return Err(coordinator.unexpected(other)),
+    };
+    let mut conf: RunCommand = serde_json::from_value(conf).map_err(|e| coordinator.error(e))?;
+    assignment.apply(&mut conf);
+    conf.connection.db.secrets_from_env();
+    latency::configure(&conf.latency);
+
+    eprintln!(
+        "info: Loading workload script {} received from the coordinator...",
+        conf.workload.display()
+    );
+    let source = Source::new(conf.workload.to_string_lossy(), script)?;
+    let lib_source = lib_script.map(|s| Source::new("lib.rn", s)).transpose()?;
+    let mut program =
+        Program::from_sources(source, lib_source, conf.params.iter().cloned().collect())?;
+    let functions = resolve_functions(&program, &conf.functions);
+
+    let session = connect_and_prepare(&mut conf, &mut program).await?;
+    let mix = resolve_function_mix(&program, &conf.function_mix);
+    let runner = Workload::new(session.clone()?, program, &functions).with_function_mix(mix);
+    warmup(&conf, &runner).await?;
+    let ready = Message::Ready {
+        cluster_name: conf.cluster_name.clone(),
+        db_version: conf.db_version.clone(),
+    };
+    coordinator.send(&ready).await?;
+
+    let start_time = match coordinator.receive().await? {
+        Message::Start { start_time } => start_time,
+        other => return Err(coordinator.unexpected(other)),
+    };
+    sleep_until(start_time).await;
+
+    eprintln!(
+        "info: Running cycles {}..{}...",
+        conf.start_cycle, conf.end_cycle
+    );
+    let exec_options = main_phase_options(&conf);
+    let start = Instant::now();
+    let (tx, mut rx) = channel(1);
+    let execution = par_execute_forward(
+        "Running...",
+        &exec_options,
+        conf.sampling_interval,
+        runner,
+        !conf.quiet,
+        tx,
+    );
+    let Connection { incoming, outgoing } = coordinator;
+    let forwarding = async {
+        while let Some(stats) = rx.next().await {
+            let threads = stats
+                .into_iter()
+                .map(|s| ThreadStats::new(start, s))
+                .collect();
+            outgoing.send(&Message::Sample { threads }).await?;
+        }
+        Ok::<_, LatteError>(())
+    };
+    let running = async {
+        let (executed, forwarded) = tokio::join!(execution, forwarding);
+        executed?;
+        forwarded
+    };
+    let result = tokio::select! {
+        result = running => result,
+        e = receive_interrupt(incoming) => Err(e),
+    };
+    #[cfg(feature = "cql")]
+    session.finish_tracing().await;
+    result?;
+    outgoing.send(&Message::Done).await
+}
+
+/// Receives the interrupt forwarded by the coordinator during the main phase
+/// and signals it as if Ctrl-C was pressed on the agent.
+/// Completes only if the connection fails or an unexpected message is received.
+async fn receive_interrupt(coordinator: &mut Incoming) -> LatteError {
+    loop {
+        match coordinator.receive().await {
+            Ok(Message::Interrupt) => {
+                eprintln!(
+                    "info: Interrupted by the coordinator. \
+                    Waiting for the pending requests to complete."
+                );
+                Interrupt::signal();
+            }
+            Ok(other) => return coordinator.unexpected(other),
+            Err(e) => return e,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stats::SampleWriters;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn split_range_between_agents() {
+        assert_eq!(split(0, 10, 0, 3), (0, 3));
+        assert_eq!(split(0, 10, 1, 3), (3, 6));
+        assert_eq!(split(0, 10, 2, 3), (6, 10));
+        assert_eq!(split(0, i64::MAX, 1, 2), (i64::MAX / 2, i64::MAX));
+        assert_eq!(split(i64::MIN, i64::MAX, 1, 2), (-1, i64::MAX));
+        assert!(matches!(
+            split_interval(Interval::Count(10), 2, 3),
+            Interval::Count(4)
+        ));
+    }
+
+    /// Simulates an agent sending `samples` samples of two threads,
+    /// each thread making 10 calls of the given latency
+    async fn fake_agent(listener: TcpListener, latency: Duration, samples: usize) {
+        let (stream, addr) = listener.accept().await.unwrap();
+        let mut coordinator = Connection::new(addr.to_string(), stream);
+        let start = Instant::now();
+        for _ in 0..samples {
+            let threads = (0..2)
+                .map(|_| {
+                    let mut fn_stats = FnStats::new(FnRef::new("run"));
+                    let mut session_stats = SessionStats::new();
+                    for _ in 0..10 {
+                        fn_stats.operation_completed(latency);
+                        session_stats.start_request();
+                        session_stats.complete_request("read", latency, 1);
+                    }
+                    let stats = WorkloadStats {
+                        start_time: start,
+                        end_time: start + Duration::from_secs(1),
+                        function_stats: vec![fn_stats],
+                        session_stats,
+                    };
+                    ThreadStats::new(start, stats)
+                })
+                .collect();
+            coordinator
+                .send(&Message::Sample { threads })
+                .await
+                .unwrap();
+        }
+        coordinator.send(&Message::Done).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn merge_samples_from_agents() {
+        let mut agents = Vec::new();
+        for (latency_ms, samples) in [(1, 3), (100, 2)] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            tokio::spawn(fake_agent(
+                listener,
+                Duration::from_millis(latency_ms),
+                samples,
+            ));
+            let (incoming, _) = Connection::connect(&addr).await.unwrap().into_split();
+            agents.push(incoming);
+        }
+
+        let mut writers = SampleWriters::default();
+        let mut recorder = Recorder::start(None, NonZeroUsize::MIN, true, &mut writers);
+        collect_samples(
+            &mut agents,
+            Instant::now(),
+            Interval::Unbounded,
+            &mut recorder,
+        )
+        .await
+        .unwrap();
+        let stats = recorder.finish();
+
+        assert_eq!(stats.log.len(), 3);
+        assert_eq!(stats.log[0].cycle_count, 40);
+        assert_eq!(stats.log[2].cycle_count, 20);
+        assert_eq!(stats.cycle_count, 100);
+        assert_eq!(stats.request_count, 100);
+        assert_eq!(stats.row_count, 100);
+        let histogram = &stats.cycle_latency.histogram.0;
+        assert_eq!(histogram.len(), 100);
+        assert_eq!(histogram.count_between(0, 2_000_000), 60);
+        assert!(histogram.max() >= 100_000_000);
+    }
+
+    #[tokio::test]
+    async fn coordinator_forwards_interrupt_to_agents() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (_, mut outgoing) = Connection::connect(&addr).await.unwrap().into_split();
+        let (stream, addr) = listener.accept().await.unwrap();
+        let mut agent = Connection::new(addr.to_string(), stream);
+
+        let (sender, interrupt) = crate::exec::interrupt::manual();
+        sender.send_replace(true);
+        tokio::select! {
+            () = forward_interrupt(interrupt, std::slice::from_mut(&mut outgoing)) => unreachable!(),
+            message = agent.receive() => assert!(matches!(message, Ok(Message::Interrupt))),
+        }
+    }
+
+    #[test]
+    fn agent_applies_assignment_to_received_settings() {
+        use clap::Parser;
+        let args = [
+            "run",
+            "-d",
+            "1000",
+            "-r",
+            "300",
+            "--arrival",
+            "step,rate=600,period=1s",
+            "-f",
+            "read:2",
+            "-f",
+            "write@30",
+            "--end-cycle",
+            "100",
+            "w.rn",
+        ];
+        let mut conf = RunCommand::try_parse_from(args).unwrap();
+        conf.id = Some("20261018-120000".to_string());
+        #[cfg(feature = "cql")]
+        {
+            conf.connection.db.trace_log = Some(PathBuf::from("/coordinator/traces.jsonl"));
+        }
+        let setup = Message::Setup {
+            conf: serde_json::to_value(&conf).unwrap(),
+            script: "pub async fn run(ctx, i) {}".to_string(),
+            lib_script: None,
+            assignment: Assignment::new(&conf, 1, 3),
+        };
+        let json = serde_json::to_string(&setup).unwrap();
+        let Message::Setup {
+            conf, assignment, ..
+        } = serde_json::from_str(&json).unwrap()
+        else {
+            panic!("Expected the setup message");
+        };
+        let mut conf: RunCommand = serde_json::from_value(conf).unwrap();
+        assignment.apply(&mut conf);
+        assert_eq!((conf.start_cycle, conf.end_cycle), (33, 66));
+        assert_eq!(conf.rate.rate, Some(100.0));
+        assert_eq!(
+            conf.rate.arrival,
+            Arrival::Step {
+                rate: 200.0,
+                period: Duration::from_secs(1)
+            }
+        );
+        assert_eq!(conf.functions[0].weight, 2.0);
+        assert_eq!(conf.functions[1].rate, Some(10.0));
+        assert!(matches!(conf.run_duration, Interval::Count(333)));
+        #[cfg(feature = "cql")]
+        assert_eq!(
+            conf.connection.db.trace_log,
+            Some(PathBuf::from("latte-20261018-120000.traces.jsonl"))
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Graceful handling of Ctrl-C during the benchmark run.
+//!
+//! The first Ctrl-C stops starting new cycles and lets the pending ones complete,
+//! so that the statistics gathered so far can be reported.
+//! The second Ctrl-C aborts the process immediately.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tokio::signal::ctrl_c;
+use tokio::sync::watch;
+
+/// Exit code used when the run is aborted by the second Ctrl-C, as if killed by SIGINT
+const ABORT_EXIT_CODE: i32 = 130;
+
+static CTRL_C: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+/// Signals that the run has been interrupted.
+#[derive(Clone, Debug)]
+pub struct Interrupt(watch::Receiver<bool>);
+
+impl Interrupt {
+    /// Returns the interrupt signalled by the first Ctrl-C received by the process.
+    /// Installs the Ctrl-C handler on the first call, so it must be called within
+    /// the tokio runtime.
+    pub fn ctrl_c() -> Interrupt {
+        Interrupt(ctrl_c_sender().subscribe())
+    }
+
+    /// Signals the interrupt as if Ctrl-C was pressed, e.g. when the coordinator forwards its
+    /// interrupt to an agent. Unlike a second Ctrl-C, signalling it again doesn't abort the process.
+    pub fn signal() {
+        ctrl_c_sender().send_replace(true);
+    }
+
+    /// Clears the interrupt, so the next run of a long-lived process, like an agent,
+    /// is not stopped by the Ctrl-C received during the previous one
+    pub fn reset() {
+        if let Some(sender) = CTRL_C.get() {
+            sender.send_replace(false);
+        }
+    }
+
+    /// Returns true if the Ctrl-C handler is installed and Ctrl-C has been pressed
+    pub fn is_ctrl_c_pressed() -> bool {
+        CTRL_C.get().is_some_and(|sender| *sender.borrow())
+    }
+
+    /// Returns true if the interrupt has been signalled
+    pub fn is_set(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes when the interrupt gets signalled
+    pub async fn wait(mut self) {
+        if self.0.wait_for(|interrupted| *interrupted).await.is_err() {
+            // The sender is gone, so the interrupt can never be signalled
+            std::future::pending::<()>().await
+        }
+    }
+
+    /// Completes after the interrupt gets signalled and the pending requests get
+    /// the given time to complete
+    pub async fn drain_deadline(self, drain_timeout: Duration) {
+        self.wait().await;
+        tokio::time::sleep(drain_timeout).await
+    }
+}
+
+/// Installs the Ctrl-C handler on the first call and returns the sender of the interrupt
+fn ctrl_c_sender() -> &'static watch::Sender<bool> {
+    CTRL_C.get_or_init(|| {
+        let (sender, _) = watch::channel(false);
+        tokio::spawn(handle_ctrl_c(sender.clone()));
+        sender
+    })
+}
+
+/// Returns an interrupt signalled by the returned sender instead of Ctrl-C
+#[cfg(test)]
+pub fn manual() -> (watch::Sender<bool>, Interrupt) {
+    let (sender, receiver) = watch::channel(false);
+    (sender, Interrupt(receiver))
+}
+
+async fn handle_ctrl_c(sender: watch::Sender<bool>) {
+    while ctrl_c().await.is_ok() {
+        if sender.send_replace(true) {
+            eprintln!("error: Aborted");
+            std::process::exit(ABORT_EXIT_CODE);
+        }
+        eprintln!(
+            "info: Interrupted. Waiting for the pending requests to complete. \
+            Press Ctrl-C again to abort."
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+
+    /// Runs cycles lasting `cycle_time` at most 4 at a time, interrupted after 250 ms
+    async fn run_interrupted(cycle_time: Duration, drain_timeout: Duration) -> Vec<u32> {
+        let (sender, interrupt) = manual();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            sender.send_replace(true);
+            // Keep the channel open till the end of the test
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        });
+        futures::stream::iter(0..)
+            .take_until(interrupt.clone().wait())
+            .map(|cycle| async move {
+                tokio::time::sleep(cycle_time).await;
+                cycle
+            })
+            .buffer_unordered(4)
+            .take_until(interrupt.drain_deadline(drain_timeout))
+            .collect()
+            .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pending_cycles_complete_after_interrupt() {
+        let cycles = run_interrupted(Duration::from_millis(100), Duration::from_secs(1)).await;
+        // Cycles 8..12 were started at 200 ms and complete after the interrupt
+        assert_eq!(cycles.len(), 12);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pending_cycles_are_dropped_after_drain_timeout() {
+        let cycles = run_interrupted(Duration::from_secs(10), Duration::from_secs(1)).await;
+        assert!(cycles.is_empty());
+    }
+}
@@ -1,30 +1,42 @@
 //! Implementation of the main benchmarking loop
 
 use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::stream::BoxStream;
 use futures::{pin_mut, Future, SinkExt, Stream, StreamExt};
 use itertools::Itertools;
 use pin_project::pin_project;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::Exp1;
 use status_line::StatusLine;
 use std::f64::consts;
 use std::future::ready;
 use std::io::IsTerminal;
 use std::num::NonZeroUsize;
+use std::ops::Range;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
-use tokio::signal::ctrl_c;
 
+use crate::config::{Arrival, RateProfile, ThinkTime};
 use crate::error::{LatteError, Result};
-use crate::stats::histogram::HistogramWriter;
+use crate::report::prometheus;
+use crate::stats::SampleWriters;
 use crate::{
     BenchmarkStats, BoundedCycleCounter, Interval, Progress, Recorder, Workload, WorkloadStats,
 };
 use chunks::ChunksExt;
+use interrupt::Interrupt;
+use workload::FnRef;
 
 mod chunks;
 pub mod cycle;
+pub mod distributed;
+pub mod interrupt;
 pub mod progress;
+pub mod run;
+pub mod search;
 pub mod workload;
 
 /// Infinite iterator returning floats that form a sinusoidal wave
@@ -32,6 +44,10 @@ pub struct InfiniteSinusoidalIterator {
     rate: f64,
     amplitude: f64,
     step: f64,
+    /// Optional linear change of the base rate: the total rate change and its duration in seconds
+    ramp: Option<(f64, f64)>,
+    /// Optional base rate changing in time, replacing the constant rate and the ramp
+    profile: Option<RateProfile>,
     start: Instant,
 }
 
@@ -42,53 +58,171 @@ impl InfiniteSinusoidalIterator {
             rate,
             amplitude,
             step,
+            ramp: None,
+            profile: None,
             start: Instant::now(),
         }
     }
+
+    /// Makes the base rate change linearly from the initial rate to `rate_end`
+    /// over the given `duration`. After that time the rate stays at `rate_end`.
+    pub fn with_ramp(mut self, rate_end: f64, duration: Duration) -> InfiniteSinusoidalIterator {
+        self.ramp = Some((rate_end - self.rate, duration.as_secs_f64()));
+        self
+    }
+
+    /// Makes the base rate follow the given profile
+    pub fn with_profile(mut self, profile: RateProfile) -> InfiniteSinusoidalIterator {
+        self.profile = Some(profile);
+        self
+    }
+
+    fn base_rate(&self, elapsed: f64) -> f64 {
+        if let Some(profile) = &self.profile {
+            return profile.rate_at(elapsed);
+        }
+        match self.ramp {
+            Some((delta, duration)) if duration > 0.0 => {
+                self.rate + delta * (elapsed / duration).min(1.0)
+            }
+            Some((delta, _)) => self.rate + delta,
+            None => self.rate,
+        }
+    }
 }
 
 impl Iterator for InfiniteSinusoidalIterator {
     type Item = f64;
 
     fn next(&mut self) -> Option<f64> {
-        if self.amplitude == 0.0 {
+        if self.amplitude == 0.0 && self.ramp.is_none() && self.profile.is_none() {
             return Some(self.rate);
         }
         let elapsed = self.start.elapsed().as_secs_f64();
-        let adjusted_rate = self.rate + self.amplitude * (self.step * elapsed).sin();
+        let adjusted_rate = self.base_rate(elapsed) + self.amplitude * (self.step * elapsed).sin();
         Some(adjusted_rate)
     }
 }
 
-/// Custom interval stream for sinusoidal ticking.
-struct SinusoidalIntervalStream {
-    tick_iterator: InfiniteSinusoidalIterator,
+/// Decides when the next cycle should be started, if the rate is limited.
+/// Each thread of execution gets its own schedule.
+pub trait ArrivalSchedule: Send {
+    /// Returns the scheduled start time of the cycle following the one scheduled at `previous`
+    fn next_arrival(&mut self, previous: Instant) -> Instant;
+}
+
+/// Converts the rate in events per second to the interval between the events
+fn rate_to_interval(rate: f64) -> Duration {
+    Duration::from_nanos((1_000_000_000.0 / rate).max(1.0) as u64)
+}
+
+/// Arrivals at equal intervals.
+/// The rate may change in time, following a sine wave or a ramp.
+pub struct UniformArrivals(InfiniteSinusoidalIterator);
+
+impl UniformArrivals {
+    pub fn new(rate: InfiniteSinusoidalIterator) -> Self {
+        UniformArrivals(rate)
+    }
+}
+
+impl ArrivalSchedule for UniformArrivals {
+    fn next_arrival(&mut self, previous: Instant) -> Instant {
+        previous + rate_to_interval(self.0.next().unwrap_or(1.0))
+    }
+}
+
+/// Arrivals forming a Poisson process, with exponentially distributed intervals.
+/// The rate may change in time, following a sine wave or a ramp.
+pub struct PoissonArrivals {
+    rate: InfiniteSinusoidalIterator,
+    rng: SmallRng,
+}
+
+impl PoissonArrivals {
+    pub fn new(rate: InfiniteSinusoidalIterator) -> Self {
+        PoissonArrivals {
+            rate,
+            rng: SmallRng::from_os_rng(),
+        }
+    }
+}
+
+impl ArrivalSchedule for PoissonArrivals {
+    fn next_arrival(&mut self, previous: Instant) -> Instant {
+        let rate = self.rate.next().unwrap_or(1.0);
+        let intervals: f64 = self.rng.sample(Exp1);
+        previous + rate_to_interval(rate / intervals)
+    }
+}
+
+/// Arrivals at equal intervals, at a rate switching between a base rate and a high rate.
+/// The high rate applies within the `high` range of offsets from the beginning of each period.
+pub struct SquareWaveArrivals {
+    base_rate: InfiniteSinusoidalIterator,
+    high_rate: f64,
+    high: Range<Duration>,
+    period: Duration,
+    start: Instant,
+}
+
+impl SquareWaveArrivals {
+    pub fn new(
+        base_rate: InfiniteSinusoidalIterator,
+        high_rate: f64,
+        high: Range<Duration>,
+        period: Duration,
+    ) -> Self {
+        SquareWaveArrivals {
+            base_rate,
+            high_rate,
+            high,
+            period,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl ArrivalSchedule for SquareWaveArrivals {
+    fn next_arrival(&mut self, previous: Instant) -> Instant {
+        let period = self.period.as_nanos();
+        let offset = previous.saturating_duration_since(self.start).as_nanos() % period;
+        let offset = Duration::from_nanos(offset as u64);
+        let (rate, phase_end) = if offset < self.high.start {
+            (self.base_rate.next().unwrap_or(1.0), self.high.start)
+        } else if offset < self.high.end {
+            (self.high_rate, self.high.end)
+        } else {
+            (self.base_rate.next().unwrap_or(1.0), self.period)
+        };
+        // Don't let a long interval at a low rate delay the beginning of the next phase
+        let interval = rate_to_interval(rate).min(phase_end - offset);
+        previous + interval
+    }
+}
+
+/// Stream of the scheduled start times of the cycles, following an [`ArrivalSchedule`].
+struct ArrivalStream {
+    schedule: Box<dyn ArrivalSchedule>,
     next_expected_tick: Instant,
     sleep: Pin<Box<tokio::time::Sleep>>,
 }
 
-impl Unpin for SinusoidalIntervalStream {}
+impl Unpin for ArrivalStream {}
 
-impl SinusoidalIntervalStream {
-    fn new(rate: f64, amplitude: f64, frequency: f64) -> Self {
-        let tick_iterator = InfiniteSinusoidalIterator::new(rate, amplitude, frequency);
+impl ArrivalStream {
+    fn new(mut schedule: Box<dyn ArrivalSchedule>) -> Self {
         let now = Instant::now();
-        let initial_duration = tokio::time::Duration::from_secs_f64(1.0 / rate);
+        let first_tick = schedule.next_arrival(now);
         Self {
-            tick_iterator,
-            next_expected_tick: now + initial_duration,
-            sleep: Box::pin(tokio::time::sleep(initial_duration)),
+            schedule,
+            next_expected_tick: first_tick,
+            sleep: Box::pin(tokio::time::sleep(first_tick - now)),
         }
     }
-
-    fn next_interval_duration(&mut self) -> tokio::time::Duration {
-        let adjusted_rate = self.tick_iterator.next();
-        let period_ns = (1_000_000_000.0 / adjusted_rate.unwrap_or(1.0)).max(1.0) as u64;
-        tokio::time::Duration::from_nanos(period_ns)
-    }
 }
 
-impl Stream for SinusoidalIntervalStream {
+impl Stream for ArrivalStream {
     // NOTE: pass through the 'scheduled time' for further
     //       coordinated omission fixed latency calculations.
     type Item = Instant;
@@ -96,74 +230,203 @@ impl Stream for SinusoidalIntervalStream {
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let now = Instant::now();
         let current_expected_tick = self.next_expected_tick;
-        if now >= self.next_expected_tick {
-            let interval_duration = self.next_interval_duration();
-            self.next_expected_tick += interval_duration;
+        if now >= current_expected_tick {
+            // NOTE: If we are behind, ticks keep being emitted without sleeping until we catch up
+            self.next_expected_tick = self.schedule.next_arrival(current_expected_tick);
             Poll::Ready(Some(current_expected_tick))
         } else {
-            // NOTE: If we are behind, keep trying to emit ticks until we catch up
-            let interval_duration = self.next_interval_duration();
-            let next_tick = self.next_expected_tick + interval_duration;
-            if next_tick <= now {
-                self.next_expected_tick = next_tick;
-                Poll::Ready(Some(current_expected_tick))
-            } else {
-                // NOTE: Reuse the pinned Sleep timer instead of spawning a new task per tick
-                let sleep_duration = self.next_expected_tick - now;
-                self.sleep
-                    .as_mut()
-                    .reset(tokio::time::Instant::now() + sleep_duration);
-                let _ = self.sleep.as_mut().poll(cx);
-                Poll::Pending
+            // NOTE: Reuse the pinned Sleep timer instead of spawning a new task per tick
+            let sleep_duration = current_expected_tick - now;
+            self.sleep
+                .as_mut()
+                .reset(tokio::time::Instant::now() + sleep_duration);
+            if self.sleep.as_mut().poll(cx).is_ready() {
+                cx.waker().wake_by_ref();
             }
+            Poll::Pending
         }
     }
 }
 
-/// Returns a stream emitting sinusoidally changing number of `rate` events per second.
-fn sinusoidal_interval_stream(
-    rate: f64,
-    amplitude: f64,
-    frequency: f64,
-) -> impl Stream<Item = Instant> {
-    SinusoidalIntervalStream::new(rate, amplitude, frequency)
+/// Creates the arrival schedule of a single thread, with the base rate given by `base_rate`.
+/// The rates of the arrival model must be given per thread.
+fn arrival_schedule(
+    arrival: Arrival,
+    base_rate: InfiniteSinusoidalIterator,
+) -> Box<dyn ArrivalSchedule> {
+    match arrival {
+        Arrival::Uniform => Box::new(UniformArrivals::new(base_rate)),
+        Arrival::Poisson => Box::new(PoissonArrivals::new(base_rate)),
+        Arrival::Burst {
+            rate,
+            length,
+            period,
+        } => Box::new(SquareWaveArrivals::new(
+            base_rate,
+            rate,
+            Duration::ZERO..length,
+            period,
+        )),
+        Arrival::Step { rate, period } => Box::new(SquareWaveArrivals::new(
+            base_rate,
+            rate,
+            period / 2..period,
+            period,
+        )),
+    }
+}
+
+/// Pauses taken by a virtual user between consecutive cycles
+struct ThinkTimes {
+    distribution: ThinkTime,
+    rng: SmallRng,
+}
+
+impl ThinkTimes {
+    fn new(distribution: ThinkTime) -> Self {
+        ThinkTimes {
+            distribution,
+            rng: SmallRng::from_os_rng(),
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        match self.distribution {
+            ThinkTime::Fixed(pause) => pause,
+            ThinkTime::Uniform(min, max) => self.rng.random_range(min..=max),
+            ThinkTime::Exponential(mean) => mean.mul_f64(self.rng.sample(Exp1)),
+        }
+    }
+}
+
+/// Decides when the cycles of a single thread get started
+struct CycleSchedule {
+    /// Start times of the cycles of the weighted functions.
+    /// If not given, the cycles are started as fast as possible.
+    weighted: Option<Box<dyn ArrivalSchedule>>,
+    /// Start times of the cycles of the functions invoked at their own rates
+    paced: Vec<(FnRef, Box<dyn ArrivalSchedule>)>,
+    /// If given, the weighted functions are invoked by virtual users pausing between the cycles
+    think_time: Option<ThinkTime>,
+}
+
+/// Returns a stream of the results of cycles run by a virtual user.
+/// The user runs one cycle at a time and pauses for the think time after each cycle.
+/// The user stops starting new cycles when interrupted.
+fn virtual_user(
+    workload: &Workload,
+    cycle_counter: BoundedCycleCounter,
+    think_time: ThinkTime,
+    interrupt: Interrupt,
+) -> impl Stream<Item = Result<(i64, Instant)>> + '_ {
+    let state = (cycle_counter, ThinkTimes::new(think_time), false);
+    futures::stream::unfold(state, move |(mut counter, mut pauses, pause)| {
+        let interrupt = interrupt.clone();
+        async move {
+            if pause {
+                tokio::select! {
+                    _ = tokio::time::sleep(pauses.next()) => {}
+                    _ = interrupt.clone().wait() => {}
+                }
+            }
+            if interrupt.is_set() {
+                return None;
+            }
+            let cycle = counter.next()?;
+            let result = tokio::task::unconstrained(workload.run(cycle, Instant::now())).await;
+            Some((result, (counter, pauses, true)))
+        }
+    })
 }
 
 /// Runs a stream of workload cycles till completion in the context of the current task.
 /// Periodically sends workload statistics to the `out` channel.
 ///
 /// # Parameters
-/// - stream: a stream of cycle numbers; None means the end of the stream
+/// - schedule: controls when to start the cycles of each function
 /// - workload: defines the function to call
 /// - cycle_counter: shared cycle numbers provider
 /// - concurrency: the maximum number of pending workload calls
 /// - sampling: controls when to output workload statistics
 /// - progress: progress bar notified about each successful cycle
 /// - out: the channel to receive workload statistics
+/// - interrupt: stops starting new cycles; the pending cycles get `drain_timeout` to complete
 ///
+#[allow(clippy::too_many_arguments)]
 async fn run_stream(
-    stream: impl Stream<Item = Instant> + std::marker::Unpin,
+    schedule: CycleSchedule,
     workload: Workload,
     cycle_counter: BoundedCycleCounter,
     concurrency: NonZeroUsize,
     sampling: Interval,
     progress: Arc<StatusLine<Progress>>,
     mut out: Sender<Result<WorkloadStats>>,
+    interrupt: Interrupt,
+    drain_timeout: Duration,
 ) {
-    let mut iter_counter = cycle_counter;
     let sample_size = sampling.count().unwrap_or(u64::MAX);
     let sample_duration = sampling.period().unwrap_or(tokio::time::Duration::MAX);
+    let workload = &workload;
 
-    let stats_stream = stream
-        .map(|scheduled_time| iter_counter.next().map(|cycle| (cycle, scheduled_time)))
+    // Cycles started at the scheduled times, regardless of how long the previous cycles took.
+    // NOTE: pass through the 'scheduled time' for further
+    //       coordinated omission fixed latency calculations.
+    let mut open_loop: Vec<BoxStream<(Instant, Option<FnRef>)>> = Vec::new();
+    let mut closed_loop = Vec::new();
+    if workload.has_weighted_functions() {
+        match (schedule.think_time, schedule.weighted) {
+            (Some(think_time), _) => {
+                for _ in 0..concurrency.get() {
+                    let user = virtual_user(
+                        workload,
+                        cycle_counter.share(),
+                        think_time,
+                        interrupt.clone(),
+                    );
+                    closed_loop.push(Box::pin(user));
+                }
+            }
+            (None, Some(weighted)) => {
+                open_loop.push(ArrivalStream::new(weighted).map(|t| (t, None)).boxed());
+            }
+            (None, None) => {
+                let stream = futures::stream::repeat_with(|| (Instant::now(), None));
+                open_loop.push(stream.boxed());
+            }
+        }
+    }
+    for (function, paced) in schedule.paced {
+        let stream = ArrivalStream::new(paced).map(move |t| (t, Some(function.clone())));
+        open_loop.push(stream.boxed());
+    }
+
+    let mut iter_counter = cycle_counter;
+    let open_loop = futures::stream::select_all(open_loop)
+        .take_until(interrupt.clone().wait())
+        .map(|(scheduled_time, function)| {
+            iter_counter
+                .next()
+                .map(|cycle| (cycle, scheduled_time, function))
+        })
         .take_while(|opt| ready(opt.is_some()))
         .map(|opt| {
-            let (cycle, scheduled_time) = opt.unwrap();
-            tokio::task::unconstrained(workload.run(cycle, scheduled_time))
+            let (cycle, scheduled_time, function) = opt.unwrap();
+            tokio::task::unconstrained(async move {
+                match function {
+                    Some(function) => {
+                        workload
+                            .run_function(cycle, scheduled_time, &function)
+                            .await
+                    }
+                    None => workload.run(cycle, scheduled_time).await,
+                }
+            })
         })
-        .buffer_unordered(concurrency.get())
+        .buffer_unordered(concurrency.get());
+
+    let stats_stream = futures::stream::select(open_loop, futures::stream::select_all(closed_loop))
         .inspect(|_| progress.tick())
-        .take_until(ctrl_c())
+        .take_until(interrupt.drain_deadline(drain_timeout))
         .terminate_after_error()
         .chunks_aggregated(sample_size, sample_duration, Vec::new, |errors, result| {
             if let Err(e) = result {
@@ -187,60 +450,43 @@ async fn run_stream(
     }
 }
 
+/// Receives the statistics published periodically by a worker task
+type StatsReceiver = Receiver<Result<WorkloadStats>>;
+
 /// Launches a new worker task that runs a series of invocations of the workload function.
 ///
 /// The task will run as long as `deadline` produces new cycle numbers.
+/// The cycles are started according to the `schedule`.
 /// The task updates the `progress` bar after each successful cycle.
 ///
+/// The task stops starting new cycles when interrupted.
+///
 /// Returns a stream where workload statistics are published.
 #[allow(clippy::too_many_arguments)]
 fn spawn_stream(
     concurrency: NonZeroUsize,
-    rate: Option<f64>,
-    rate_sine_amplitude: Option<f64>, // Enables the sine wave if set
-    rate_sine_frequency: f64,
+    schedule: CycleSchedule,
     sampling: Interval,
     workload: Workload,
     iter_counter: BoundedCycleCounter,
     progress: Arc<StatusLine<Progress>>,
-) -> Receiver<Result<WorkloadStats>> {
+    interrupt: Interrupt,
+    drain_timeout: Duration,
+) -> StatsReceiver {
     let (tx, rx) = channel(1);
-
     tokio::spawn(async move {
-        match rate {
-            Some(rate) => {
-                // NOTE: if 'rate_sine_amplitude' is empty or 0.0
-                //       then it will behave like common uniform rate limiter.
-                let stream = sinusoidal_interval_stream(
-                    rate,
-                    rate_sine_amplitude.unwrap_or(0.0) * rate, // transform to absolute value
-                    rate_sine_frequency,
-                );
-                run_stream(
-                    stream,
-                    workload,
-                    iter_counter,
-                    concurrency,
-                    sampling,
-                    progress,
-                    tx,
-                )
-                .await
-            }
-            None => {
-                let stream = futures::stream::repeat_with(Instant::now);
-                run_stream(
-                    stream,
-                    workload,
-                    iter_counter,
-                    concurrency,
-                    sampling,
-                    progress,
-                    tx,
-                )
-                .await
-            }
-        }
+        run_stream(
+            schedule,
+            workload,
+            iter_counter,
+            concurrency,
+            sampling,
+            progress,
+            tx,
+            interrupt,
+            drain_timeout,
+        )
+        .await
     });
     rx
 }
@@ -261,6 +507,7 @@ where
 }
 
 /// Controls the intensity of requests sent to the server
+#[derive(Clone)]
 pub struct ExecutionOptions {
     /// How long to execute
     pub duration: Interval,
@@ -272,10 +519,24 @@ pub struct ExecutionOptions {
     pub rate_sine_amplitude: Option<f64>,
     /// Rate sine wave period
     pub rate_sine_period: Duration,
+    /// Model of the arrival times of the cycles, used if the rate is limited
+    pub arrival: Arrival,
+    /// Rate reached at the end of a time-bounded execution, changing linearly from `rate`
+    pub rate_end: Option<f64>,
+    /// Rate changing in time, used instead of `rate`
+    pub rate_profile: Option<RateProfile>,
+    /// Functions invoked at their own rates instead of sharing the main stream of cycles,
+    /// and their rates
+    pub function_rates: Vec<(FnRef, f64)>,
+    /// If set, the weighted functions are invoked by virtual users pausing between the cycles,
+    /// instead of being started at the rate
+    pub think_time: Option<ThinkTime>,
     /// Number of parallel threads of execution
     pub threads: NonZeroUsize,
     /// Number of outstanding async requests per each thread
     pub concurrency: NonZeroUsize,
+    /// How long to wait for the pending requests to complete after Ctrl-C
+    pub drain_timeout: Duration,
 }
 
 /// Executes the given function many times in parallel.
@@ -294,8 +555,153 @@ pub async fn par_execute(
     workload: Workload,
     show_progress: bool,
     keep_log: bool,
-    hdrh_writer: &mut Option<Box<dyn HistogramWriter>>,
+    writers: &mut SampleWriters,
 ) -> Result<BenchmarkStats> {
+    let rate_limit = exec_options
+        .rate
+        .filter(|_| exec_options.rate_end.is_none());
+    let mut stats = Recorder::start(rate_limit, exec_options.concurrency, keep_log, writers)
+        .with_rate_profile(exec_options.rate_profile.clone());
+    execute_phase(
+        name,
+        exec_options,
+        0,
+        sampling,
+        workload,
+        show_progress,
+        &mut stats,
+        None,
+    )
+    .await?;
+    Ok(stats.finish())
+}
+
+/// A single stage of a multi-stage benchmark run.
+pub struct Stage {
+    pub name: String,
+    pub exec_options: ExecutionOptions,
+    pub workload: Workload,
+}
+
+/// Executes the given stages one after another.
+/// Returns the statistics of the whole run and the statistics of each stage separately.
+/// If interrupted, the remaining stages are skipped.
+/// The sampling log is kept only in the statistics of the whole run.
+pub async fn par_execute_stages(
+    stages: Vec<Stage>,
+    sampling: Interval,
+    show_progress: bool,
+    keep_log: bool,
+    writers: &mut SampleWriters,
+) -> Result<(BenchmarkStats, Vec<BenchmarkStats>)> {
+    let concurrency = stages
+        .iter()
+        .map(|s| s.exec_options.concurrency)
+        .max()
+        .unwrap_or(NonZeroUsize::MIN);
+    let mut stats = Recorder::start(None, concurrency, keep_log, writers);
+    let mut stage_stats = Vec::with_capacity(stages.len());
+    for stage in stages {
+        let options = &stage.exec_options;
+        let rate_limit = options.rate.filter(|_| options.rate_end.is_none());
+        let mut no_writers = SampleWriters::default();
+        let mut recorder = Recorder::start(rate_limit, options.concurrency, false, &mut no_writers);
+        eprintln!("info: Running stage {}...", stage.name);
+        // Continue with the cycles following the ones executed by the previous stages
+        let first_cycle = stats.cycle_count;
+        execute_phase(
+            &stage.name,
+            options,
+            first_cycle,
+            sampling,
+            stage.workload,
+            show_progress,
+            &mut stats,
+            Some(&mut recorder),
+        )
+        .await?;
+        stage_stats.push(recorder.finish());
+        if Interrupt::is_ctrl_c_pressed() {
+            break;
+        }
+    }
+    Ok((stats.finish(), stage_stats))
+}
+
+/// Runs the workload on all threads until the configured duration elapses and
+/// records the samples in the `stats` recorder and, optionally, in the `stage_stats` recorder.
+/// The cycles start at `first_cycle` cycles past the start of the cycle range.
+#[allow(clippy::too_many_arguments)]
+async fn execute_phase(
+    name: &str,
+    exec_options: &ExecutionOptions,
+    first_cycle: u64,
+    sampling: Interval,
+    workload: Workload,
+    show_progress: bool,
+    stats: &mut Recorder<'_>,
+    mut stage_stats: Option<&mut Recorder<'_>>,
+) -> Result<()> {
+    let (mut streams, progress) = spawn_threads(
+        name,
+        exec_options,
+        first_cycle,
+        sampling,
+        workload,
+        show_progress,
+    )?;
+    loop {
+        let partial_stats = receive_one_of_each(&mut streams).await;
+        let partial_stats: Vec<_> = partial_stats.into_iter().try_collect()?;
+        if partial_stats.is_empty() {
+            break Ok(());
+        }
+
+        if let Some(stage_stats) = stage_stats.as_mut() {
+            stage_stats.record(&partial_stats);
+        }
+        let aggregate = stats.record(&partial_stats);
+        prometheus::publish(aggregate);
+        if sampling.is_bounded() {
+            progress.set_visible(false);
+            println!("{aggregate}");
+            progress.set_visible(show_progress);
+        }
+    }
+}
+
+/// Runs the workload on all threads until the configured duration elapses, like [`par_execute`],
+/// but instead of recording the statistics, sends the statistics collected by all threads
+/// in each sampling period to `out`.
+pub async fn par_execute_forward(
+    name: &str,
+    exec_options: &ExecutionOptions,
+    sampling: Interval,
+    workload: Workload,
+    show_progress: bool,
+    mut out: Sender<Vec<WorkloadStats>>,
+) -> Result<()> {
+    let (mut streams, _progress) =
+        spawn_threads(name, exec_options, 0, sampling, workload, show_progress)?;
+    loop {
+        let partial_stats = receive_one_of_each(&mut streams).await;
+        let partial_stats: Vec<_> = partial_stats.into_iter().try_collect()?;
+        if partial_stats.is_empty() || out.send(partial_stats).await.is_err() {
+            break Ok(());
+        }
+    }
+}
+
+/// Spawns the worker threads running the workload.
+/// Returns the streams of statistics published by each thread and the progress bar.
+fn spawn_threads(
+    name: &str,
+    exec_options: &ExecutionOptions,
+    first_cycle: u64,
+    sampling: Interval,
+    workload: Workload,
+    show_progress: bool,
+) -> Result<(Vec<StatsReceiver>, Arc<StatusLine<Progress>>)> {
     if exec_options.cycle_range.1 <= exec_options.cycle_range.0 {
         return Err(LatteError::Configuration(format!(
             "End cycle {} must not be lower than start cycle {}",
@@ -303,11 +709,33 @@ pub async fn par_execute(
         )));
     }
 
+    if !workload.has_weighted_functions() && exec_options.function_rates.is_empty() {
+        return Err(LatteError::Configuration(
+            "At least one function must have a weight greater than 0 or its own rate".to_string(),
+        ));
+    }
+
     let thread_count = exec_options.threads.get();
     let concurrency = exec_options.concurrency;
     let rate = exec_options.rate;
+    let rate_profile = exec_options.rate_profile.as_ref();
+    if rate.is_none() && rate_profile.is_none() && exec_options.arrival != Arrival::Uniform {
+        return Err(LatteError::Configuration(format!(
+            "Arrival model `{}` requires the rate to be set",
+            exec_options.arrival
+        )));
+    }
     let rate_sine_amplitude = exec_options.rate_sine_amplitude;
     let rate_sine_frequency = 1.0 / exec_options.rate_sine_period.as_secs_f64();
+    let rate_ramp = match (exec_options.rate_end, exec_options.duration.period()) {
+        (Some(rate_end), Some(duration)) => Some((rate_end / thread_count as f64, duration)),
+        (Some(_), None) => {
+            return Err(LatteError::Configuration(
+                "Rate ramp requires a time-based duration".to_string(),
+            ))
+        }
+        (None, _) => None,
+    };
     let progress = match exec_options.duration {
         Interval::Count(count) => Progress::with_count(name.to_string(), count),
         Interval::Time(duration) => Progress::with_duration(name.to_string(), duration),
@@ -320,38 +748,69 @@ pub async fn par_execute(
         enable_ansi_escapes: is_tty,
     };
     let progress = Arc::new(StatusLine::with_options(progress, progress_opts));
-    let deadline = BoundedCycleCounter::new(exec_options.duration, exec_options.cycle_range);
+    let deadline = BoundedCycleCounter::new(exec_options.duration, exec_options.cycle_range)
+        .starting_at(first_cycle);
+    let interrupt = Interrupt::ctrl_c();
     let mut streams = Vec::with_capacity(thread_count);
-    let mut stats = Recorder::start(rate, concurrency, keep_log, hdrh_writer);
 
     for _ in 0..thread_count {
+        let thread_share = 1.0 / thread_count as f64;
+        let base_rate = match (rate, rate_profile) {
+            (_, Some(profile)) => {
+                let profile = profile.scale_rate(thread_share);
+                Some(
+                    InfiniteSinusoidalIterator::new(profile.rate_at(0.0), 0.0, 0.0)
+                        .with_profile(profile),
+                )
+            }
+            (Some(rate), None) => {
+                let rate = rate * thread_share;
+                // NOTE: if 'rate_sine_amplitude' is empty or 0.0
+                //       then it will behave like common uniform rate limiter.
+                let amplitude = rate_sine_amplitude.unwrap_or(0.0) * rate; // transform to absolute value
+                let iterator =
+                    InfiniteSinusoidalIterator::new(rate, amplitude, rate_sine_frequency);
+                Some(match rate_ramp {
+                    Some((rate_end, duration)) => iterator.with_ramp(rate_end, duration),
+                    None => iterator,
+                })
+            }
+            (None, None) => None,
+        };
+        let weighted = base_rate.map(|base_rate| {
+            arrival_schedule(exec_options.arrival.scale_rate(thread_share), base_rate)
+        });
+        // Functions invoked at their own rates follow the Poisson or the uniform model only
+        let paced_arrival = match exec_options.arrival {
+            Arrival::Poisson => Arrival::Poisson,
+            _ => Arrival::Uniform,
+        };
+        let paced = exec_options
+            .function_rates
+            .iter()
+            .map(|(function, rate)| {
+                let rate = InfiniteSinusoidalIterator::new(rate * thread_share, 0.0, 0.0);
+                (function.clone(), arrival_schedule(paced_arrival, rate))
+            })
+            .collect();
+        let schedule = CycleSchedule {
+            weighted,
+            paced,
+            think_time: exec_options.think_time,
+        };
         let s = spawn_stream(
             concurrency,
-            rate.map(|r| r / (thread_count as f64)),
-            rate_sine_amplitude,
-            rate_sine_frequency,
+            schedule,
             sampling,
             workload.clone()?,
             deadline.share(),
             progress.clone(),
+            interrupt.clone(),
+            exec_options.drain_timeout,
         );
         streams.push(s);
     }
-
-    loop {
-        let partial_stats = receive_one_of_each(&mut streams).await;
-        let partial_stats: Vec<_> = partial_stats.into_iter().try_collect()?;
-        if partial_stats.is_empty() {
-            break Ok(stats.finish());
-        }
-
-        let aggregate = stats.record(&partial_stats);
-        if sampling.is_bounded() {
-            progress.set_visible(false);
-            println!("{aggregate}");
-            progress.set_visible(show_progress);
-        }
-    }
+    Ok((streams, progress))
 }
 
 trait TerminateAfterErrorExt: Stream + Sized {
@@ -411,16 +870,38 @@ mod test {
         assert_eq!(s.collect::<Vec<_>>().await, vec![Ok(1), Ok(2), Err(3)])
     }
 
-    mod sinusoidal_interval_stream_test {
-        use crate::exec::SinusoidalIntervalStream;
+    mod arrival_stream_test {
+        use crate::config::ThinkTime;
+        use crate::exec::{
+            ArrivalSchedule, ArrivalStream, InfiniteSinusoidalIterator, PoissonArrivals,
+            SquareWaveArrivals, ThinkTimes, UniformArrivals,
+        };
         use futures::StreamExt;
         use std::time::{Duration, Instant};
 
+        fn uniform(rate: f64, amplitude: f64, frequency: f64) -> ArrivalStream {
+            let rate = InfiniteSinusoidalIterator::new(rate, amplitude, frequency);
+            ArrivalStream::new(Box::new(UniformArrivals::new(rate)))
+        }
+
+        /// Returns the intervals between `count` consecutive arrivals following `start`, in seconds
+        fn intervals(schedule: &mut dyn ArrivalSchedule, start: Instant, count: usize) -> Vec<f64> {
+            let mut time = start;
+            (0..count)
+                .map(|_| {
+                    let next = schedule.next_arrival(time);
+                    let interval = next.duration_since(time).as_secs_f64();
+                    time = next;
+                    interval
+                })
+                .collect()
+        }
+
         /// The stream must emit ticks at approximately the requested rate.
         #[tokio::test]
         async fn emits_ticks_at_expected_rate() {
             let rate = 5000.0; // 5000 ops/sec → 0.2ms per tick
-            let mut stream = SinusoidalIntervalStream::new(rate, 0.0, 0.0);
+            let mut stream = uniform(rate, 0.0, 0.0);
             let mut count = 0u32;
             let run_for = Duration::from_millis(50);
             let deadline = Instant::now() + run_for;
@@ -443,7 +924,7 @@ mod test {
         /// were not registered, the stream would hang forever.
         #[tokio::test]
         async fn waker_is_registered_correctly() {
-            let mut stream = SinusoidalIntervalStream::new(1000.0, 0.0, 0.0);
+            let mut stream = uniform(1000.0, 0.0, 0.0);
             let tick = tokio::time::timeout(Duration::from_millis(100), stream.next()).await;
             assert!(
                 tick.is_ok() && tick.unwrap().is_some(),
@@ -456,7 +937,7 @@ mod test {
         #[tokio::test]
         async fn catches_up_after_delay() {
             let rate = 2000.0; // 0.5ms per tick
-            let mut stream = SinusoidalIntervalStream::new(rate, 0.0, 0.0);
+            let mut stream = uniform(rate, 0.0, 0.0);
             // Consume first tick
             let _ = stream.next().await;
             // Simulate a slow consumer: sleep 10ms → ~20 ticks accumulate
@@ -487,7 +968,7 @@ mod test {
         #[tokio::test]
         async fn constant_rate_produces_uniform_ticks() {
             let rate = 2000.0; // 0.5ms per tick
-            let mut stream = SinusoidalIntervalStream::new(rate, 0.0, 0.0);
+            let mut stream = uniform(rate, 0.0, 0.0);
             let mut timestamps = Vec::new();
             for _ in 0..20 {
                 if let Some(tick_time) = stream.next().await {
@@ -505,11 +986,37 @@ mod test {
             }
         }
 
+        /// With a rate ramp, the tick rate should change from the initial to the final rate.
+        #[tokio::test]
+        async fn ramp_changes_tick_rate() {
+            use assert_approx_eq::assert_approx_eq;
+            let iterator = InfiniteSinusoidalIterator::new(100.0, 0.0, 0.0)
+                .with_ramp(1000.0, Duration::from_millis(20));
+            assert_approx_eq!(iterator.base_rate(0.0), 100.0);
+            assert_approx_eq!(iterator.base_rate(0.01), 550.0);
+            assert_approx_eq!(iterator.base_rate(1.0), 1000.0);
+
+            let mut stream = ArrivalStream::new(Box::new(UniformArrivals::new(iterator)));
+            let _ = stream.next().await;
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let mut timestamps = Vec::new();
+            for _ in 0..10 {
+                if let Some(t) = stream.next().await {
+                    timestamps.push(t);
+                }
+            }
+            let last = timestamps[9].duration_since(timestamps[8]);
+            assert!(
+                last < Duration::from_millis(5),
+                "expected ~1ms tick interval after the ramp, got {last:?}"
+            );
+        }
+
         /// With sinusoidal amplitude, tick intervals should visibly vary.
         #[tokio::test]
         async fn sinusoidal_amplitude_varies_tick_rate() {
             // rate=1000, amplitude=500 → oscillates between 500 and 1500 Hz
-            let mut stream = SinusoidalIntervalStream::new(1000.0, 500.0, 2.0);
+            let mut stream = uniform(1000.0, 500.0, 2.0);
             let mut timestamps = Vec::new();
             for _ in 0..50 {
                 if let Some(t) = stream.next().await {
@@ -527,5 +1034,73 @@ mod test {
                 "expected varying tick intervals with amplitude, but min={min:?} max={max:?}"
             );
         }
+
+        /// Poisson intervals should have the mean and the standard deviation of 1 / rate.
+        #[test]
+        fn poisson_intervals_are_exponential() {
+            let rate = InfiniteSinusoidalIterator::new(1000.0, 0.0, 0.0);
+            let intervals = intervals(&mut PoissonArrivals::new(rate), Instant::now(), 100000);
+            let n = intervals.len() as f64;
+            let mean = intervals.iter().sum::<f64>() / n;
+            let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+            assert!((mean - 0.001).abs() < 0.00005, "mean interval {mean}");
+            assert!(
+                (variance.sqrt() - 0.001).abs() < 0.0001,
+                "std dev {}",
+                variance.sqrt()
+            );
+        }
+
+        /// Bursts should raise the rate only for the burst length at the beginning of each period.
+        #[test]
+        fn burst_switches_rate_at_phase_boundaries() {
+            let base_rate = InfiniteSinusoidalIterator::new(10.0, 0.0, 0.0);
+            let mut schedule = SquareWaveArrivals::new(
+                base_rate,
+                1000.0,
+                Duration::ZERO..Duration::from_millis(100),
+                Duration::from_secs(1),
+            );
+            let start = schedule.start;
+            let intervals = intervals(&mut schedule, start, 110);
+            // 100 arrivals in the burst, then 9 arrivals at the base rate until the next burst
+            assert!(intervals[..99].iter().all(|i| (i - 0.001).abs() < 1e-6));
+            assert!(intervals[100..108].iter().all(|i| (i - 0.1).abs() < 1e-6));
+            assert!((intervals[109] - 0.001).abs() < 1e-6);
+            let total: f64 = intervals[..109].iter().sum();
+            assert!((total - 1.0).abs() < 0.0001, "period {total}");
+        }
+
+        /// A long interval at the base rate must not delay the beginning of the high phase.
+        #[test]
+        fn step_starts_high_phase_on_time() {
+            let base_rate = InfiniteSinusoidalIterator::new(3.0, 0.0, 0.0);
+            let mut schedule = SquareWaveArrivals::new(
+                base_rate,
+                100.0,
+                Duration::from_millis(500)..Duration::from_secs(1),
+                Duration::from_secs(1),
+            );
+            let start = schedule.start;
+            let intervals = intervals(&mut schedule, start, 3);
+            assert!((intervals[0] - 1.0 / 3.0).abs() < 1e-6);
+            assert!((intervals[1] - 1.0 / 6.0).abs() < 1e-6);
+            assert!((intervals[2] - 0.01).abs() < 1e-6);
+        }
+
+        #[test]
+        fn think_times_follow_distribution() {
+            let (min, max) = (Duration::from_millis(100), Duration::from_millis(300));
+            let mut pauses = ThinkTimes::new(ThinkTime::Uniform(min, max));
+            assert!((0..1000)
+                .map(|_| pauses.next())
+                .all(|p| p >= min && p <= max));
+
+            let mean = Duration::from_millis(50);
+            let mut pauses = ThinkTimes::new(ThinkTime::Exponential(mean));
+            let total: Duration = (0..10000).map(|_| pauses.next()).sum();
+            let avg = total.as_secs_f64() / 10000.0;
+            assert!((avg - 0.05).abs() < 0.005, "mean pause {avg}");
+        }
     }
 }
@@ -0,0 +1,269 @@
+//! Steps of a benchmark run shared by the local runs and by the agents of distributed runs.
+
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::SystemTime;
+
+use hdrhistogram::serialization::{interval_log, V2DeflateSerializer};
+use itertools::Itertools;
+use rune::Source;
+use search_path::SearchPath;
+use tracing::info;
+use tracing_appender::non_blocking::WorkerGuard;
+
+use crate::config::{ConnectionConf, Interval, MixChange, RunCommand, WeightedFunction};
+use crate::error::{LatteError, Result};
+use crate::exec::workload::{FnRef, MixTransition, Program, Workload};
+use crate::exec::{par_execute, ExecutionOptions};
+use crate::report;
+use crate::scripting;
+use crate::scripting::cluster_info::ClusterInfo;
+use crate::scripting::context::Context;
+use crate::stats::sample_log::create_sample_writer;
+use crate::stats::SampleWriters;
+
+/// Reads the workload script from a file and compiles it.
+pub fn load_workload_script(workload: &Path, params: &[(String, String)]) -> Result<Program> {
+    let workload = find_workload(workload)
+        .canonicalize()
+        .unwrap_or_else(|_| workload.to_path_buf());
+    eprintln!("info: Loading workload script {}...", workload.display());
+    let src =
+        Source::from_path(&workload).map_err(|e| LatteError::ScriptRead(workload.clone(), e))?;
+    let program = Program::new(src, params.iter().cloned().collect())?;
+    info!(
+        "Loaded workload script {}\n\tParams: {:?}",
+        workload.display(),
+        params,
+    );
+    Ok(program)
+}
+
+/// Locates the workload and returns an absolute path to it.
+/// If not found, returns the original path unchanged.
+/// If the workload path is relative, it is searched in the directories
+/// listed by `LATTE_WORKLOAD_PATH` environment variable.
+/// If the variable is not set, workload is searched in
+/// `.local/share/latte/workloads` and `/usr/share/latte/workloads`.
+pub fn find_workload(workload: &Path) -> PathBuf {
+    if workload.starts_with(".") || workload.is_absolute() {
+        return workload.to_path_buf();
+    }
+    let search_path = SearchPath::new("LATTE_WORKLOAD_PATH").unwrap_or_else(|_| {
+        let relative_to_exe = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .map(|p| p.join("workloads"));
+        SearchPath::from(
+            [
+                PathBuf::from(".local/share/latte/workloads"),
+                PathBuf::from("/usr/share/latte/workloads"),
+            ]
+            .into_iter()
+            .chain(relative_to_exe)
+            .collect_vec(),
+        )
+    });
+    search_path
+        .find_file(workload)
+        .unwrap_or_else(|| workload.to_path_buf())
+}
+
+/// Connects to the server and returns the session
+pub async fn connect(conf: &ConnectionConf) -> Result<(Context, Option<ClusterInfo>)> {
+    eprintln!("info: Connecting to {:?}... ", conf.addresses);
+    let session = scripting::connect::connect(conf).await?;
+    let cluster_info = session.cluster_info().await?;
+    eprintln!(
+        "info: Connected to '{}' cluster running {}",
+        cluster_info
+            .as_ref()
+            .map(|c| c.name.as_str())
+            .unwrap_or("unknown"),
+        cluster_info
+            .as_ref()
+            .map(|c| c.db_version.as_str())
+            .unwrap_or("unknown")
+    );
+    Ok((session, cluster_info))
+}
+
+/// Returns the functions invoked at their own rates, together with their rates.
+pub fn function_rates(functions: &[WeightedFunction]) -> Vec<(FnRef, f64)> {
+    functions
+        .iter()
+        .filter_map(|f| f.rate.map(|rate| (FnRef::new(f.name.as_str()), rate)))
+        .collect()
+}
+
+/// Looks up the given functions in the workload script.
+/// Exits with error if any of the functions is not present.
+pub fn resolve_functions(program: &Program, functions: &[WeightedFunction]) -> Vec<(FnRef, f64)> {
+    let mut result = Vec::new();
+    for f in functions {
+        let function = FnRef::new(f.name.as_str());
+        if !program.has_function(&function) {
+            eprintln!(
+                "error: Function {} not found in the workload script.",
+                f.name.as_str()
+            );
+            exit(255);
+        }
+        result.push((function, f.weight))
+    }
+    result
+}
+
+/// Looks up the functions of the function mix changes in the workload script.
+/// Exits with error if any of the functions is not present.
+pub fn resolve_function_mix(program: &Program, mix: &[MixChange]) -> Vec<MixTransition> {
+    mix.iter()
+        .map(|change| MixTransition {
+            from: change.from,
+            to: change.to,
+            functions: resolve_functions(program, &change.functions),
+        })
+        .collect()
+}
+
+/// Connects to the database and runs the `prepare` function of the workload script.
+/// Records the information about the cluster in `conf`.
+/// Exits with error if `prepare` fails.
+pub async fn connect_and_prepare(conf: &mut RunCommand, program: &mut Program) -> Result<Context> {
+    let functions_to_invoke = conf
+        .functions
+        .iter()
+        .chain(conf.stages.iter().flat_map(|s| s.functions.iter()))
+        .chain(conf.function_mix.iter().flat_map(|m| m.functions.iter()))
+        .map(|f| (f.name.as_str(), f.weight))
+        .collect_vec();
+
+    let (session, cluster_info) = connect(&conf.connection).await?;
+
+    // NOTE: Add info about the target rune functions to the context
+    //       for the more flexible tweaking of the 'prepare' rune function.
+    match session.data.borrow_mut::<rune::runtime::Object>() {
+        Ok(mut obj) => {
+            let _ = obj.insert(
+                rune::alloc::String::try_from("functions_to_invoke")?,
+                rune::to_value(functions_to_invoke)?,
+            );
+        }
+        Err(_) => {
+            eprintln!("error: session.data is not a Rune Object");
+            exit(255);
+        }
+    }
+
+    if let Some(cluster_info) = cluster_info {
+        conf.cluster_name = Some(cluster_info.name);
+        conf.db_version = Some(cluster_info.db_version);
+    }
+
+    if program.has_prepare() {
+        eprintln!("info: Preparing...");
+        if let Err(e) = program.prepare(&session).await {
+            eprintln!("error: Failed to prepare: {e}");
+            exit(255);
+        }
+    }
+    Ok(session)
+}
+
+/// Starts serving the live statistics if requested in the configuration.
+pub async fn serve_metrics(conf: &RunCommand) -> Result<()> {
+    if let Some(addr) = conf.metrics_addr {
+        let addr = report::prometheus::serve(addr).await?;
+        eprintln!("info: Serving metrics at http://{addr}/metrics");
+    }
+    Ok(())
+}
+
+/// Runs the warmup phase, if configured.
+pub async fn warmup(conf: &RunCommand, runner: &Workload) -> Result<()> {
+    if conf.warmup_duration.is_not_zero() {
+        eprintln!("info: Warming up...");
+        let warmup_options = ExecutionOptions {
+            duration: conf.warmup_duration,
+            cycle_range: (conf.start_cycle, conf.end_cycle),
+            rate: None,
+            rate_sine_amplitude: conf.rate.rate_sine_amplitude,
+            rate_sine_period: conf.rate.rate_sine_period,
+            arrival: conf.rate.arrival,
+            rate_end: None,
+            rate_profile: None,
+            function_rates: function_rates(&conf.functions),
+            think_time: conf.rate.think_time,
+            threads: conf.threads,
+            concurrency: conf.concurrency,
+            drain_timeout: conf.drain_timeout,
+        };
+        par_execute(
+            "Warming up...",
+            &warmup_options,
+            Interval::Unbounded,
+            runner.clone()?,
+            !conf.quiet,
+            false,
+            &mut SampleWriters::default(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Returns the execution options of the main benchmark phase.
+pub fn main_phase_options(conf: &RunCommand) -> ExecutionOptions {
+    ExecutionOptions {
+        duration: conf.run_duration,
+        cycle_range: (conf.start_cycle, conf.end_cycle),
+        concurrency: conf.concurrency,
+        rate: conf.rate.rate,
+        rate_sine_amplitude: conf.rate.rate_sine_amplitude,
+        rate_sine_period: conf.rate.rate_sine_period,
+        arrival: conf.rate.arrival,
+        rate_end: None,
+        rate_profile: conf.rate.rate_profile.clone(),
+        function_rates: function_rates(&conf.functions),
+        think_time: conf.rate.think_time,
+        threads: conf.threads,
+        drain_timeout: conf.drain_timeout,
+    }
+}
+
+/// Creates the writers of the HDR histogram log and of the sample log, if configured.
+/// The returned guard must be kept alive until the HDR histogram log is complete.
+pub fn create_sample_writers(conf: &RunCommand) -> Result<(SampleWriters, Option<WorkerGuard>)> {
+    let mut writers = SampleWriters::default();
+    let mut hdrh_guard = None;
+    if let Some(ref hdrfile) = conf.hdrfile {
+        let path = Path::new(&hdrfile);
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|e| LatteError::LogFileCreate(hdrfile.clone(), e))?;
+        }
+        let hdrfile = File::create(hdrfile)
+            .map_err(|e| LatteError::LogFileCreate(hdrfile.to_path_buf(), e))?;
+        let (non_blocking_writer, guard) = tracing_appender::non_blocking(hdrfile);
+        hdrh_guard = Some(guard);
+        let non_blocking_writer = Box::new(non_blocking_writer);
+        let serializer = Box::new(V2DeflateSerializer::new());
+        let system_time_now = SystemTime::now();
+        let hdrh_writer = interval_log::IntervalLogWriterBuilder::new()
+            .add_comment(format!("[Logged with Latte {}]", env!("CARGO_PKG_VERSION")).as_str())
+            .with_start_time(system_time_now)
+            .with_base_time(system_time_now)
+            .with_max_value_divisor(1000000.0) // ms
+            .begin_log_with(Box::leak(non_blocking_writer), Box::leak(serializer))
+            .unwrap();
+        writers.hdrh_writer = Some(Box::new(hdrh_writer));
+    }
+    if let Some(ref sample_log) = conf.sample_log {
+        let sample_writer = create_sample_writer(sample_log, conf.sample_log_format)
+            .map_err(|e| LatteError::LogFileCreate(sample_log.clone(), e))?;
+        writers.sample_writer = Some(sample_writer);
+    }
+    Ok((writers, hdrh_guard))
+}
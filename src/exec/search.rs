@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Interval, SearchConf, SearchMode};
+use crate::error::{LatteError, Result};
+use crate::exec::interrupt::Interrupt;
+use crate::exec::workload::Workload;
+use crate::exec::{par_execute, ExecutionOptions};
+use crate::stats::latency::LatencyDistribution;
+use crate::stats::{BenchmarkStats, SampleWriters};
+
+/// Result of running the workload at a single fixed rate during the max rate search.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchProbe {
+    /// Target rate, in cycles per second
+    pub rate: f64,
+    /// Achieved cycle throughput, in cycles per second
+    pub throughput: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p99_ms: f64,
+    pub latency_p99_9_ms: f64,
+    /// Value of the cycle latency percentile the limit is checked against
+    pub latency_ms: f64,
+    /// Percentage of failed cycles
+    pub errors_ratio: f64,
+    pub passed: bool,
+}
+
+impl SearchProbe {
+    pub fn new(conf: &SearchConf, rate: f64, stats: &BenchmarkStats) -> Self {
+        let latency = &stats.cycle_latency;
+        let latency_ms = percentile_ms(latency, conf.percentile);
+        let errors_ratio = stats.errors_ratio.unwrap_or(0.0);
+        let passed = latency_ms <= conf.latency_limit.as_secs_f64() * 1000.0
+            && errors_ratio <= conf.error_limit
+            && stats.cycle_count > 0;
+        SearchProbe {
+            rate,
+            throughput: stats.cycle_throughput.value,
+            latency_p50_ms: percentile_ms(latency, 50.0),
+            latency_p99_ms: percentile_ms(latency, 99.0),
+            latency_p99_9_ms: percentile_ms(latency, 99.9),
+            latency_ms,
+            errors_ratio,
+            passed,
+        }
+    }
+}
+
+fn percentile_ms(latency: &LatencyDistribution, percentile: f64) -> f64 {
+    latency.histogram.0.value_at_percentile(percentile) as f64 / 1_000_000.0
+}
+
+/// Decides which rate to probe next, given the outcomes of the previous probes.
+pub struct RateSearch {
+    mode: SearchMode,
+    step: f64,
+    max_rate: Option<f64>,
+    precision: f64,
+    next: Option<f64>,
+    /// The highest rate that passed
+    passed: Option<f64>,
+    /// The lowest rate that failed
+    failed: Option<f64>,
+}
+
+impl RateSearch {
+    pub fn new(conf: &SearchConf) -> Result<Self> {
+        if conf.start_rate <= 0.0 {
+            return Err(LatteError::Configuration(
+                "Search start rate must be greater than zero".to_string(),
+            ));
+        }
+        let step = conf.step.unwrap_or(conf.start_rate);
+        if step <= 0.0 {
+            return Err(LatteError::Configuration(
+                "Search step must be greater than zero".to_string(),
+            ));
+        }
+        if conf.max_rate.is_some_and(|max| max < conf.start_rate) {
+            return Err(LatteError::Configuration(
+                "Search max rate must not be lower than the start rate".to_string(),
+            ));
+        }
+        Ok(RateSearch {
+            mode: conf.mode,
+            step,
+            max_rate: conf.max_rate,
+            precision: conf.precision,
+            next: Some(conf.start_rate),
+            passed: None,
+            failed: None,
+        })
+    }
+
+    /// Returns the rate to probe next or `None` if the search is finished.
+    pub fn next_rate(&self) -> Option<f64> {
+        self.next
+    }
+
+    /// Records the outcome of probing given rate.
+    pub fn record(&mut self, rate: f64, passed: bool) {
+        if passed {
+            self.passed = Some(self.passed.map_or(rate, |r| r.max(rate)));
+        } else {
+            self.failed = Some(self.failed.map_or(rate, |r| r.min(rate)));
+        }
+        self.next = self.compute_next();
+    }
+
+    /// Returns the highest rate that passed so far.
+    pub fn max_passed_rate(&self) -> Option<f64> {
+        self.passed
+    }
+
+    fn compute_next(&self) -> Option<f64> {
+        match (self.passed, self.failed) {
+            (None, _) => None,
+            (Some(_), Some(_)) if self.mode == SearchMode::Step => None,
+            (Some(lo), Some(hi)) if hi - lo > self.precision * lo => Some((lo + hi) / 2.0),
+            (Some(_), Some(_)) => None,
+            (Some(lo), None) => {
+                let next = match self.mode {
+                    SearchMode::Binary => lo * 2.0,
+                    SearchMode::Step => lo + self.step,
+                };
+                match self.max_rate {
+                    Some(max) if lo >= max => None,
+                    Some(max) => Some(next.min(max)),
+                    None => Some(next),
+                }
+            }
+        }
+    }
+}
+
+/// Runs the workload at increasing rates until the highest rate that meets
+/// the latency and error limits is found.
+///
+/// Returns the statistics of the highest passing probe (or of the first probe, if no probe
+/// passed) and the list of all probes in the order they were executed.
+/// If interrupted, the search stops and the interrupted probe is not included in the list.
+pub async fn find_max_rate(
+    conf: &SearchConf,
+    exec_options: &ExecutionOptions,
+    sampling: Interval,
+    workload: &Workload,
+    show_progress: bool,
+) -> Result<(BenchmarkStats, Vec<SearchProbe>)> {
+    let mut search = RateSearch::new(conf)?;
+    let mut probes = Vec::new();
+    let mut best: Option<BenchmarkStats> = None;
+    while let Some(rate) = search.next_rate() {
+        let options = ExecutionOptions {
+            duration: Interval::Time(conf.probe_duration),
+            rate: Some(rate),
+            rate_end: None,
+            rate_profile: None,
+            ..exec_options.clone()
+        };
+        eprintln!("info: Probing rate {rate:.0} op/s...");
+        let name = format!("Probing {rate:.0} op/s...");
+        let stats = par_execute(
+            &name,
+            &options,
+            sampling,
+            workload.clone()?,
+            show_progress,
+            false,
+            &mut SampleWriters::default(),
+        )
+        .await?;
+        if Interrupt::is_ctrl_c_pressed() {
+            // The probe didn't run for its full duration, so it can't tell if the rate passes
+            best.get_or_insert(stats);
+            break;
+        }
+        let probe = SearchProbe::new(conf, rate, &stats);
+        eprintln!(
+            "info: Rate {:.0} op/s: throughput {:.0} op/s, P{} {:.3} ms, errors {:.2}%: {}",
+            probe.rate,
+            probe.throughput,
+            conf.percentile,
+            probe.latency_ms,
+            probe.errors_ratio,
+            if probe.passed { "passed" } else { "failed" }
+        );
+        search.record(rate, probe.passed);
+        if search.max_passed_rate() == Some(rate) || best.is_none() {
+            best = Some(stats);
+        }
+        probes.push(probe);
+    }
+    Ok((best.unwrap(), probes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn conf(mode: SearchMode, max_rate: Option<f64>) -> SearchConf {
+        SearchConf {
+            mode,
+            start_rate: 1000.0,
+            max_rate,
+            precision: 0.1,
+            ..SearchConf::default()
+        }
+    }
+
+    /// Runs the search against a system that passes all rates up to `capacity`.
+    fn run_search(conf: &SearchConf, capacity: f64) -> (Vec<f64>, Option<f64>) {
+        let mut search = RateSearch::new(conf).unwrap();
+        let mut probed = Vec::new();
+        while let Some(rate) = search.next_rate() {
+            probed.push(rate);
+            search.record(rate, rate <= capacity);
+        }
+        (probed, search.max_passed_rate())
+    }
+
+    #[test]
+    fn binary_search_converges_to_capacity() {
+        let (probed, result) = run_search(&conf(SearchMode::Binary, None), 5000.0);
+        assert_eq!(&probed[..4], &[1000.0, 2000.0, 4000.0, 8000.0]);
+        let result = result.unwrap();
+        assert!(result <= 5000.0);
+        assert!(result >= 5000.0 / 1.1);
+    }
+
+    #[test]
+    fn step_search_stops_at_first_failure() {
+        let (probed, result) = run_search(&conf(SearchMode::Step, None), 3500.0);
+        assert_eq!(probed, vec![1000.0, 2000.0, 3000.0, 4000.0]);
+        assert_eq!(result, Some(3000.0));
+    }
+
+    #[test]
+    fn search_stops_at_max_rate() {
+        let (probed, result) = run_search(&conf(SearchMode::Binary, Some(3000.0)), 1e9);
+        assert_eq!(probed, vec![1000.0, 2000.0, 3000.0]);
+        assert_eq!(result, Some(3000.0));
+    }
+
+    #[test]
+    fn search_stops_if_start_rate_fails() {
+        let (probed, result) = run_search(&conf(SearchMode::Binary, None), 500.0);
+        assert_eq!(probed, vec![1000.0]);
+        assert_eq!(result, None);
+    }
+}
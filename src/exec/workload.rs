@@ -3,17 +3,19 @@ use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::mem;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
 
-use crate::config::ValidationStrategy;
+use crate::config::{ErrorAction, Interval};
 use crate::error::LatteError;
 use crate::scripting::context::Context;
 use crate::scripting::db_error::{DbError, DbErrorKind};
 use crate::scripting::retry_error::handle_retry_error;
-use crate::stats::latency::LatencyDistributionRecorder;
-use crate::stats::session::SessionStats;
+use crate::stats::errors::ErrorCategory;
+use crate::stats::latency::{self, LatencyDistributionRecorder};
+use crate::stats::session::{CallStats, SessionStats};
 use rand::distr::weighted::WeightedIndex;
 use rand::distr::Distribution;
 use rand::rngs::SmallRng;
@@ -93,6 +95,26 @@ impl Program {
     /// - `script`: source code in Rune language
     /// - `params`: parameter values that will be exposed to the script by the `params!` macro
     pub fn new(source: Source, params: HashMap<String, String>) -> Result<Program, LatteError> {
+        let sources = Self::load_sources(source)?;
+        Self::build(sources, params)
+    }
+
+    /// Creates a program from the main script and the optional library script
+    /// loaded by the caller, e.g. received over the network.
+    pub fn from_sources(
+        source: Source,
+        lib_source: Option<Source>,
+        params: HashMap<String, String>,
+    ) -> Result<Program, LatteError> {
+        let mut sources = Sources::new();
+        if let Some(lib_source) = lib_source {
+            sources.insert(lib_source)?;
+        }
+        sources.insert(source)?;
+        Self::build(sources, params)
+    }
+
+    fn build(mut sources: Sources, params: HashMap<String, String>) -> Result<Program, LatteError> {
         let mut context = rune::Context::with_default_modules().unwrap();
         crate::scripting::install(&mut context, params);
 
@@ -100,7 +122,6 @@ impl Program {
         options.debug_info(true);
 
         let mut diagnostics = Diagnostics::new();
-        let mut sources = Self::load_sources(source)?;
         let mut meta = ProgramMetadata::new();
         let unit = rune::prepare(&mut sources)
             .with_context(&context)
@@ -290,6 +311,19 @@ pub struct FnStats {
     pub call_count: u64,
     pub error_count: u64,
     pub call_latency: LatencyDistributionRecorder,
+    /// Delays between the scheduled and the actual start times of the calls
+    pub start_delay: LatencyDistributionRecorder,
+    /// Number of calls started later than the configured threshold
+    pub late_count: u64,
+    /// Start delay above which a call is counted as late, taken from the latency settings
+    /// when the statistics are created
+    pub late_threshold: Duration,
+    /// Number of requests made by the calls
+    pub req_count: u64,
+    /// Number of requests retried by the calls
+    pub req_retry_count: u64,
+    /// Number of rows returned or written by the calls
+    pub row_count: u64,
 }
 
 impl FnStats {
@@ -299,6 +333,12 @@ impl FnStats {
             call_count: 0,
             error_count: 0,
             call_latency: LatencyDistributionRecorder::default(),
+            start_delay: LatencyDistributionRecorder::default(),
+            late_count: 0,
+            late_threshold: latency::late_threshold(),
+            req_count: 0,
+            req_retry_count: 0,
+            row_count: 0,
         }
     }
 
@@ -306,6 +346,24 @@ impl FnStats {
         self.call_count = 0;
         self.error_count = 0;
         self.call_latency.clear();
+        self.start_delay.clear();
+        self.late_count = 0;
+        self.req_count = 0;
+        self.req_retry_count = 0;
+        self.row_count = 0;
+    }
+
+    pub fn operation_started(&mut self, delay: Duration) {
+        self.start_delay.record(delay);
+        if delay > self.late_threshold {
+            self.late_count += 1;
+        }
+    }
+
+    pub fn requests_completed(&mut self, call: CallStats) {
+        self.req_count += call.req_count;
+        self.req_retry_count += call.req_retry_count;
+        self.row_count += call.row_count;
     }
 
     pub fn operation_completed(&mut self, duration: Duration) {
@@ -350,6 +408,16 @@ impl FnStatsCollector {
         self.fn_stats.iter().map(|f| f.function.clone())
     }
 
+    /// Records how late the operation started relative to its scheduled time
+    pub fn operation_started(&mut self, function: &FnRef, delay: Duration) {
+        self.fn_stats_mut(function).operation_started(delay);
+    }
+
+    /// Records the requests made by an operation
+    pub fn requests_completed(&mut self, function: &FnRef, call: CallStats) {
+        self.fn_stats_mut(function).requests_completed(call);
+    }
+
     /// Records the duration of a successful operation
     pub fn operation_completed(&mut self, function: &FnRef, duration: Duration) {
         self.fn_stats_mut(function).operation_completed(duration);
@@ -361,12 +429,20 @@ impl FnStatsCollector {
     }
 
     /// Finds the stats for given function.
-    /// The function must exist! Otherwise, it will panic.
+    /// Functions not invoked before, e.g. functions run at their own rate, are added on the first call.
     fn fn_stats_mut(&mut self, function: &FnRef) -> &mut FnStats {
-        self.fn_stats
-            .iter_mut()
-            .find(|f| f.function.hash == function.hash)
-            .unwrap()
+        let index = match self
+            .fn_stats
+            .iter()
+            .position(|f| f.function.hash == function.hash)
+        {
+            Some(index) => index,
+            None => {
+                self.fn_stats.push(FnStats::new(function.clone()));
+                self.fn_stats.len() - 1
+            }
+        };
+        &mut self.fn_stats[index]
     }
 
     /// Clears any collected stats and sets the start time
@@ -389,6 +465,9 @@ pub struct Workload {
     program: Program,
     router: FunctionRouter,
     state: TryLock<FnStatsCollector>,
+    /// Time of the last reset, used for changing the function mix.
+    /// Kept out of `state`, because it is read by every cycle.
+    phase_start_time: PhaseStartTime,
 }
 
 impl Workload {
@@ -399,6 +478,7 @@ impl Workload {
             program,
             router: FunctionRouter::new(functions),
             state: TryLock::new(state),
+            phase_start_time: PhaseStartTime::new(),
         }
     }
 
@@ -411,10 +491,35 @@ impl Workload {
             state: TryLock::new(FnStatsCollector::new(
                 self.state.try_lock().unwrap().functions(),
             )),
+            phase_start_time: PhaseStartTime::new(),
         })
     }
 
-    /// Executes a single cycle of a workload.
+    /// Creates a copy of this workload that invokes a different set of functions.
+    pub fn with_functions(&self, functions: &[(FnRef, f64)]) -> Result<Self, LatteError> {
+        Ok(Workload {
+            context: self.context.clone()?,
+            program: self.program.unshare(),
+            router: FunctionRouter::new(functions),
+            state: TryLock::new(FnStatsCollector::new(functions.iter().map(|x| x.0.clone()))),
+            phase_start_time: PhaseStartTime::new(),
+        })
+    }
+
+    /// Returns the same workload with the weights of the functions changing during the run.
+    /// Functions not present in the workload are added with the initial weight 0.
+    pub fn with_function_mix(mut self, mix: Vec<MixTransition>) -> Self {
+        self.router = self.router.with_mix(mix);
+        self
+    }
+
+    /// Returns true if the workload has functions to choose from by weight.
+    /// There are no such functions if all functions are invoked at their own rates.
+    pub fn has_weighted_functions(&self) -> bool {
+        self.router.selector.is_some()
+    }
+
+    /// Executes a single cycle of a workload, invoking one of the weighted functions.
     /// This should be idempotent –
     /// the generated action should be a function of the iteration number.
     /// Returns the cycle number and the end time of the query.
@@ -424,76 +529,98 @@ impl Workload {
         scheduled_time: Instant,
     ) -> Result<(i64, Instant), LatteError> {
         let mut rng = SmallRng::seed_from_u64(cycle as u64);
-        let function = self.router.select(&mut rng);
-        let mut current_retries_counter = 0;
-        let mut end_time = Instant::now();
-        let mut is_ok = false;
-        let retry_number = self.context.retry_number;
-        while current_retries_counter < retry_number {
-            let current_err: DbError;
-            // NOTE: Create a separate scope inside of the loop
+        let function = if self.router.mix.is_empty() {
+            self.router.select(&mut rng)
+        } else {
+            let elapsed = Instant::now().saturating_duration_since(self.phase_start_time.get());
+            self.router.select_at(&mut rng, elapsed, cycle)
+        };
+        self.run_function(cycle, scheduled_time, function).await
+    }
+
+    /// Executes a single cycle of the given function of the workload.
+    /// Returns the cycle number and the end time of the query.
+    pub async fn run_function(
+        &self,
+        cycle: i64,
+        scheduled_time: Instant,
+        function: &FnRef,
+    ) -> Result<(i64, Instant), LatteError> {
+        let call = self.call_function(cycle, scheduled_time, function);
+        let (result, call_stats) = CallStats::track(call).await;
+        self.state
+            .try_lock()
+            .unwrap()
+            .requests_completed(function, call_stats);
+        result
+    }
+
+    /// Calls the function, retrying it if configured, and records the call stats
+    async fn call_function(
+        &self,
+        cycle: i64,
+        scheduled_time: Instant,
+        function: &FnRef,
+    ) -> Result<(i64, Instant), LatteError> {
+        let start_delay = Instant::now().saturating_duration_since(scheduled_time);
+        self.state
+            .try_lock()
+            .unwrap()
+            .operation_started(function, start_delay);
+        let mut current_attempt_num = 0;
+        loop {
+            // NOTE: Create a separate scope for the call
             //       to be able to run additional retry-related async context functions.
-            {
+            let result = {
                 let context = SessionRef::new(&self.context);
-                // TODO: calculate 2nd metric using 'start_time'?
-                // let start_time = Instant::now();
                 let result = self.program.async_call(function, (context, cycle)).await;
-                end_time = Instant::now();
-                let mut state = self.state.try_lock().unwrap();
-                let duration = end_time - scheduled_time;
-
-                match result {
-                    Ok(_) => {
-                        state.operation_completed(function, duration);
-                        is_ok = true;
-                        break;
-                    }
-                    Err(LatteError::Database(boxed_err))
-                        if matches!(boxed_err.0, DbErrorKind::Overloaded(..)) =>
-                    {
-                        // don't stop on overload errors;
-                        // they are being counted by the context stats anyways
-                        state.operation_failed(function, duration);
-                        return Ok((cycle, end_time));
-                    }
-                    // NOTE: "CustomError" gets generated by the "signal_failure" context function
-                    //       which may be called anytime in a rune function.
-                    //       May be used for data validation and other needs which require re-run
-                    //       of a rune function.
-                    Err(LatteError::Database(boxed_err))
-                        if matches!(boxed_err.0, DbErrorKind::CustomError(_)) =>
-                    {
-                        state.operation_failed(function, duration);
-                        match &self.context.validation_strategy {
-                            ValidationStrategy::Retry => {
-                                current_err = *boxed_err;
-                            }
-                            ValidationStrategy::FailFast => {
-                                return Err(LatteError::Database(boxed_err))
-                            }
-                            ValidationStrategy::Ignore => {
-                                current_err = *boxed_err;
-                                is_ok = true;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        state.operation_failed(function, duration);
-                        return Err(e);
-                    }
+                result.map(|_| ())
+            };
+            let end_time = Instant::now();
+            let duration = end_time - scheduled_time;
+            let err = match result {
+                Ok(_) => {
+                    self.state
+                        .try_lock()
+                        .unwrap()
+                        .operation_completed(function, duration);
+                    return Ok((cycle, end_time));
                 }
+                Err(err) => err,
+            };
+            self.state
+                .try_lock()
+                .unwrap()
+                .operation_failed(function, duration);
+            let LatteError::Database(db_err) = err else {
+                return Err(err);
+            };
+            // The requests have been already retried by the context,
+            // so only the errors of the function itself may get the function re-run.
+            // NOTE: "CustomError" gets generated by the "signal_failure" context function
+            //       which may be called anytime in a rune function.
+            //       May be used for data validation and other needs which require re-run
+            //       of a rune function.
+            if matches!(db_err.0, DbErrorKind::QueryRetriesExceeded(_)) {
+                return Err(LatteError::Database(db_err));
             }
-            handle_retry_error(&self.context, current_retries_counter, current_err).await;
-            if is_ok {
-                // ValidationStrategy::Ignore
-                break;
+            let category = db_err.category();
+            match self.context.retry_policy.action(category) {
+                ErrorAction::Retry if category == ErrorCategory::Validation => {
+                    handle_retry_error(&self.context, current_attempt_num, *db_err).await?;
+                    current_attempt_num += 1;
+                }
+                ErrorAction::Ignore => {
+                    // the error is counted by the context stats, so go on
+                    if matches!(db_err.0, DbErrorKind::CustomError(_)) {
+                        let _ =
+                            handle_retry_error(&self.context, current_attempt_num, *db_err).await;
+                    }
+                    return Ok((cycle, end_time));
+                }
+                _ => return Err(LatteError::Database(db_err)),
             }
-            current_retries_counter += 1; // ValidationStrategy::Retry
-        }
-        if is_ok {
-            return Ok((cycle, end_time));
         }
-        Err(DbError::query_retries_exceeded(self.context.retry_number).into())
     }
 
     /// Returns the reference to the contained context.
@@ -507,6 +634,7 @@ impl Workload {
     /// recorded start and end times of measurement.
     pub fn reset(&self, start_time: Instant) {
         self.state.try_lock().unwrap().reset(start_time);
+        self.phase_start_time.set(start_time);
         self.context.reset();
     }
 
@@ -524,23 +652,186 @@ impl Workload {
     }
 }
 
+/// A point in time that can be read and changed by many threads without locking
+struct PhaseStartTime {
+    origin: Instant,
+    /// Nanoseconds since `origin`
+    offset_ns: AtomicU64,
+}
+
+impl PhaseStartTime {
+    fn new() -> Self {
+        PhaseStartTime {
+            origin: Instant::now(),
+            offset_ns: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self) -> Instant {
+        self.origin + Duration::from_nanos(self.offset_ns.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, time: Instant) {
+        let offset = time.saturating_duration_since(self.origin).as_nanos() as u64;
+        self.offset_ns.store(offset, Ordering::Relaxed);
+    }
+}
+
 #[derive(Clone, Debug)]
 struct FunctionRouter {
-    selector: WeightedIndex<f64>,
+    /// Not set if there are no functions with positive weights
+    selector: Option<WeightedIndex<f64>>,
     functions: Vec<FnRef>,
+    /// Initial weights of the functions
+    weights: Vec<f64>,
+    /// Changes of the weights, in the order of their positions
+    mix: Vec<(Interval, Interval, Vec<f64>)>,
 }
 
 impl FunctionRouter {
     pub fn new(functions: &[(FnRef, f64)]) -> Self {
         let (functions, weights): (Vec<_>, Vec<_>) = functions.iter().cloned().unzip();
-        let selector = WeightedIndex::new(weights).unwrap();
+        let selector = WeightedIndex::new(&weights).ok();
         FunctionRouter {
             selector,
             functions,
+            weights,
+            mix: Vec::new(),
         }
     }
 
+    pub fn with_mix(mut self, mix: Vec<MixTransition>) -> Self {
+        for t in &mix {
+            for (function, _) in &t.functions {
+                if !self.functions.contains(function) {
+                    self.functions.push(function.clone());
+                    self.weights.push(0.0);
+                }
+            }
+        }
+        self.mix = mix
+            .into_iter()
+            .map(|t| {
+                let weights = self
+                    .functions
+                    .iter()
+                    .map(|f| {
+                        t.functions
+                            .iter()
+                            .find(|(g, _)| g == f)
+                            .map_or(0.0, |(_, w)| *w)
+                    })
+                    .collect();
+                (t.from, t.to, weights)
+            })
+            .collect();
+        self
+    }
+
     pub fn select(&self, rng: &mut impl Rng) -> &FnRef {
-        &self.functions[self.selector.sample(rng)]
+        let selector = self
+            .selector
+            .as_ref()
+            .expect("no weighted functions to select from");
+        &self.functions[selector.sample(rng)]
+    }
+
+    /// Selects a function using the weights in effect at the given time since the start
+    /// of the phase and at the given cycle.
+    /// Runs on every cycle, so it computes the weights on the fly instead of allocating them.
+    pub fn select_at(&self, rng: &mut impl Rng, elapsed: Duration, cycle: i64) -> &FnRef {
+        let count = self.functions.len();
+        let total: f64 = (0..count).map(|i| self.weight_at(i, elapsed, cycle)).sum();
+        let mut point = rng.random::<f64>() * total;
+        let mut last = None;
+        for i in 0..count {
+            let weight = self.weight_at(i, elapsed, cycle);
+            if point < weight {
+                return &self.functions[i];
+            }
+            point -= weight;
+            if weight > 0.0 {
+                last = Some(i);
+            }
+        }
+        // The weights may not sum up exactly due to rounding; pick the last one
+        &self.functions[last.expect("no weighted functions to select from")]
+    }
+
+    /// Returns the weight of the function at given index in effect at the given position.
+    /// Each change moves the weight linearly towards its own weight over its range.
+    fn weight_at(&self, index: usize, elapsed: Duration, cycle: i64) -> f64 {
+        self.mix
+            .iter()
+            .fold(self.weights[index], |weight, (from, to, target)| {
+                let progress = match (from, to) {
+                    (Interval::Time(from), Interval::Time(to)) => {
+                        progress(elapsed.as_secs_f64(), from.as_secs_f64(), to.as_secs_f64())
+                    }
+                    (Interval::Count(from), Interval::Count(to)) => {
+                        progress(cycle as f64, *from as f64, *to as f64)
+                    }
+                    _ => 0.0,
+                };
+                weight + progress * (target[index] - weight)
+            })
+    }
+}
+
+/// Returns the fraction of the range `from..to` passed at `x`, between 0.0 and 1.0
+fn progress(x: f64, from: f64, to: f64) -> f64 {
+    if x < from {
+        0.0
+    } else if x >= to {
+        1.0
+    } else {
+        (x - from) / (to - from)
+    }
+}
+
+/// A change of the weights of the workload functions, resolved from the run configuration
+#[derive(Clone, Debug)]
+pub struct MixTransition {
+    pub from: Interval,
+    pub to: Interval,
+    pub functions: Vec<(FnRef, f64)>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fn_ref(name: &str) -> FnRef {
+        FnRef::new(name)
+    }
+
+    #[test]
+    fn mix_changes_weights_linearly() {
+        let router = FunctionRouter::new(&[(fn_ref("read"), 0.9), (fn_ref("write"), 0.1)])
+            .with_mix(vec![MixTransition {
+                from: Interval::Time(Duration::ZERO),
+                to: Interval::Time(Duration::from_secs(600)),
+                functions: vec![(fn_ref("read"), 0.5), (fn_ref("write"), 0.5)],
+            }]);
+        let elapsed = Duration::from_secs(300);
+        assert!((router.weight_at(0, elapsed, 0) - 0.7).abs() < 1e-9);
+        assert!((router.weight_at(1, elapsed, 0) - 0.3).abs() < 1e-9);
+        let elapsed = Duration::from_secs(900);
+        assert_eq!(router.weight_at(0, elapsed, 0), 0.5);
+        assert_eq!(router.weight_at(1, elapsed, 0), 0.5);
+    }
+
+    #[test]
+    fn mix_switches_weights_at_cycle() {
+        let router = FunctionRouter::new(&[(fn_ref("read"), 1.0)]).with_mix(vec![MixTransition {
+            from: Interval::Count(1000),
+            to: Interval::Count(1000),
+            functions: vec![(fn_ref("write"), 1.0)],
+        }]);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let before = router.select_at(&mut rng, Duration::ZERO, 999);
+        assert_eq!(before.name, "read");
+        let after = router.select_at(&mut rng, Duration::ZERO, 1000);
+        assert_eq!(after.name, "write");
     }
 }
@@ -1,9 +1,10 @@
 use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{stdout, Write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process::exit;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 use std::{env, fs};
 
 use clap::{CommandFactory, FromArgMatches};
@@ -12,31 +13,39 @@ use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use hdrhistogram::serialization::{interval_log, V2DeflateSerializer};
 use itertools::Itertools;
-use rune::Source;
-use search_path::SearchPath;
 use tokio::runtime::{Builder, Runtime};
 use tokio::task::spawn_blocking;
-use tracing::info;
 use tracing::level_filters::LevelFilter;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::EnvFilter;
 use walkdir::WalkDir;
 
 use crate::config::{
-    AppConfig, Command, ConnectionConf, EditCommand, HdrCommand, Interval, ListCommand,
-    LoadCommand, SchemaCommand, ShowCommand, VersionCommand,
+    AppConfig, Command, EditCommand, HdrCommand, Interval, LatencyConf, ListCommand, LoadCommand,
+    MergeCommand, SchemaCommand, ShowCommand, VersionCommand,
 };
 use crate::error::{LatteError, Result};
-use crate::exec::{par_execute, ExecutionOptions};
-use crate::report::{PathAndSummary, Report, RunConfigCmp};
-use crate::scripting::cluster_info::ClusterInfo;
-use crate::scripting::context::Context;
-use crate::stats::histogram::HistogramWriter;
-use crate::stats::{BenchmarkCmp, BenchmarkStats, Recorder};
+use crate::exec::distributed;
+use crate::exec::interrupt::Interrupt;
+use crate::exec::run::{
+    connect, connect_and_prepare, create_sample_writers, find_workload, function_rates,
+    load_workload_script, main_phase_options, resolve_function_mix, resolve_functions,
+    serve_metrics, warmup,
+};
+use crate::exec::search::{find_max_rate, SearchProbe};
+use crate::exec::{par_execute, par_execute_stages, ExecutionOptions, Stage};
+use crate::report::traces::{read_trace_log, TraceSummary};
+use crate::report::{
+    load_report_or_abort, AssertionSummary, PathAndSummary, Report, ReportList, RunConfigCmp,
+    SearchSummary, StageCmp, StageReport,
+};
+use crate::stats::latency;
+use crate::stats::{BenchmarkCmp, BenchmarkMultiCmp, BenchmarkStats, Recorder, SampleWriters};
 use crate::version::{format_version_info_human, get_formatted_version_info};
 use exec::cycle::BoundedCycleCounter;
 use exec::progress::Progress;
-use exec::workload::{FnRef, Program, Workload, WorkloadStats, LOAD_FN};
+use exec::workload::{FnRef, Workload, WorkloadStats, LOAD_FN};
+use report::export::export;
 use report::plot::plot_graph;
 use report::table::{Alignment, Table};
 
@@ -50,89 +59,12 @@ mod version;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Exit code returned when the results of a run don't satisfy the assertions
+const ASSERTION_FAILED_EXIT_CODE: i32 = 3;
+
 #[global_allocator]
 static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
-fn load_report_or_abort(path: &Path) -> Report {
-    match Report::load(path) {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!(
-                "error: Failed to read report from {}: {}",
-                path.display(),
-                e
-            );
-            exit(1)
-        }
-    }
-}
-
-/// Reads the workload script from a file and compiles it.
-fn load_workload_script(workload: &Path, params: &[(String, String)]) -> Result<Program> {
-    let workload = find_workload(workload)
-        .canonicalize()
-        .unwrap_or_else(|_| workload.to_path_buf());
-    eprintln!("info: Loading workload script {}...", workload.display());
-    let src =
-        Source::from_path(&workload).map_err(|e| LatteError::ScriptRead(workload.clone(), e))?;
-    let program = Program::new(src, params.iter().cloned().collect())?;
-    info!(
-        "Loaded workload script {}\n\tParams: {:?}",
-        workload.display(),
-        params,
-    );
-    Ok(program)
-}
-
-/// Locates the workload and returns an absolute path to it.
-/// If not found, returns the original path unchanged.
-/// If the workload path is relative, it is searched in the directories
-/// listed by `LATTE_WORKLOAD_PATH` environment variable.
-/// If the variable is not set, workload is searched in
-/// `.local/share/latte/workloads` and `/usr/share/latte/workloads`.
-fn find_workload(workload: &Path) -> PathBuf {
-    if workload.starts_with(".") || workload.is_absolute() {
-        return workload.to_path_buf();
-    }
-    let search_path = SearchPath::new("LATTE_WORKLOAD_PATH").unwrap_or_else(|_| {
-        let relative_to_exe = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(Path::to_path_buf))
-            .map(|p| p.join("workloads"));
-        SearchPath::from(
-            [
-                PathBuf::from(".local/share/latte/workloads"),
-                PathBuf::from("/usr/share/latte/workloads"),
-            ]
-            .into_iter()
-            .chain(relative_to_exe)
-            .collect_vec(),
-        )
-    });
-    search_path
-        .find_file(workload)
-        .unwrap_or_else(|| workload.to_path_buf())
-}
-
-/// Connects to the server and returns the session
-async fn connect(conf: &ConnectionConf) -> Result<(Context, Option<ClusterInfo>)> {
-    eprintln!("info: Connecting to {:?}... ", conf.addresses);
-    let session = scripting::connect::connect(conf).await?;
-    let cluster_info = session.cluster_info().await?;
-    eprintln!(
-        "info: Connected to '{}' cluster running {}",
-        cluster_info
-            .as_ref()
-            .map(|c| c.name.as_str())
-            .unwrap_or("unknown"),
-        cluster_info
-            .as_ref()
-            .map(|c| c.db_version.as_str())
-            .unwrap_or("unknown")
-    );
-    Ok((session, cluster_info))
-}
-
 /// Runs the `schema` function of the workload script.
 /// Exits with error if the `schema` function is not present or fails.
 async fn schema(conf: SchemaCommand) -> Result<()> {
@@ -191,8 +123,14 @@ async fn load(conf: LoadCommand) -> Result<()> {
         rate: conf.rate.rate,
         rate_sine_amplitude: conf.rate.rate_sine_amplitude,
         rate_sine_period: conf.rate.rate_sine_period,
+        arrival: conf.rate.arrival,
+        rate_end: None,
+        rate_profile: None,
+        function_rates: Vec::new(),
+        think_time: None,
         threads: conf.threads,
         concurrency: conf.concurrency,
+        drain_timeout: Duration::ZERO,
     };
     let result = par_execute(
         "Loading...",
@@ -201,10 +139,14 @@ async fn load(conf: LoadCommand) -> Result<()> {
         loader,
         !conf.quiet,
         false,
-        &mut None,
+        &mut SampleWriters::default(),
     )
     .await?;
 
+    if Interrupt::is_ctrl_c_pressed() {
+        eprintln!("error: Loading interrupted. Some data might be missing.");
+        exit(255)
+    }
     if result.error_count > 0 {
         for e in result.errors {
             eprintln!("error: {e}");
@@ -217,151 +159,61 @@ async fn load(conf: LoadCommand) -> Result<()> {
 
 async fn run(conf: RunCommand) -> Result<()> {
     let mut conf = conf.set_timestamp_if_empty();
-    let compare = conf.baseline.as_ref().map(|p| load_report_or_abort(p));
-
-    let mut program = load_workload_script(&conf.workload, &conf.params)?;
-
-    let mut functions = Vec::new();
-    let mut functions_to_invoke = Vec::new();
-    for f in &conf.functions {
-        functions_to_invoke.push((f.name.as_str(), f.weight));
-        let function = FnRef::new(f.name.as_str());
-        if !program.has_function(&function) {
-            eprintln!(
-                "error: Function {} not found in the workload script.",
-                f.name.as_str()
-            );
-            exit(255);
-        }
-        functions.push((function, f.weight))
+    if conf.search.find_max_rate && !conf.stages.is_empty() {
+        return Err(LatteError::Configuration(
+            "Max rate search cannot be combined with stages".to_string(),
+        ));
     }
-
-    let (session, cluster_info) = connect(&conf.connection).await?;
-
-    // NOTE: Add info about the target rune functions to the context
-    //       for the more flexible tweaking of the 'prepare' rune function.
-    match session.data.borrow_mut::<rune::runtime::Object>() {
-        Ok(mut obj) => {
-            let _ = obj.insert(
-                rune::alloc::String::try_from("functions_to_invoke")?,
-                rune::to_value(functions_to_invoke)?,
-            );
-        }
-        Err(_) => {
-            eprintln!("error: session.data is not a Rune Object");
-            exit(255);
-        }
+    if conf.baseline.is_none() && conf.assertions.iter().any(|a| a.needs_baseline()) {
+        return Err(LatteError::Configuration(
+            "Assertions referring to the baseline require the baseline to be given".to_string(),
+        ));
     }
-
-    if let Some(cluster_info) = cluster_info {
-        conf.cluster_name = Some(cluster_info.name);
-        conf.db_version = Some(cluster_info.db_version);
+    if conf.search.find_max_rate && (conf.sample_log.is_some() || conf.hdrfile.is_some()) {
+        return Err(LatteError::Configuration(
+            "Max rate search cannot be combined with a sample log nor an HDR histogram file"
+                .to_string(),
+        ));
     }
-
-    if program.has_prepare() {
-        eprintln!("info: Preparing...");
-        if let Err(e) = program.prepare(&session).await {
-            eprintln!("error: Failed to prepare: {e}");
-            exit(255);
-        }
+    let percentiles = conf.latency.percentiles();
+    for assertion in &conf.assertions {
+        assertion
+            .check_percentile(&percentiles)
+            .map_err(LatteError::Configuration)?;
     }
-
-    let runner = Workload::new(session.clone()?, program.clone(), &functions);
-    if conf.warmup_duration.is_not_zero() {
-        eprintln!("info: Warming up...");
-        let warmup_options = ExecutionOptions {
-            duration: conf.warmup_duration,
-            cycle_range: (conf.start_cycle, conf.end_cycle),
-            rate: None,
-            rate_sine_amplitude: conf.rate.rate_sine_amplitude,
-            rate_sine_period: conf.rate.rate_sine_period,
-            threads: conf.threads,
-            concurrency: conf.concurrency,
-        };
-        par_execute(
-            "Warming up...",
-            &warmup_options,
-            Interval::Unbounded,
-            runner.clone()?,
-            !conf.quiet,
-            false,
-            &mut None,
-        )
-        .await?;
+    if conf.rate.rate_profile.is_some() && (conf.search.find_max_rate || !conf.stages.is_empty()) {
+        return Err(LatteError::Configuration(
+            "Rate profile cannot be combined with stages nor max rate search".to_string(),
+        ));
+    }
+    if !conf.function_mix.is_empty()
+        && !conf
+            .functions
+            .iter()
+            .any(|f| f.rate.is_none() && f.weight > 0.0)
+    {
+        return Err(LatteError::Configuration(
+            "Function mix requires at least one function with a weight greater than 0".to_string(),
+        ));
+    }
+    if !conf.agents.is_empty() && (conf.search.find_max_rate || !conf.stages.is_empty()) {
+        return Err(LatteError::Configuration(
+            "Running on agents cannot be combined with stages nor max rate search".to_string(),
+        ));
+    }
+    let compare = conf.baseline.as_ref().map(|p| load_report_or_abort(p));
+    latency::configure(&conf.latency);
+    // The agents save the traces to their own trace logs
+    #[cfg(feature = "cql")]
+    if conf.connection.db.trace_log.is_none() && conf.agents.is_empty() {
+        conf.connection.db.trace_log = Some(report_path(&conf).with_extension("traces.jsonl"));
     }
 
-    eprintln!("info: Running benchmark...");
-
-    println!(
-        "{}",
-        RunConfigCmp {
-            v1: &conf,
-            v2: compare.as_ref().map(|c| &c.conf),
-        }
-    );
-
-    let exec_options = ExecutionOptions {
-        duration: conf.run_duration,
-        cycle_range: (conf.start_cycle, conf.end_cycle),
-        concurrency: conf.concurrency,
-        rate: conf.rate.rate,
-        rate_sine_amplitude: conf.rate.rate_sine_amplitude,
-        rate_sine_period: conf.rate.rate_sine_period,
-        threads: conf.threads,
-    };
-
-    report::print_log_header();
-    let stats = match conf.hdrfile {
-        Some(ref hdrfile) => {
-            let path = Path::new(&hdrfile);
-            if let Some(parent_dir) = path.parent() {
-                fs::create_dir_all(parent_dir)
-                    .map_err(|e| LatteError::LogFileCreate(hdrfile.clone(), e))?;
-            }
-            let hdrfile = File::create(hdrfile)
-                .map_err(|e| LatteError::LogFileCreate(hdrfile.to_path_buf(), e))?;
-            let (non_blocking_writer, _hdrh_guard) = tracing_appender::non_blocking(hdrfile);
-            let non_blocking_writer = Box::new(non_blocking_writer);
-            let serializer = Box::new(V2DeflateSerializer::new());
-            let system_time_now = SystemTime::now();
-            let hdrh_writer = interval_log::IntervalLogWriterBuilder::new()
-                .add_comment(format!("[Logged with Latte {VERSION}]").as_str())
-                .with_start_time(system_time_now)
-                .with_base_time(system_time_now)
-                .with_max_value_divisor(1000000.0) // ms
-                .begin_log_with(Box::leak(non_blocking_writer), Box::leak(serializer))
-                .unwrap();
-            let boxed_hdrh_writer: Box<dyn HistogramWriter + Send + Sync> = Box::new(hdrh_writer);
-
-            par_execute(
-                "Running...",
-                &exec_options,
-                conf.sampling_interval,
-                runner,
-                !conf.quiet,
-                conf.generate_report,
-                &mut Some(boxed_hdrh_writer),
-            )
-            .await
-        }
-        None => {
-            par_execute(
-                "Running...",
-                &exec_options,
-                conf.sampling_interval,
-                runner,
-                !conf.quiet,
-                conf.generate_report,
-                &mut None,
-            )
-            .await
-        }
-    };
-    let stats = match stats {
-        Ok(stats) => stats,
-        Err(e) => {
-            return Err(e);
-        }
+    let (stats, stage_stats, probes) = if conf.agents.is_empty() {
+        run_locally(&mut conf, compare.as_ref()).await?
+    } else {
+        let stats = distributed::coordinate(&mut conf, compare.as_ref()).await?;
+        (stats, Vec::new(), Vec::new())
     };
 
     let stats_cmp = BenchmarkCmp {
@@ -371,13 +223,68 @@ async fn run(conf: RunCommand) -> Result<()> {
     println!();
     println!("{}", &stats_cmp);
 
-    if stats_cmp.v1.log.len() > 1 {
-        let path = conf
-            .output
+    let stage_names = conf
+        .stages
+        .iter()
+        .enumerate()
+        .map(|(i, s)| s.name_or_default(i))
+        .collect_vec();
+    for (name, stats) in stage_names.iter().zip(&stage_stats) {
+        let stage_cmp = StageCmp::new(
+            name,
+            stats,
+            compare
+                .as_ref()
+                .and_then(|c| c.find_stage(name))
+                .map(|s| &s.result),
+        );
+        println!("{stage_cmp}");
+    }
+
+    if !probes.is_empty() {
+        let search_summary = SearchSummary {
+            conf: &conf.search,
+            probes: &probes,
+        };
+        println!("{search_summary}");
+    }
+
+    let assertion_outcomes = conf
+        .assertions
+        .iter()
+        .map(|a| a.evaluate(&stats_cmp))
+        .collect_vec();
+    if !assertion_outcomes.is_empty() {
+        println!("{}", AssertionSummary(&assertion_outcomes));
+    }
+
+    let interrupted = Interrupt::is_ctrl_c_pressed();
+    if interrupted {
+        eprintln!("warning: The run was interrupted. The results cover only its completed part.");
+    }
+
+    if stats_cmp.v1.log.len() > 1 || !probes.is_empty() || interrupted {
+        let path = report_path(&conf);
+        #[cfg(feature = "cql")]
+        let trace_log = conf
+            .connection
+            .db
+            .trace_log
             .clone()
-            .unwrap_or_else(|| PathBuf::from(format!("latte-{}.json", conf.id.as_ref().unwrap())));
+            .filter(|p| conf.agents.is_empty() && p.exists());
+        #[cfg(not(feature = "cql"))]
+        let trace_log = None;
 
-        let report = Report::new(conf, stats);
+        let stages = stage_names
+            .into_iter()
+            .zip(stage_stats)
+            .map(|(name, result)| StageReport { name, result })
+            .collect();
+        let report = Report::new(conf, stats)
+            .with_stages(stages)
+            .with_probes(probes)
+            .with_interrupted(interrupted)
+            .with_trace_log(trace_log);
         match report.save(&path) {
             Ok(()) => {
                 eprintln!("info: Saved report to {}", path.display());
@@ -388,9 +295,141 @@ async fn run(conf: RunCommand) -> Result<()> {
             }
         }
     }
+
+    let failed_count = assertion_outcomes.iter().filter(|o| !o.passed).count();
+    if failed_count > 0 {
+        eprintln!("error: {failed_count} assertion(s) failed");
+        exit(ASSERTION_FAILED_EXIT_CODE);
+    }
     Ok(())
 }
 
+/// Returns the path the report of the run is saved to
+fn report_path(conf: &RunCommand) -> PathBuf {
+    conf.output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("latte-{}.json", conf.id.as_ref().unwrap())))
+}
+
+/// Runs the benchmark in this process.
+/// Returns the statistics of the whole main phase, the statistics of each stage
+/// and the results of the search probes.
+async fn run_locally(
+    conf: &mut RunCommand,
+    compare: Option<&Report>,
+) -> Result<(BenchmarkStats, Vec<BenchmarkStats>, Vec<SearchProbe>)> {
+    let mut program = load_workload_script(&conf.workload, &conf.params)?;
+
+    let functions = resolve_functions(&program, &conf.functions);
+    let stage_functions = conf
+        .stages
+        .iter()
+        .map(|s| resolve_functions(&program, &s.functions))
+        .collect_vec();
+
+    let session = connect_and_prepare(conf, &mut program).await?;
+    serve_metrics(conf).await?;
+
+    let mix = resolve_function_mix(&program, &conf.function_mix);
+    let runner =
+        Workload::new(session.clone()?, program.clone(), &functions).with_function_mix(mix);
+    warmup(conf, &runner).await?;
+    if Interrupt::is_ctrl_c_pressed() {
+        return Err(LatteError::Interrupted);
+    }
+
+    eprintln!("info: Running benchmark...");
+
+    println!("{}", RunConfigCmp::new(conf, compare.map(|c| &c.conf)));
+
+    let exec_options = main_phase_options(conf);
+    let mut stages = Vec::with_capacity(conf.stages.len());
+    for (i, (stage, functions)) in conf.stages.iter().zip(stage_functions).enumerate() {
+        let workload = if functions.is_empty() {
+            runner.clone()?
+        } else {
+            runner.with_functions(&functions)?
+        };
+        stages.push(Stage {
+            name: stage.name_or_default(i),
+            exec_options: ExecutionOptions {
+                duration: stage.duration,
+                cycle_range: (conf.start_cycle, conf.end_cycle),
+                concurrency: stage.concurrency.unwrap_or(conf.concurrency),
+                rate: stage.rate.or(conf.rate.rate),
+                rate_sine_amplitude: conf.rate.rate_sine_amplitude,
+                rate_sine_period: conf.rate.rate_sine_period,
+                arrival: conf.rate.arrival,
+                rate_end: stage.rate_end,
+                rate_profile: None,
+                function_rates: function_rates(if stage.functions.is_empty() {
+                    &conf.functions
+                } else {
+                    &stage.functions
+                }),
+                think_time: conf.rate.think_time,
+                threads: conf.threads,
+                drain_timeout: conf.drain_timeout,
+            },
+            workload,
+        });
+    }
+
+    report::print_log_header();
+    let (mut writers, _hdrh_guard) = create_sample_writers(conf)?;
+    let result = run_main_phase(conf, &exec_options, stages, runner, &mut writers).await;
+    #[cfg(feature = "cql")]
+    session.finish_tracing().await;
+    result
+}
+
+/// Runs the main benchmark phase, either as a single phase, as a sequence of stages
+/// or as a sequence of max rate search probes.
+/// Returns the statistics of the whole phase, the statistics of each stage
+/// and the results of the search probes.
+async fn run_main_phase(
+    conf: &RunCommand,
+    exec_options: &ExecutionOptions,
+    stages: Vec<Stage>,
+    runner: Workload,
+    writers: &mut SampleWriters,
+) -> Result<(BenchmarkStats, Vec<BenchmarkStats>, Vec<SearchProbe>)> {
+    report::prometheus::reset();
+    if conf.search.find_max_rate {
+        let (stats, probes) = find_max_rate(
+            &conf.search,
+            exec_options,
+            conf.sampling_interval,
+            &runner,
+            !conf.quiet,
+        )
+        .await?;
+        Ok((stats, Vec::new(), probes))
+    } else if stages.is_empty() {
+        let stats = par_execute(
+            "Running...",
+            exec_options,
+            conf.sampling_interval,
+            runner,
+            !conf.quiet,
+            conf.generate_report,
+            writers,
+        )
+        .await?;
+        Ok((stats, Vec::new(), Vec::new()))
+    } else {
+        let (stats, stage_stats) = par_execute_stages(
+            stages,
+            conf.sampling_interval,
+            !conf.quiet,
+            conf.generate_report,
+            writers,
+        )
+        .await?;
+        Ok((stats, stage_stats, Vec::new()))
+    }
+}
+
 async fn list(conf: ListCommand) -> Result<()> {
     let max_depth = if conf.recursive { usize::MAX } else { 1 };
 
@@ -473,20 +512,175 @@ fn should_list(report: &Report, conf: &ListCommand) -> bool {
 }
 
 async fn show(conf: ShowCommand) -> Result<()> {
-    let report1 = load_report_or_abort(&conf.report);
-    let report2 = conf.baseline.map(|p| load_report_or_abort(&p));
+    let mut paths = conf.baseline.into_iter().chain(conf.reports).collect_vec();
+    let mut reference = conf.reference.map(|r| r.get() - 1).unwrap_or_default();
+    if reference >= paths.len() {
+        return Err(LatteError::Configuration(format!(
+            "Reference report position {} is out of range, only {} report(s) given",
+            reference + 1,
+            paths.len()
+        )));
+    }
+    // When comparing two reports, the reference report is displayed first, as the baseline
+    if paths.len() == 2 && reference == 1 {
+        paths.swap(0, 1);
+        reference = 0;
+    }
+    let reports = paths.iter().map(|p| load_report_or_abort(p)).collect_vec();
+    for (path, report) in paths.iter().zip(&reports) {
+        if report.interrupted {
+            eprintln!(
+                "warning: Report {} covers only a part of an interrupted run",
+                path.display()
+            );
+        }
+    }
+
+    if reports.len() > 1 {
+        let report_list = ReportList {
+            paths: &paths,
+            reference,
+        };
+        println!("{report_list}");
+    }
 
     let config_cmp = RunConfigCmp {
-        v1: &report1.conf,
-        v2: report2.as_ref().map(|r| &r.conf),
+        runs: reports.iter().map(|r| &r.conf).collect(),
+        reference,
     };
     println!("{config_cmp}");
 
-    let results_cmp = BenchmarkCmp {
-        v1: &report1.result,
-        v2: report2.as_ref().map(|r| &r.result),
+    let results_cmp = BenchmarkMultiCmp {
+        runs: reports.iter().map(|r| Some(&r.result)).collect(),
+        reference,
     };
     println!("{results_cmp}");
+
+    let stage_names = reports
+        .iter()
+        .flat_map(|r| r.stages.iter().map(|s| s.name.as_str()))
+        .unique()
+        .collect_vec();
+    for name in stage_names {
+        let stage_cmp = StageCmp {
+            name,
+            cmp: BenchmarkMultiCmp {
+                runs: reports
+                    .iter()
+                    .map(|r| r.find_stage(name).map(|s| &s.result))
+                    .collect(),
+                reference,
+            },
+        };
+        println!("{stage_cmp}");
+    }
+
+    for report in reports.iter().filter(|r| !r.probes.is_empty()) {
+        let search_summary = SearchSummary {
+            conf: &report.conf.search,
+            probes: &report.probes,
+        };
+        println!("{search_summary}");
+    }
+
+    for (path, report) in paths.iter().zip(&reports) {
+        let Some(trace_log) = report.trace_log_path(path) else {
+            continue;
+        };
+        match read_trace_log(&trace_log) {
+            Ok(traces) if !traces.is_empty() => {
+                let trace_summary = TraceSummary {
+                    traces: &traces,
+                    limit: conf.traces,
+                };
+                println!("{trace_summary}");
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "warning: Failed to read trace log {}: {}",
+                trace_log.display(),
+                e
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Merges the reports of runs executed at the same time by different clients
+async fn merge(conf: MergeCommand) -> Result<()> {
+    let mut reports = conf
+        .reports
+        .iter()
+        .map(|p| load_report_or_abort(p))
+        .collect_vec();
+    // The samples of the reports are merged period by period
+    if reports
+        .iter()
+        .any(|r| r.conf.sampling_interval != reports[0].conf.sampling_interval)
+    {
+        return Err(LatteError::Configuration(
+            "Reports with different sampling periods cannot be merged".to_string(),
+        ));
+    }
+    let percentiles = reports
+        .iter()
+        .flat_map(|r| r.result.percentiles.iter().copied())
+        .collect();
+    // Don't lose the precision of the histograms recorded with a higher precision than the default
+    let histogram_precision = reports
+        .iter()
+        .map(|r| r.conf.latency.histogram_precision)
+        .max()
+        .unwrap_or_default();
+    latency::configure(&LatencyConf {
+        percentiles,
+        histogram_precision,
+        ..Default::default()
+    });
+
+    let result = BenchmarkStats::merge(&reports.iter().map(|r| &r.result).collect_vec());
+    let stage_names = reports
+        .iter()
+        .flat_map(|r| r.stages.iter().map(|s| s.name.clone()))
+        .unique()
+        .collect_vec();
+    let stages = stage_names
+        .into_iter()
+        .map(|name| {
+            let results = reports
+                .iter()
+                .filter_map(|r| r.find_stage(&name).map(|s| &s.result))
+                .collect_vec();
+            let result = BenchmarkStats::merge(&results);
+            StageReport { name, result }
+        })
+        .collect();
+    if reports.iter().any(|r| !r.probes.is_empty()) {
+        eprintln!("warning: Results of the max rate search cannot be merged and are left out");
+    }
+
+    let rate = reports
+        .iter()
+        .map(|r| r.conf.rate.rate)
+        .sum::<Option<f64>>();
+    let start_cycle = reports.iter().map(|r| r.conf.start_cycle).min().unwrap();
+    let end_cycle = reports.iter().map(|r| r.conf.end_cycle).max().unwrap();
+    let tags = reports
+        .iter()
+        .flat_map(|r| r.conf.tags.iter().cloned())
+        .unique()
+        .collect();
+    let mut run_conf = reports.swap_remove(0).conf;
+    run_conf.rate.rate = rate;
+    run_conf.start_cycle = start_cycle;
+    run_conf.end_cycle = end_cycle;
+    run_conf.tags = tags;
+
+    let report = Report::new(run_conf, result).with_stages(stages);
+    report
+        .save(&conf.output)
+        .map_err(|e| LatteError::OutputFileCreate(conf.output.clone(), e))?;
+    eprintln!("info: Saved merged report to {}", conf.output.display());
     Ok(())
 }
 
@@ -557,8 +751,11 @@ async fn async_main(run_id: String, command: Command) -> Result<()> {
         }
         Command::List(config) => list(config).await?,
         Command::Show(config) => show(config).await?,
+        Command::Merge(config) => merge(config).await?,
         Command::Hdr(config) => export_hdr_log(config).await?,
         Command::Plot(config) => plot_graph(config).await?,
+        Command::Export(config) => export(config).await?,
+        Command::Agent(config) => distributed::agent(config).await?,
         Command::Version(config) => print_version(config).await?,
     }
     Ok(())
@@ -651,6 +848,7 @@ fn main() {
     let thread_count = match &command {
         Command::Run(cmd) => cmd.threads.get(),
         Command::Load(cmd) => cmd.threads.get(),
+        Command::Agent(cmd) => cmd.threads.map_or_else(num_cpus::get, NonZeroUsize::get),
         _ => 1,
     };
     let runtime = init_runtime(thread_count);
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::config::{ExportCommand, ExportFormat, WeightedFunction};
+use crate::report::load_report_or_abort;
+use crate::report::plot::plot_svg;
+use crate::report::{format_time, Report};
+use crate::stats::latency::LatencyDistribution;
+use crate::stats::percentiles::Percentile;
+use crate::stats::sample_log::sample_values;
+use crate::{LatteError, Result};
+
+/// Percentiles of the response time plotted in the HTML page if none are given
+const DEFAULT_PLOT_PERCENTILES: &[f64] = &[50.0, 99.0];
+
+/// Cycle latency statistics listed in the summary and per-function tables
+const LATENCY_COLUMNS: &[(&str, Option<Percentile>)] = &[
+    ("Mean [ms]", None),
+    ("P50 [ms]", Some(Percentile::P50)),
+    ("P90 [ms]", Some(Percentile::P90)),
+    ("P99 [ms]", Some(Percentile::P99)),
+    ("P99.9 [ms]", Some(Percentile::P99_9)),
+    ("Max [ms]", Some(Percentile::Max)),
+];
+
+/// A table of already formatted values, independent of the output format
+struct TextTable {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl TextTable {
+    fn new<S: ToString>(columns: impl IntoIterator<Item = S>) -> TextTable {
+        TextTable {
+            columns: columns.into_iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::new();
+        for row in std::iter::once(&self.columns).chain(&self.rows) {
+            let _ = writeln!(out, "{}", row.iter().map(|v| escape_csv(v)).join(","));
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let escape = |v: &String| v.replace('|', "\\|");
+        let _ = writeln!(out, "| {} |", self.columns.iter().map(escape).join(" | "));
+        let _ = writeln!(out, "|{}", "---|".repeat(self.columns.len()));
+        for row in &self.rows {
+            let _ = writeln!(out, "| {} |", row.iter().map(escape).join(" | "));
+        }
+        out
+    }
+
+    fn to_html(&self) -> String {
+        let mut out = String::from("<table>\n<tr>");
+        for c in &self.columns {
+            let _ = write!(out, "<th>{}</th>", escape_html(c));
+        }
+        out.push_str("</tr>\n");
+        for row in &self.rows {
+            out.push_str("<tr>");
+            for v in row {
+                let _ = write!(out, "<td>{}</td>", escape_html(v));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+        out
+    }
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns the name identifying the report in the exported tables
+fn report_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn latency_values(latency: &LatencyDistribution) -> Vec<String> {
+    LATENCY_COLUMNS
+        .iter()
+        .map(|(_, p)| {
+            let value = match p {
+                Some(p) => latency.percentiles.get(*p).value,
+                None => latency.mean.value,
+            };
+            format!("{value:.3}")
+        })
+        .collect()
+}
+
+fn summary_table(names: &[String], reports: &[Report]) -> TextTable {
+    let columns = [
+        "Report",
+        "Workload",
+        "Function(s)",
+        "Tags",
+        "Params",
+        "Timestamp",
+        "Rate [op/s]",
+        "Elapsed [s]",
+        "Cycles",
+        "Errors",
+        "Thrpt. [op/s]",
+        "Req. thrpt. [req/s]",
+    ];
+    let mut table = TextTable::new(
+        columns
+            .iter()
+            .copied()
+            .chain(LATENCY_COLUMNS.iter().map(|(c, _)| *c)),
+    );
+    for (name, report) in names.iter().zip(reports) {
+        let conf = &report.conf;
+        let result = &report.result;
+        let mut row = vec![
+            name.clone(),
+            conf.workload
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            conf.functions
+                .iter()
+                .map(WeightedFunction::to_string)
+                .join(", "),
+            conf.tags.join(", "),
+            conf.params
+                .iter()
+                .map(|(k, v)| format!("{k} = {v}"))
+                .join(", "),
+            format_time(conf.timestamp, "%Y-%m-%d %H:%M:%S"),
+            conf.rate.rate.map(|r| r.to_string()).unwrap_or_default(),
+            format!("{:.3}", result.elapsed_time_s),
+            result.cycle_count.to_string(),
+            result.error_count.to_string(),
+            format!("{:.0}", result.cycle_throughput.value),
+            format!("{:.0}", result.req_throughput.value),
+        ];
+        row.extend(latency_values(&result.cycle_latency));
+        table.push(row);
+    }
+    table
+}
+
+fn function_table(names: &[String], reports: &[Report]) -> TextTable {
+    let mut table = TextTable::new(
+        ["Report", "Function"]
+            .into_iter()
+            .chain(LATENCY_COLUMNS.iter().map(|(c, _)| *c)),
+    );
+    for (name, report) in names.iter().zip(reports) {
+        let by_fn = &report.result.cycle_latency_by_fn;
+        for (function, latency) in by_fn.iter().sorted_by_key(|(f, _)| *f) {
+            let mut row = vec![name.clone(), function.clone()];
+            row.extend(latency_values(latency));
+            table.push(row);
+        }
+    }
+    table
+}
+
+fn sample_log_table(names: &[String], reports: &[Report]) -> TextTable {
+    let rows = names
+        .iter()
+        .zip(reports)
+        .flat_map(|(name, report)| {
+            report
+                .result
+                .log
+                .iter()
+                .map(move |s| (name, sample_values(s)))
+        })
+        .collect_vec();
+    keyed_table("report", &rows)
+}
+
+/// Builds a table with a column for each key found in any of the rows.
+/// Reports saved by different versions may have different columns,
+/// so the cells of the keys missing in a row are left empty.
+fn keyed_table(first_column: &str, rows: &[(&String, Vec<(String, f64)>)]) -> TextTable {
+    let columns = rows
+        .iter()
+        .flat_map(|(_, values)| values.iter().map(|(k, _)| k))
+        .unique()
+        .collect_vec();
+    let mut table =
+        TextTable::new(std::iter::once(first_column).chain(columns.iter().map(|c| c.as_str())));
+    for (name, values) in rows {
+        let values: HashMap<&String, f64> = values.iter().map(|(k, v)| (k, *v)).collect();
+        let mut row = vec![name.to_string()];
+        row.extend(
+            columns
+                .iter()
+                .map(|c| values.get(c).map(|v| v.to_string()).unwrap_or_default()),
+        );
+        table.push(row);
+    }
+    table
+}
+
+fn markdown(names: &[String], reports: &[Report]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "### Summary\n");
+    out.push_str(&summary_table(names, reports).to_markdown());
+    let functions = function_table(names, reports);
+    if !functions.rows.is_empty() {
+        let _ = writeln!(out, "\n### Cycle latency by function\n");
+        out.push_str(&functions.to_markdown());
+    }
+    out
+}
+
+fn html(names: &[String], reports: &[Report], percentiles: &[f64]) -> String {
+    let mut out = String::new();
+    out.push_str(concat!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+        "<title>Latte benchmark report</title>\n<style>\n",
+        "body { font-family: sans-serif; margin: 2em; }\n",
+        "table { border-collapse: collapse; margin-bottom: 2em; }\n",
+        "th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }\n",
+        "th { background: #f0f0f0; }\n",
+        "svg { max-width: 100%; height: auto; }\n",
+        "</style>\n</head>\n<body>\n<h1>Latte benchmark report</h1>\n",
+    ));
+    out.push_str("<h2>Summary</h2>\n");
+    out.push_str(&summary_table(names, reports).to_html());
+    let functions = function_table(names, reports);
+    if !functions.rows.is_empty() {
+        out.push_str("<h2>Cycle latency by function</h2>\n");
+        out.push_str(&functions.to_html());
+    }
+
+    if reports.iter().any(|r| !r.result.log.is_empty()) {
+        let charts = [
+            ("Throughput", plot_svg(reports, &[], true, &[], false)),
+            (
+                "Response time",
+                plot_svg(reports, percentiles, false, &[], false),
+            ),
+        ];
+        for (title, svg) in charts {
+            match svg {
+                Ok(svg) => {
+                    let _ = writeln!(out, "<h2>{title}</h2>\n{svg}");
+                }
+                Err(e) => eprintln!("warning: Failed to plot {title}: {e}"),
+            }
+        }
+    } else {
+        out.push_str(concat!(
+            "<p>The reports contain no sampling log. ",
+            "Run the benchmark with <code>--generate-report</code> to include the charts.</p>\n"
+        ));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Exports the reports in the format chosen in the command
+pub async fn export(conf: ExportCommand) -> Result<()> {
+    let names = conf.reports.iter().map(|p| report_name(p)).collect_vec();
+    let reports = conf
+        .reports
+        .iter()
+        .map(|p| load_report_or_abort(p))
+        .collect_vec();
+
+    let percentiles = if conf.percentiles.is_empty() {
+        DEFAULT_PLOT_PERCENTILES
+    } else {
+        conf.percentiles.as_slice()
+    };
+    let output = match conf.format {
+        ExportFormat::Csv => summary_table(&names, &reports).to_csv(),
+        ExportFormat::CsvLog => sample_log_table(&names, &reports).to_csv(),
+        ExportFormat::Markdown => markdown(&names, &reports),
+        ExportFormat::Html => html(&names, &reports, percentiles),
+    };
+
+    match conf.output {
+        Some(path) => write_output(path, &output),
+        None => {
+            let _ = stdout().write_all(output.as_bytes());
+            Ok(())
+        }
+    }
+}
+
+fn write_output(path: PathBuf, output: &str) -> Result<()> {
+    fs::write(&path, output).map_err(|e| LatteError::OutputFileCreate(path.clone(), e))?;
+    eprintln!("Saved output to: {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn table() -> TextTable {
+        let mut table = TextTable::new(["Name", "Value"]);
+        table.push(vec!["a, b".to_string(), "1".to_string()]);
+        table.push(vec!["say \"hi\"".to_string(), "x|y".to_string()]);
+        table
+    }
+
+    #[test]
+    fn render_csv() {
+        assert_eq!(
+            table().to_csv(),
+            "Name,Value\n\"a, b\",1\n\"say \"\"hi\"\"\",x|y\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown() {
+        assert_eq!(
+            table().to_markdown(),
+            "| Name | Value |\n|---|---|\n| a, b | 1 |\n| say \"hi\" | x\\|y |\n"
+        );
+    }
+
+    #[test]
+    fn fill_missing_columns() {
+        let (old, new) = ("old".to_string(), "new".to_string());
+        let rows = [
+            (&old, vec![("a".to_string(), 1.0)]),
+            (&new, vec![("a".to_string(), 2.0), ("b".to_string(), 3.0)]),
+        ];
+        assert_eq!(
+            keyed_table("report", &rows).to_csv(),
+            "report,a,b\nold,1,\nnew,2,3\n"
+        );
+    }
+
+    #[test]
+    fn render_html() {
+        let html = table().to_html();
+        assert!(html.contains("<th>Name</th><th>Value</th>"));
+        assert!(html.contains("<td>say &quot;hi&quot;</td>"));
+    }
+}
@@ -1,23 +1,32 @@
-use crate::config::{RunCommand, WeightedFunction, PRINT_RETRY_ERROR_LIMIT};
-use crate::stats::percentiles::Percentile;
-use crate::stats::{BenchmarkCmp, BenchmarkStats, Mean, Sample, Significance};
+use crate::config::{RunCommand, SearchConf, WeightedFunction, PRINT_RETRY_ERROR_LIMIT};
+use crate::exec::search::SearchProbe;
+use crate::stats::assertion::AssertionOutcome;
+use crate::stats::errors::ErrorCategory;
+use crate::stats::percentiles::{percentile_name, Percentile};
+use crate::stats::{
+    BenchmarkCmp, BenchmarkMultiCmp, BenchmarkStats, Mean, Sample, Significance, StatementSummary,
+};
 use chrono::{DateTime, Local, TimeZone};
-use console::{pad_str, style, Alignment};
+use clap::ValueEnum;
+use console::{pad_str, strip_ansi_codes, style, Alignment};
 use core::fmt;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
 use std::io::{BufReader, BufWriter};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::process::exit;
 use std::{fs, io};
-use strum::IntoEnumIterator;
-use table::Row;
+use table::{Row, Table};
 use thiserror::Error;
 
+pub mod export;
 pub mod plot;
+pub mod prometheus;
 pub mod table;
+pub mod traces;
 
 /// A standard error is multiplied by this factor to get the error margin.
 /// For a normally distributed random variable,
@@ -39,17 +48,94 @@ pub struct Report {
     pub conf: RunCommand,
     pub percentiles: Vec<f32>,
     pub result: BenchmarkStats,
+    #[serde(default)]
+    pub stages: Vec<StageReport>,
+    #[serde(default)]
+    pub probes: Vec<SearchProbe>,
+    /// Set if the run was stopped with Ctrl-C before completing
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Path to the log of the traced requests, if any requests were traced
+    #[serde(default)]
+    pub trace_log: Option<PathBuf>,
+}
+
+/// Results of a single stage of a multi-stage run
+#[derive(Serialize, Deserialize)]
+pub struct StageReport {
+    pub name: String,
+    pub result: BenchmarkStats,
+}
+
+/// Loads the report from a JSON file. Exits with error if it can't be loaded.
+pub fn load_report_or_abort(path: &Path) -> Report {
+    match Report::load(path) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!(
+                "error: Failed to read report from {}: {}",
+                path.display(),
+                e
+            );
+            exit(1)
+        }
+    }
 }
 
 impl Report {
     /// Creates a new report from given configuration and results
     pub fn new(conf: RunCommand, result: BenchmarkStats) -> Report {
-        let percentiles: Vec<f32> = Percentile::iter().map(|p| p.value() as f32).collect();
+        let percentiles: Vec<f32> = result.percentiles.iter().map(|&p| p as f32).collect();
         Report {
             conf,
             percentiles,
             result,
+            stages: Vec::new(),
+            probes: Vec::new(),
+            interrupted: false,
+            trace_log: None,
+        }
+    }
+
+    /// Attaches the results of the individual stages of the run
+    pub fn with_stages(mut self, stages: Vec<StageReport>) -> Report {
+        self.stages = stages;
+        self
+    }
+
+    /// Attaches the probes of the max rate search
+    pub fn with_probes(mut self, probes: Vec<SearchProbe>) -> Report {
+        self.probes = probes;
+        self
+    }
+
+    /// Marks the report as covering only a part of the run stopped with Ctrl-C
+    pub fn with_interrupted(mut self, interrupted: bool) -> Report {
+        self.interrupted = interrupted;
+        self
+    }
+
+    /// Links the log of the requests traced during the run
+    pub fn with_trace_log(mut self, trace_log: Option<PathBuf>) -> Report {
+        self.trace_log = trace_log;
+        self
+    }
+
+    /// Returns the path to the trace log. A relative path that doesn't exist
+    /// is looked up in the directory of the report given by `report_path`.
+    pub fn trace_log_path(&self, report_path: &Path) -> Option<PathBuf> {
+        let trace_log = self.trace_log.as_ref()?;
+        if trace_log.is_relative() && !trace_log.exists() {
+            let dir = report_path.parent().unwrap_or(Path::new(""));
+            return Some(dir.join(trace_log.file_name()?));
         }
+        Some(trace_log.clone())
+    }
+
+    /// Finds the stage of this report matching the given stage of another report.
+    /// Stages are matched by name.
+    pub fn find_stage(&self, name: &str) -> Option<&StageReport> {
+        self.stages.iter().find(|s| s.name == name)
     }
     /// Loads benchmark results from a JSON file
     pub fn load(path: &Path) -> Result<Report, ReportLoadError> {
@@ -262,11 +348,17 @@ impl Rational for &str {
     }
 }
 
+impl Significance {
+    /// Returns one star for each significance level met, up to five stars
+    fn stars(&self) -> String {
+        let levels = [0.000001, 0.00001, 0.0001, 0.001, 0.01];
+        "*".repeat(levels.iter().filter(|&&l| l > self.0).count())
+    }
+}
+
 impl Display for Significance {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let levels = [0.000001, 0.00001, 0.0001, 0.001, 0.01];
-        let stars = "*".repeat(levels.iter().filter(|&&l| l > self.0).count());
-        let s = format!("{:7.5}  {:5}", self.0, stars);
+        let s = format!("{:7.5}  {:5}", self.0, self.stars());
         if self.0 <= 0.01 {
             write!(f, "{}", style(s).cyan().bright())
         } else {
@@ -286,12 +378,15 @@ where
     pub unit: String,
     /// 1 means the more of the quantity the better, -1 means the more of it the worse, 0 is neutral
     pub orientation: i8,
-    /// First object to measure
-    pub v1: V,
-    /// Second object to measure
-    pub v2: Option<V>,
-    /// Statistical significance level
-    pub significance: Option<Significance>,
+    /// Objects to measure, one per compared run; missing objects are displayed as empty cells
+    pub values: Vec<Option<V>>,
+    /// Index of the object the other objects are compared to
+    pub reference: usize,
+    /// Statistical significance level of the difference between each object and the reference
+    pub significance: Vec<Option<Significance>>,
+    /// If set, measurements different from the reference get highlighted
+    /// instead of displaying the relative change, when comparing more than two objects
+    pub highlight_diff: bool,
     /// Measurement function
     pub f: F,
 }
@@ -302,14 +397,23 @@ where
     V: Copy,
     F: Fn(V) -> M,
 {
-    fn new(label: String, unit: String, orientation: i8, v1: V, v2: Option<V>, f: F) -> Self {
+    fn new(
+        label: String,
+        unit: String,
+        orientation: i8,
+        values: Vec<Option<V>>,
+        reference: usize,
+        f: F,
+    ) -> Self {
+        let significance = vec![None; values.len()];
         Line {
             label,
             unit,
             orientation,
-            v1,
-            v2,
-            significance: None,
+            values,
+            reference,
+            significance,
+            highlight_diff: false,
             f,
         }
     }
@@ -323,11 +427,16 @@ where
         self
     }
 
-    fn with_significance(mut self, s: Option<Significance>) -> Self {
+    fn with_significance(mut self, s: Vec<Option<Significance>>) -> Self {
         self.significance = s;
         self
     }
 
+    fn with_highlight_diff(mut self) -> Self {
+        self.highlight_diff = true;
+        self
+    }
+
     /// Measures the object `v` by applying `f` to it and formats the measurement result.
     /// If the object is None, returns an empty string.
     fn fmt_measurement(&self, v: Option<V>) -> String {
@@ -335,14 +444,21 @@ where
             .unwrap_or_else(|| "".to_owned())
     }
 
-    /// Computes the relative difference between v2 and v1 as: 100.0 * f(v2) / f(v1) - 100.0.
-    /// Then formats the difference as percentage.
+    /// Formats the measurement of the object `v` without styling and error margin.
+    fn fmt_compact_measurement(&self, v: Option<V>) -> String {
+        let m = strip_ansi_codes(&self.fmt_measurement(v)).to_string();
+        m.split('±').next().unwrap_or_default().trim().to_string()
+    }
+
+    /// Computes the relative difference between the object at `index` and the reference object
+    /// as: 100.0 * f(v) / f(reference) - 100.0. Then formats the difference as percentage.
     /// If any of the values are missing, returns an empty String
-    fn fmt_relative_change(&self, direction: i8, significant: bool) -> String {
-        self.v2
-            .and_then(|v2| {
-                let m1 = (self.f)(self.v1);
-                let m2 = (self.f)(v2);
+    fn fmt_relative_change(&self, index: usize, direction: i8, significant: bool) -> String {
+        self.values[index]
+            .zip(self.values[self.reference])
+            .and_then(|(v, reference)| {
+                let m1 = (self.f)(v);
+                let m2 = (self.f)(reference);
                 let ratio = Rational::ratio(m1, m2);
                 ratio.map(|r| {
                     let mut diff = 100.0 * (r - 1.0);
@@ -364,12 +480,57 @@ where
             .unwrap_or_default()
     }
 
+    fn is_significant(&self, index: usize) -> bool {
+        match self.significance[index] {
+            None => false,
+            Some(s) => s.0 <= 0.01,
+        }
+    }
+
     fn fmt_unit(&self) -> String {
         match self.unit.as_str() {
             "" => "".to_string(),
             u => format!("[{u}]"),
         }
     }
+
+    fn fmt_label(&self) -> String {
+        format!(
+            "{label:>16} {unit:>9}",
+            label = style(&self.label).yellow().bold().for_stdout(),
+            unit = style(self.fmt_unit()).yellow(),
+        )
+    }
+
+    /// Formats the measurement of the object at `index` into a narrow cell
+    /// used when comparing more than two objects.
+    fn fmt_cell(&self, index: usize) -> String {
+        let m = self.fmt_compact_measurement(self.values[index]);
+        if self.highlight_diff {
+            let differs = m != self.fmt_compact_measurement(self.values[self.reference]);
+            let cell = pad_str(&m, CELL_WIDTH, Alignment::Left, Some("…")).to_string();
+            return if differs {
+                format!("{}", style(cell).bright().magenta().for_stdout())
+            } else {
+                cell
+            };
+        }
+        if index == self.reference || self.values[index].is_none() {
+            return pad_str(&m, CELL_WIDTH - 15, Alignment::Right, None).to_string();
+        }
+        let is_significant = self.is_significant(index);
+        let change = self.fmt_relative_change(index, self.orientation, is_significant);
+        let stars = match self.significance[index] {
+            Some(s) if is_significant => format!("{}", style(s.stars()).cyan().bright()),
+            _ => "".to_owned(),
+        };
+        format!(
+            "{m} {change} {stars}",
+            m = pad_str(&m, CELL_WIDTH - 15, Alignment::Right, None),
+            change = pad_str(&change, 8, Alignment::Right, None),
+            stars = pad_str(&stars, 5, Alignment::Left, None),
+        )
+    }
 }
 
 impl<M, V, F> Display for Line<M, V, F>
@@ -379,22 +540,29 @@ where
     V: Copy,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        // if v2 defined, put v2 on left
-        let m1 = self.fmt_measurement(self.v2.or(Some(self.v1)));
-        let m2 = self.fmt_measurement(self.v2.map(|_| self.v1));
-        let is_significant = match self.significance {
-            None => false,
-            Some(s) => s.0 <= 0.01,
-        };
+        if self.values.len() > 2 {
+            write!(f, "{} ", self.fmt_label())?;
+            for i in 0..self.values.len() {
+                write!(f, " {}", self.fmt_cell(i))?;
+            }
+            return Ok(());
+        }
+
+        // put the reference object on left
+        let other = (0..self.values.len()).find(|&i| i != self.reference);
+        let m1 = self.fmt_measurement(self.values[self.reference]);
+        let m2 = self.fmt_measurement(other.and_then(|i| self.values[i]));
+        let is_significant = other.is_some_and(|i| self.is_significant(i));
         write!(
             f,
-            "{label:>16} {unit:>9}  {m1} {m2}  {cmp:6}     {signif}",
-            label = style(&self.label).yellow().bold().for_stdout(),
-            unit = style(self.fmt_unit()).yellow(),
+            "{label}  {m1} {m2}  {cmp:6}     {signif}",
+            label = self.fmt_label(),
             m1 = pad_str(m1.as_str(), 30, Alignment::Left, None),
             m2 = pad_str(m2.as_str(), 30, Alignment::Left, None),
-            cmp = self.fmt_relative_change(self.orientation, is_significant),
-            signif = match &self.significance {
+            cmp = other
+                .map(|i| self.fmt_relative_change(i, self.orientation, is_significant))
+                .unwrap_or_default(),
+            signif = match other.and_then(|i| self.significance[i]) {
                 Some(s) => format!("{s}"),
                 None => "".to_owned(),
             }
@@ -404,6 +572,9 @@ where
 
 const REPORT_WIDTH: usize = 124;
 
+/// Width of the column of a single run, when comparing more than two runs
+const CELL_WIDTH: usize = 24;
+
 fn fmt_section_header(name: &str) -> String {
     format!(
         "{} {}",
@@ -434,38 +605,88 @@ fn fmt_cmp_header(display_significance: bool) -> String {
     format!("{}", style(header).yellow().bold().for_stdout())
 }
 
+/// Returns the letter identifying the run at given position in the comparison tables
+pub fn run_letter(index: usize) -> char {
+    (b'A' + (index % 26) as u8) as char
+}
+
+/// Formats the header of a comparison of more than two runs
+fn fmt_multi_cmp_header(run_count: usize, reference: usize) -> String {
+    let mut header = " ".repeat(27);
+    for i in 0..run_count {
+        let title = if i == reference {
+            format!(" {} (ref) ", run_letter(i))
+        } else {
+            format!(" {} ", run_letter(i))
+        };
+        let fill = CELL_WIDTH - title.len();
+        header += &format!(
+            " {}{}{}",
+            "─".repeat(fill / 2),
+            title,
+            "─".repeat(fill - fill / 2)
+        );
+    }
+    format!("{}", style(header).yellow().bold().for_stdout())
+}
+
+/// Returns the header of the comparison of given number of runs, if needed
+fn fmt_runs_header(
+    run_count: usize,
+    reference: usize,
+    display_significance: bool,
+) -> Option<String> {
+    match run_count {
+        0 | 1 => None,
+        2 => Some(fmt_cmp_header(display_significance)),
+        _ => Some(fmt_multi_cmp_header(run_count, reference)),
+    }
+}
+
+/// Stores the configurations of the compared runs.
+/// Each configuration is compared to the reference configuration.
 pub struct RunConfigCmp<'a> {
-    pub v1: &'a RunCommand,
-    pub v2: Option<&'a RunCommand>,
+    pub runs: Vec<&'a RunCommand>,
+    pub reference: usize,
 }
 
-impl RunConfigCmp<'_> {
-    fn line<S, M, F>(&self, label: S, unit: &str, f: F) -> Box<Line<M, &RunCommand, F>>
+impl<'a> RunConfigCmp<'a> {
+    /// Creates a comparison of the configuration `v1` to the baseline `v2`, if given
+    pub fn new(v1: &'a RunCommand, v2: Option<&'a RunCommand>) -> Self {
+        RunConfigCmp {
+            runs: v2.into_iter().chain([v1]).collect(),
+            reference: 0,
+        }
+    }
+
+    fn line<S, M, F>(&self, label: S, unit: &str, f: F) -> Box<Line<M, &'a RunCommand, F>>
     where
         S: ToString,
         M: Display + Rational,
         F: Fn(&RunCommand) -> M,
     {
-        Box::new(Line::new(
-            label.to_string(),
-            unit.to_string(),
-            0,
-            self.v1,
-            self.v2,
-            f,
-        ))
+        Box::new(
+            Line::new(
+                label.to_string(),
+                unit.to_string(),
+                0,
+                self.runs.iter().copied().map(Some).collect(),
+                self.reference,
+                f,
+            )
+            .with_highlight_diff(),
+        )
     }
 
     fn format_time(&self, conf: &RunCommand, format: &str) -> String {
         format_time(conf.timestamp, format)
     }
 
-    /// Returns the set union of custom user parameters in both configurations.
+    /// Returns the set union of custom user parameters in all configurations.
     fn param_names(&self) -> BTreeSet<&String> {
         let mut keys = BTreeSet::new();
-        keys.extend(self.v1.params.iter().map(|x| &x.0));
-        if let Some(v2) = self.v2 {
-            keys.extend(v2.params.iter().map(|x| &x.0));
+        for conf in &self.runs {
+            keys.extend(conf.params.iter().map(|x| &x.0));
         }
         keys
     }
@@ -474,8 +695,8 @@ impl RunConfigCmp<'_> {
 impl Display for RunConfigCmp<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "{}", fmt_section_header("CONFIG"))?;
-        if self.v2.is_some() {
-            writeln!(f, "{}", fmt_cmp_header(false))?;
+        if let Some(header) = fmt_runs_header(self.runs.len(), self.reference, false) {
+            writeln!(f, "{header}")?;
         }
 
         let lines: Vec<Box<dyn Display>> = vec![
@@ -561,6 +782,10 @@ impl Display for RunConfigCmp<'_> {
                 Quantity::from(conf.concurrency)
             }),
             self.line("Max rate", "op/s", |conf| Quantity::from(conf.rate.rate)),
+            self.line("Arrival", "", |conf| conf.rate.arrival.to_string()),
+            self.line("Think time", "", |conf| {
+                OptionDisplay(conf.rate.think_time.map(|t| t.to_string()))
+            }),
             self.line("Warmup", "s", |conf| {
                 Quantity::from(conf.warmup_duration.period_secs())
             }),
@@ -573,12 +798,68 @@ impl Display for RunConfigCmp<'_> {
             self.line("└─", "op", |conf| {
                 Quantity::from(conf.run_duration.count())
             }),
+        ];
+        for l in lines {
+            writeln!(f, "{l}")?;
+        }
+
+        let stage_count = self
+            .runs
+            .iter()
+            .map(|c| c.stages.len())
+            .max()
+            .unwrap_or_default();
+        for i in 0..stage_count {
+            let name = self
+                .runs
+                .iter()
+                .find_map(|c| c.stages.get(i))
+                .map(|s| s.name_or_default(i))
+                .unwrap_or_default();
+            let line = self.line(format!("Stage {name}"), "", |conf| {
+                OptionDisplay(conf.stages.get(i).map(|s| s.to_string()))
+            });
+            writeln!(f, "{line}")?;
+        }
+
+        let mix_count = self
+            .runs
+            .iter()
+            .map(|c| c.function_mix.len())
+            .max()
+            .unwrap_or_default();
+        for i in 0..mix_count {
+            let line = self.line("Function mix", "", |conf| {
+                OptionDisplay(conf.function_mix.get(i).map(|m| m.to_string()))
+            });
+            writeln!(f, "{line}")?;
+        }
+
+        if self.runs.iter().any(|c| c.rate.rate_profile.is_some()) {
+            let line = self.line("Rate profile", "", |conf| {
+                OptionDisplay(conf.rate.rate_profile.as_ref().map(|p| p.to_string()))
+            });
+            writeln!(f, "{line}")?;
+        }
+
+        if self.runs.iter().any(|c| c.search.find_max_rate) {
+            let line = self.line("Max rate search", "", |conf| {
+                OptionDisplay(conf.search.find_max_rate.then(|| conf.search.to_string()))
+            });
+            writeln!(f, "{line}")?;
+        }
+
+        let lines: Vec<Box<dyn Display>> = vec![
             self.line("Sampling", "s", |conf| {
                 Quantity::from(conf.sampling_interval.period_secs()).with_precision(1)
             }),
             self.line("└─", "op", |conf| {
                 Quantity::from(conf.sampling_interval.count())
             }),
+            self.line("Latency histogram", "", |conf| conf.latency.to_string()),
+            self.line("Late threshold", "ms", |conf| {
+                Quantity::from(conf.latency.late_threshold.as_secs_f64() * 1000.0)
+            }),
             self.line("Request timeout", "s", |conf| {
                 Quantity::from(conf.connection.request_timeout.as_secs_f64())
             }),
@@ -589,14 +870,96 @@ impl Display for RunConfigCmp<'_> {
             self.line("├─min interval", "ms", |conf| {
                 Quantity::from(conf.connection.retry_interval.min.as_secs_f64() * 1000.0)
             }),
-            self.line("└─max interval", "ms", |conf| {
+            self.line("├─max interval", "ms", |conf| {
                 Quantity::from(conf.connection.retry_interval.max.as_secs_f64() * 1000.0)
             }),
+            self.line("└─backoff", "", |conf| {
+                let backoff = conf.connection.retry_backoff.to_possible_value().unwrap();
+                backoff.get_name().to_string()
+            }),
         ];
 
         for l in lines {
             writeln!(f, "{l}")?;
         }
+        if self
+            .runs
+            .iter()
+            .any(|c| !c.connection.error_rules.is_empty())
+        {
+            let line = self.line("On error", "", |conf| {
+                conf.connection.error_rules.iter().join(", ")
+            });
+            writeln!(f, "{line}")?;
+        }
+        if self
+            .runs
+            .iter()
+            .any(|c| c.connection.speculative_retry.is_some())
+        {
+            let line = self.line("Speculative retry", "ms", |conf| {
+                Quantity::from(
+                    conf.connection
+                        .speculative_retry
+                        .map(|d| d.as_secs_f64() * 1000.0),
+                )
+            });
+            writeln!(f, "{line}")?;
+        }
+        #[cfg(feature = "cql")]
+        if self
+            .runs
+            .iter()
+            .any(|c| !c.connection.db.execution_profiles.is_empty())
+        {
+            let line = self.line("Execution profiles", "", |conf| {
+                conf.connection.db.execution_profiles.iter().join("; ")
+            });
+            writeln!(f, "{line}")?;
+        }
+        #[cfg(feature = "cql")]
+        {
+            let line = self.line("Load balancing", "", |conf| {
+                let policy = conf
+                    .connection
+                    .db
+                    .load_balancing
+                    .to_possible_value()
+                    .unwrap();
+                policy.get_name().to_string()
+            });
+            writeln!(f, "{line}")?;
+            let line = self.line("Speculative execution", "", |conf| {
+                OptionDisplay(
+                    conf.connection
+                        .db
+                        .speculative_execution
+                        .map(|s| s.to_string()),
+                )
+            });
+            writeln!(f, "{line}")?;
+            let line = self.line("Driver retry policy", "", |conf| {
+                let policy = conf.connection.db.driver_retry_policy.to_possible_value();
+                policy.unwrap().get_name().to_string()
+            });
+            writeln!(f, "{line}")?;
+        }
+        #[cfg(feature = "cql")]
+        if self
+            .runs
+            .iter()
+            .any(|c| c.connection.db.trace_slow.is_some())
+        {
+            let line = self.line("Trace slow", "ms", |conf| {
+                Quantity::from(
+                    conf.connection
+                        .db
+                        .trace_slow
+                        .map(|d| d.as_secs_f64() * 1000.0),
+                )
+            });
+            writeln!(f, "{line}")?;
+        }
         Ok(())
     }
 }
@@ -647,8 +1010,8 @@ impl Display for Sample {
     }
 }
 
-impl BenchmarkCmp<'_> {
-    fn line<S, M, F>(&self, label: S, unit: &str, f: F) -> Box<Line<M, &BenchmarkStats, F>>
+impl<'a> BenchmarkMultiCmp<'a> {
+    fn line<S, M, F>(&self, label: S, unit: &str, f: F) -> Box<Line<M, &'a BenchmarkStats, F>>
     where
         S: ToString,
         M: Display + Rational,
@@ -658,8 +1021,8 @@ impl BenchmarkCmp<'_> {
             label.to_string(),
             unit.to_string(),
             0,
-            self.v1,
-            self.v2,
+            self.runs.clone(),
+            self.reference,
             f,
         ))
     }
@@ -668,9 +1031,126 @@ impl BenchmarkCmp<'_> {
 /// Formats all benchmark stats
 impl Display for BenchmarkCmp<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}", fmt_section_header("SUMMARY STATS"))?;
-        if self.v2.is_some() {
-            writeln!(f, "{}", fmt_cmp_header(true))?;
+        BenchmarkMultiCmp::from(self).fmt_with_title(f, "SUMMARY STATS")
+    }
+}
+
+/// Formats all benchmark stats of many runs
+impl Display for BenchmarkMultiCmp<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_with_title(f, "SUMMARY STATS")
+    }
+}
+
+/// Stores the statistics of a single stage of the compared multi-stage runs.
+pub struct StageCmp<'a> {
+    pub name: &'a str,
+    pub cmp: BenchmarkMultiCmp<'a>,
+}
+
+impl<'a> StageCmp<'a> {
+    /// Creates a comparison of the stage stats `v1` to the baseline `v2`, if given
+    pub fn new(name: &'a str, v1: &'a BenchmarkStats, v2: Option<&'a BenchmarkStats>) -> Self {
+        StageCmp {
+            name,
+            cmp: BenchmarkMultiCmp::from(&BenchmarkCmp { v1, v2 }),
+        }
+    }
+}
+
+impl Display for StageCmp<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.cmp
+            .fmt_with_title(f, format!("STAGE {}", self.name).as_str())
+    }
+}
+
+impl BenchmarkMultiCmp<'_> {
+    /// Formats a section for each statement, batch, node or shard selected by `select`.
+    /// Errors and retries are not shown for nodes and shards,
+    /// because only successful requests are attributed to them.
+    fn fmt_statements(
+        &self,
+        f: &mut Formatter<'_>,
+        title: &str,
+        header: &Option<String>,
+        resp_time_percentiles: &[f64],
+        select: fn(&BenchmarkStats) -> &BTreeMap<String, StatementSummary>,
+        with_errors: bool,
+    ) -> fmt::Result {
+        let keys: BTreeSet<&String> = self
+            .runs
+            .iter()
+            .flatten()
+            .flat_map(|s| select(s).keys())
+            .collect();
+        for key in keys {
+            writeln!(f)?;
+            let title = format!("{title} {key} ");
+            writeln!(f, "{}", fmt_section_header(title.as_str()))?;
+            if let Some(header) = header {
+                writeln!(f, "{header}")?;
+            }
+            let mut lines: Vec<Box<dyn Display>> = vec![self.line("Requests", "req", |s| {
+                Quantity::from(select(s).get(key).map(|st| st.request_count))
+            })];
+            if with_errors {
+                lines.push(self.line("Errors", "req", |s| {
+                    Quantity::from(select(s).get(key).map(|st| st.error_count))
+                }));
+                lines.push(self.line("Retries", "ret", |s| {
+                    Quantity::from(select(s).get(key).map(|st| st.retry_count))
+                }));
+            }
+            lines.push(self.line("Rows", "row", |s| {
+                Quantity::from(select(s).get(key).map(|st| st.row_count))
+            }));
+            if self
+                .runs
+                .iter()
+                .flatten()
+                .any(|s| select(s).get(key).is_some_and(|st| st.statement_count > 0))
+            {
+                lines.push(self.line("Statements", "stmt", |s| {
+                    Quantity::from(select(s).get(key).map(|st| st.statement_count))
+                }));
+                lines.push(self.line("└─", "stmt/req", |s| {
+                    Quantity::from(select(s).get(key).and_then(|st| st.mean_size()))
+                        .with_precision(1)
+                }));
+            }
+            lines.push(
+                self.line("Mean latency", "ms", |s| {
+                    let latency = select(s).get(key).and_then(|st| st.latency.as_ref());
+                    Quantity::from(latency.map(|l| l.mean)).with_precision(3)
+                })
+                .with_orientation(-1)
+                .into_box(),
+            );
+            for l in lines {
+                writeln!(f, "{l}")?;
+            }
+            for p in resp_time_percentiles.iter() {
+                let l = self
+                    .line(percentile_name(*p), "ms", |s| {
+                        let rt = select(s)
+                            .get(key)
+                            .and_then(|st| st.latency.as_ref())
+                            .and_then(|l| l.percentiles.find(*p));
+                        Quantity::from(rt).with_precision(3)
+                    })
+                    .with_orientation(-1);
+                writeln!(f, "{l}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fmt_with_title(&self, f: &mut Formatter<'_>, title: &str) -> fmt::Result {
+        writeln!(f, "{}", fmt_section_header(title))?;
+        let header = fmt_runs_header(self.runs.len(), self.reference, true);
+        if let Some(header) = &header {
+            writeln!(f, "{header}")?;
         }
 
         let summary: Vec<Box<dyn Display>> = vec![
@@ -688,6 +1168,10 @@ impl Display for BenchmarkCmp<'_> {
             self.line("└─", "%", |s| {
                 Quantity::from(s.errors_ratio).with_precision(1)
             }),
+            self.line("Late cycles", "op", |s| Quantity::from(s.late_cycle_count)),
+            self.line("└─", "%", |s| {
+                Quantity::from(s.late_cycle_ratio).with_precision(1)
+            }),
             self.line("Requests", "req", |s| Quantity::from(s.request_count)),
             self.line("└─", "req/op", |s| {
                 Quantity::from(s.requests_per_cycle).with_precision(1)
@@ -709,31 +1193,36 @@ impl Display for BenchmarkCmp<'_> {
             self.line("Throughput", "op/s", |s| {
                 Quantity::from(s.cycle_throughput).with_precision(0)
             })
-            .with_significance(self.cmp_cycle_throughput())
+            .with_significance(self.significance(BenchmarkCmp::cmp_cycle_throughput))
             .with_orientation(1)
             .into_box(),
             self.line("├─", "req/s", |s| {
                 Quantity::from(s.req_throughput).with_precision(0)
             })
-            .with_significance(self.cmp_req_throughput())
+            .with_significance(self.significance(BenchmarkCmp::cmp_req_throughput))
             .with_orientation(1)
             .into_box(),
             self.line("└─", "row/s", |s| {
                 Quantity::from(s.row_throughput).with_precision(0)
             })
-            .with_significance(self.cmp_row_throughput())
+            .with_significance(self.significance(BenchmarkCmp::cmp_row_throughput))
             .with_orientation(1)
             .into_box(),
             self.line("Cycle latency", "ms", |s| {
                 Quantity::from(s.cycle_latency.mean).with_precision(3)
             })
-            .with_significance(self.cmp_mean_resp_time())
+            .with_significance(self.significance(BenchmarkCmp::cmp_mean_resp_time))
             .with_orientation(-1)
             .into_box(),
             self.line("Request latency", "ms", |s| {
                 Quantity::from(s.request_latency.as_ref().map(|rt| rt.mean)).with_precision(3)
             })
-            .with_significance(self.cmp_mean_resp_time())
+            .with_significance(self.significance(BenchmarkCmp::cmp_mean_resp_time))
+            .with_orientation(-1)
+            .into_box(),
+            self.line("Start delay", "ms", |s| {
+                Quantity::from(s.start_delay.as_ref().map(|d| d.mean)).with_precision(3)
+            })
             .with_orientation(-1)
             .into_box(),
         ];
@@ -741,50 +1230,164 @@ impl Display for BenchmarkCmp<'_> {
             writeln!(f, "{l}")?;
         }
 
-        let resp_time_percentiles = [
-            Percentile::Min,
-            Percentile::P25,
-            Percentile::P50,
-            Percentile::P75,
-            Percentile::P90,
-            Percentile::P95,
-            Percentile::P98,
-            Percentile::P99,
-            Percentile::P99_9,
-            Percentile::P99_99,
-            Percentile::Max,
-        ];
+        let error_categories = ErrorCategory::ALL
+            .into_iter()
+            .filter(|c| {
+                self.runs
+                    .iter()
+                    .flatten()
+                    .any(|s| s.error_counts.get(*c) > 0)
+            })
+            .collect_vec();
+        if !error_categories.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", fmt_section_header("ERRORS BY CATEGORY"))?;
+            if let Some(header) = &header {
+                writeln!(f, "{header}")?;
+            }
+            for category in error_categories {
+                let count = self.line(category, "err", |s| {
+                    Quantity::from(s.error_counts.get(category))
+                });
+                let rate = self.line("└─", "err/s", |s| {
+                    Quantity::from(s.error_counts.get(category) as f64 / s.elapsed_time_s)
+                        .with_precision(2)
+                });
+                writeln!(f, "{count}")?;
+                writeln!(f, "{rate}")?;
+            }
+        }
+
+        let resp_time_percentiles = self
+            .runs
+            .get(self.reference)
+            .copied()
+            .flatten()
+            .or_else(|| self.runs.iter().flatten().next().copied())
+            .map(|s| s.percentiles.clone())
+            .unwrap_or_default();
+
+        let fn_names: BTreeSet<&String> = self
+            .runs
+            .iter()
+            .flatten()
+            .flat_map(|s| s.cycle_latency_by_fn.keys())
+            .collect();
+        for fn_name in fn_names {
+            // Reports saved by older versions don't have the counts of the functions
+            if self
+                .runs
+                .iter()
+                .flatten()
+                .any(|s| s.counts_by_fn.contains_key(fn_name))
+            {
+                writeln!(f)?;
+                let title = format!("CALLS of {fn_name} ");
+                writeln!(f, "{}", fmt_section_header(title.as_str()))?;
+                if let Some(header) = &header {
+                    writeln!(f, "{header}")?;
+                }
+                let counts = |s: &BenchmarkStats| s.counts_by_fn.get(fn_name.as_str()).cloned();
+                let lines: Vec<Box<dyn Display>> = vec![
+                    self.line("Calls", "op", |s| {
+                        Quantity::from(counts(s).map(|c| c.call_count))
+                    }),
+                    self.line("Errors", "op", |s| {
+                        Quantity::from(counts(s).map(|c| c.error_count))
+                    }),
+                    self.line("└─", "%", |s| {
+                        Quantity::from(counts(s).and_then(|c| c.error_ratio())).with_precision(1)
+                    }),
+                    self.line("Requests", "req", |s| {
+                        Quantity::from(counts(s).map(|c| c.request_count))
+                    }),
+                    self.line("Retries", "ret", |s| {
+                        Quantity::from(counts(s).map(|c| c.retry_count))
+                    }),
+                    self.line("Rows", "row", |s| {
+                        Quantity::from(counts(s).map(|c| c.row_count))
+                    }),
+                    self.line("Throughput", "op/s", |s| {
+                        Quantity::from(s.fn_throughput(fn_name)).with_precision(0)
+                    })
+                    .with_orientation(1)
+                    .into_box(),
+                ];
+                for l in lines {
+                    writeln!(f, "{l}")?;
+                }
+            }
 
-        for fn_name in self.v1.cycle_latency_by_fn.keys() {
             writeln!(f)?;
             writeln!(
                 f,
                 "{}",
                 fmt_section_header(format!("CYCLE LATENCY for {fn_name} [ms] ").as_str())
             )?;
-            if self.v2.is_some() {
-                writeln!(f, "{}", fmt_cmp_header(true))?;
+            if let Some(header) = &header {
+                writeln!(f, "{header}")?;
             }
 
             for p in resp_time_percentiles.iter() {
                 let l = self
-                    .line(p.name(), "", |s| {
+                    .line(percentile_name(*p), "", |s| {
                         let rt = s
                             .cycle_latency_by_fn
                             .get(fn_name)
-                            .map(|l| l.percentiles.get(*p));
+                            .and_then(|l| l.percentiles.find(*p));
                         Quantity::from(rt).with_precision(3)
                     })
                     .with_orientation(-1)
-                    .with_significance(self.cmp_resp_time_percentile(*p));
+                    .with_significance(self.significance(|c| c.cmp_resp_time_percentile(*p)));
                 writeln!(f, "{l}")?;
             }
         }
 
-        if self.v1.error_count > 0 {
+        let percentiles = &resp_time_percentiles;
+        self.fmt_statements(
+            f,
+            "STATEMENT",
+            &header,
+            percentiles,
+            |s| &s.statements,
+            true,
+        )?;
+        self.fmt_statements(f, "BATCH", &header, percentiles, |s| &s.batches, true)?;
+        self.fmt_statements(f, "NODE", &header, percentiles, |s| &s.nodes, false)?;
+        self.fmt_statements(f, "SHARD", &header, percentiles, |s| &s.shards, false)?;
+
+        // Start delays matter only if the client could not keep up with the schedule
+        if self.runs.iter().flatten().any(|s| s.late_cycle_count > 0) {
             writeln!(f)?;
-            writeln!(f, "{}", fmt_section_header("ERRORS"))?;
-            for e in self.v1.errors.iter() {
+            writeln!(f, "{}", fmt_section_header("START DELAY [ms]"))?;
+            if let Some(header) = &header {
+                writeln!(f, "{header}")?;
+            }
+            for p in resp_time_percentiles.iter() {
+                let l = self
+                    .line(percentile_name(*p), "", |s| {
+                        let delay = s.start_delay.as_ref().and_then(|d| d.percentiles.find(*p));
+                        Quantity::from(delay).with_precision(3)
+                    })
+                    .with_orientation(-1);
+                writeln!(f, "{l}")?;
+            }
+        }
+
+        // The errors of the reference run are skipped, unless it is the only run
+        for (i, stats) in self.runs.iter().enumerate() {
+            let Some(stats) = stats else { continue };
+            if stats.error_count == 0 || (i == self.reference && self.runs.len() > 1) {
+                continue;
+            }
+            let title = if self.runs.len() > 2 {
+                format!("ERRORS in {}", run_letter(i))
+            } else {
+                "ERRORS".to_string()
+            };
+            writeln!(f)?;
+            writeln!(f, "{}", fmt_section_header(&title))?;
+            for e in stats.errors.iter() {
                 writeln!(f, "{e}")?;
             }
         }
@@ -857,6 +1460,117 @@ impl Row for PathAndSummary {
     }
 }
 
+impl SearchProbe {
+    pub const COLUMNS: &'static [&'static str] = &[
+        "Rate [op/s]",
+        "Thrpt. [op/s]",
+        "P50 [ms]",
+        "P99 [ms]",
+        "P99.9 [ms]",
+        "Checked [ms]",
+        "Errors [%]",
+        "Result",
+    ];
+}
+
+impl Row for SearchProbe {
+    fn cell_value(&self, column: &str) -> Option<String> {
+        match column {
+            "Rate [op/s]" => Some(format!("{:.0}", self.rate)),
+            "Thrpt. [op/s]" => Some(format!("{:.0}", self.throughput)),
+            "P50 [ms]" => Some(format!("{:.3}", self.latency_p50_ms)),
+            "P99 [ms]" => Some(format!("{:.3}", self.latency_p99_ms)),
+            "P99.9 [ms]" => Some(format!("{:.3}", self.latency_p99_9_ms)),
+            "Checked [ms]" => Some(format!("{:.3}", self.latency_ms)),
+            "Errors [%]" => Some(format!("{:.2}", self.errors_ratio)),
+            "Result" => Some(if self.passed { "pass" } else { "fail" }.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Lists the compared reports with the letters identifying them in the comparison tables
+pub struct ReportList<'a> {
+    pub paths: &'a [PathBuf],
+    pub reference: usize,
+}
+
+impl Display for ReportList<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", fmt_section_header("REPORTS"))?;
+        for (i, path) in self.paths.iter().enumerate() {
+            let letter = style(run_letter(i)).yellow().bold().for_stdout();
+            let reference = if i == self.reference {
+                " (reference)"
+            } else {
+                ""
+            };
+            writeln!(f, "{letter}  {}{reference}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists all probes of the max rate search and the maximum rate found
+pub struct SearchSummary<'a> {
+    pub conf: &'a SearchConf,
+    pub probes: &'a [SearchProbe],
+}
+
+impl Display for SearchSummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", fmt_section_header("MAX RATE SEARCH"))?;
+        writeln!(
+            f,
+            "Checked: P{} of cycle latency <= {:.3} ms, errors <= {}%",
+            self.conf.percentile,
+            self.conf.latency_limit.as_secs_f64() * 1000.0,
+            self.conf.error_limit
+        )?;
+        writeln!(f)?;
+        let mut table = Table::new(SearchProbe::COLUMNS);
+        for i in 0..SearchProbe::COLUMNS.len() - 1 {
+            table.align(i, table::Alignment::Right);
+        }
+        for probe in self.probes {
+            table.push(probe.clone());
+        }
+        writeln!(f, "{table}")?;
+        let max_rate = self
+            .probes
+            .iter()
+            .filter(|p| p.passed)
+            .map(|p| p.rate)
+            .reduce(f64::max);
+        match max_rate {
+            Some(rate) => writeln!(f, "Max rate: {rate:.0} op/s"),
+            None => writeln!(f, "Max rate: none of the probed rates met the limits"),
+        }
+    }
+}
+
+/// Lists the outcomes of the assertions checked against the results of a run
+pub struct AssertionSummary<'a>(pub &'a [AssertionOutcome]);
+
+impl Display for AssertionSummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", fmt_section_header("ASSERTIONS"))?;
+        for outcome in self.0 {
+            let status = if outcome.passed {
+                style("PASSED").bright().green().for_stdout()
+            } else {
+                style("FAILED").bright().red().for_stdout()
+            };
+            let value = match outcome.value {
+                Some(v) => format!("{v:.6}"),
+                None => "n/a".to_string(),
+            };
+            writeln!(f, "{status}  {}  (actual: {value})", outcome.assertion)?;
+        }
+        Ok(())
+    }
+}
+
 fn format_time(timestamp: Option<i64>, format: &str) -> String {
     timestamp
         .and_then(|ts| {
@@ -867,3 +1581,51 @@ fn format_time(timestamp: Option<i64>, format: &str) -> String {
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::workload::{FnRef, FnStats, WorkloadStats};
+    use crate::stats::session::SessionStats;
+    use crate::stats::{Recorder, SampleWriters};
+    use std::time::{Duration, Instant};
+
+    fn stats(latency_ms: u64) -> BenchmarkStats {
+        let mut writers = SampleWriters::default();
+        let concurrency = NonZeroUsize::new(1).unwrap();
+        let mut recorder = Recorder::start(None, concurrency, false, &mut writers);
+        for i in 0..10 {
+            let mut fn_stats = FnStats::new(FnRef::new("read"));
+            for j in 0..100 {
+                fn_stats.operation_completed(Duration::from_micros(latency_ms * 1000 + i * 10 + j));
+            }
+            let start_time = Instant::now();
+            recorder.record(&[WorkloadStats {
+                start_time,
+                end_time: start_time + Duration::from_secs(1),
+                function_stats: vec![fn_stats],
+                session_stats: SessionStats::new(),
+            }]);
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn compare_many_runs() {
+        let runs = [stats(1), stats(2), stats(4)];
+        let cmp = BenchmarkMultiCmp {
+            runs: runs.iter().map(Some).collect(),
+            reference: 1,
+        };
+        let text = strip_ansi_codes(&cmp.to_string()).to_string();
+        assert!(text.lines().nth(1).unwrap().contains(" B (ref) "));
+        let min_latency = text
+            .lines()
+            .find(|l| l.trim_start().starts_with("Min"))
+            .unwrap();
+        assert_eq!(
+            min_latency.split_whitespace().collect_vec(),
+            ["Min", "1.000", "-50.0%", "2.000", "4.000", "+100.0%"]
+        );
+    }
+}
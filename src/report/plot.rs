@@ -1,22 +1,28 @@
-use crate::config::PlotCommand;
-use crate::load_report_or_abort;
-use crate::report::plot::SeriesKind::{ResponseTime, Throughput};
+use crate::config::{FnCounter, PlotCommand};
+use crate::report::load_report_or_abort;
+use crate::report::plot::SeriesKind::{ErrorRate, ResponseTime, Throughput};
 use crate::report::Report;
-use crate::Result;
+use crate::stats::errors::ErrorCategory;
+use crate::{LatteError, Result};
 use itertools::Itertools;
 use plotters::coord::ranged1d::{DefaultFormatting, KeyPointHint};
 use plotters::coord::types::RangedCoordf32;
 use plotters::prelude::full_palette::ORANGE;
 use plotters::prelude::*;
 use std::collections::BTreeSet;
+use std::fs;
 use std::ops::Range;
 use std::path::PathBuf;
 use std::process::exit;
 
+/// Number of colors used for telling apart the series
+const COLOR_COUNT: usize = 6;
+
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Ord, PartialOrd)]
 enum SeriesKind {
     ResponseTime,
     Throughput,
+    ErrorRate,
 }
 
 impl SeriesKind {
@@ -24,6 +30,7 @@ impl SeriesKind {
         match self {
             ResponseTime => "response time [ms]",
             Throughput => "throughput [req/s]",
+            ErrorRate => "errors [err/s]",
         }
     }
 }
@@ -105,7 +112,49 @@ pub async fn plot_graph(conf: PlotCommand) -> Result<()> {
         .collect_vec();
     assert!(!reports.is_empty());
 
-    let data = data(&reports, &conf);
+    // Plot the percentiles chosen to be reported, unless told otherwise
+    let throughput = conf.throughput || !conf.by_function.is_empty();
+    let percentiles = if conf.percentiles.is_empty() && !throughput && !conf.errors {
+        reports[0].result.percentiles.clone()
+    } else {
+        conf.percentiles
+    };
+    let svg = match plot_svg(
+        &reports,
+        &percentiles,
+        throughput,
+        &conf.by_function,
+        conf.errors,
+    ) {
+        Ok(svg) => svg,
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit(1);
+        }
+    };
+
+    let output_path = conf.output.unwrap_or(PathBuf::from(format!(
+        "latte-{}.svg",
+        reports[0].conf.id.as_ref().unwrap()
+    )));
+    fs::write(&output_path, svg)
+        .map_err(|e| LatteError::OutputFileCreate(output_path.clone(), e))?;
+    eprintln!("Saved output image to: {}", output_path.display());
+    Ok(())
+}
+
+/// Plots the sampling logs of given reports and returns the SVG image as a string.
+/// Plots response time `percentiles` and, if `throughput` is set, the throughput.
+/// Plots also the rate of each of the `by_function` counters of each function.
+/// If `errors` is set, plots the rate of errors of each category.
+pub fn plot_svg(
+    reports: &[Report],
+    percentiles: &[f64],
+    throughput: bool,
+    by_function: &[FnCounter],
+    errors: bool,
+) -> std::result::Result<String, String> {
+    let data = data(reports, percentiles, throughput, by_function, errors);
     let scales: BTreeSet<SeriesKind> = data.iter().map(|s| s.kind).collect();
     let scales = scales.into_iter().collect_vec();
 
@@ -129,24 +178,23 @@ pub async fn plot_graph(conf: PlotCommand) -> Result<()> {
 
     let primary_y_spec: YSpec = match scales.as_slice() {
         [ResponseTime] => YSpec::Log((min_value..max_value).log_scale().into()),
-        [Throughput] => YSpec::Linear((0f32..max_value).into()),
+        [Throughput] | [ErrorRate] => YSpec::Linear((0f32..max_value).into()),
         [] => {
-            eprintln!("error: No data series selected. Add --throughput or --percentile options.");
-            exit(1);
+            return Err(
+                "No data series selected. Add --throughput, --errors or --percentile options."
+                    .to_string(),
+            );
         }
         _ => {
-            eprintln!(
-                "error: Plotting throughput and response times in one graph is not supported."
+            return Err(
+                "Plotting throughput, errors or response times in one graph is not supported."
+                    .to_string(),
             );
-            exit(1);
         }
     };
 
-    let output_path = conf.output.unwrap_or(PathBuf::from(format!(
-        "latte-{}.svg",
-        reports[0].conf.id.as_ref().unwrap()
-    )));
-    let root = SVGBackend::new(&output_path, (2000, 1000)).into_drawing_area();
+    let mut svg = String::new();
+    let root = SVGBackend::with_string(&mut svg, (2000, 1000)).into_drawing_area();
     root.fill(&WHITE).unwrap();
 
     let mut chart = ChartBuilder::on(&root)
@@ -166,7 +214,7 @@ pub async fn plot_graph(conf: PlotCommand) -> Result<()> {
         .draw()
         .unwrap();
 
-    let colors = [&RED, &BLUE, &GREEN, &ORANGE, &MAGENTA, &BLACK];
+    let colors: [&RGBColor; COLOR_COUNT] = [&RED, &BLUE, &GREEN, &ORANGE, &MAGENTA, &BLACK];
     const SYMBOL_SIZE: u32 = 6;
 
     for series in data {
@@ -216,28 +264,47 @@ pub async fn plot_graph(conf: PlotCommand) -> Result<()> {
         .position(SeriesLabelPosition::UpperRight)
         .draw()
         .unwrap();
-
-    eprintln!("Saved output image to: {}", output_path.display());
-    Ok(())
+    root.present().unwrap();
+    drop(chart);
+    drop(root);
+    Ok(svg)
 }
 
-fn data(reports: &[Report], conf: &PlotCommand) -> Vec<Series> {
+fn data(
+    reports: &[Report],
+    percentiles: &[f64],
+    throughput: bool,
+    by_function: &[FnCounter],
+    errors: bool,
+) -> Vec<Series> {
     let mut series = vec![];
     for (color_index, report) in reports.iter().enumerate() {
-        series.extend(report_series(report, color_index, conf));
+        series.extend(report_series(report, color_index, percentiles, throughput));
+        for (i, counter) in by_function.iter().unique().enumerate() {
+            series.extend(fn_rate_series(report, color_index, *counter, 2 + i));
+        }
+        if errors {
+            series.extend(error_rate_series(report, color_index));
+        }
     }
     series
 }
 
 /// Generates data from given report
-fn report_series(report: &Report, color_index: usize, conf: &PlotCommand) -> Vec<Series> {
+fn report_series(
+    report: &Report,
+    color_index: usize,
+    percentiles: &[f64],
+    throughput: bool,
+) -> Vec<Series> {
     let mut series = vec![];
-    let mut percentiles = conf.percentiles.clone();
+    let mut percentiles = percentiles.to_vec();
     percentiles.sort_by(|a, b| a.partial_cmp(b).unwrap().reverse());
 
     series.extend(resp_time_series(report, color_index, &percentiles));
-    if conf.throughput {
-        series.push(throughput_series(report, color_index))
+    if throughput {
+        series.push(throughput_series(report, color_index));
+        series.extend(target_rate_series(report, color_index));
     }
     series
 }
@@ -282,3 +349,90 @@ fn throughput_series(report: &Report, color_index: usize) -> Series {
             .collect(),
     }
 }
+
+/// Returns the series of the rate of the counter of each function, e.g. calls per second.
+/// The functions are told apart by color, starting from the color following the report color,
+/// and the counters by the symbol.
+fn fn_rate_series(
+    report: &Report,
+    color_index: usize,
+    counter: FnCounter,
+    symbol_index: usize,
+) -> Vec<Series> {
+    let functions: BTreeSet<&String> = report
+        .result
+        .log
+        .iter()
+        .flat_map(|s| s.counts_by_fn.keys())
+        .collect();
+    functions
+        .into_iter()
+        .enumerate()
+        .map(|(i, function)| Series {
+            tags: report.conf.tags.clone(),
+            label: format!("{function} {}", counter.name()),
+            color_index: (color_index + i + 1) % COLOR_COUNT,
+            symbol_index,
+            kind: Throughput,
+            data: report
+                .result
+                .log
+                .iter()
+                .map(|s| {
+                    let counts = s.counts_by_fn.get(function).cloned().unwrap_or_default();
+                    let count = match counter {
+                        FnCounter::Calls => counts.call_count,
+                        FnCounter::Errors => counts.error_count,
+                        FnCounter::Retries => counts.retry_count,
+                        FnCounter::Rows => counts.row_count,
+                    };
+                    (s.time_s, count as f32 / s.duration_s)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Returns the series of the rate of errors of each category occurring in the report.
+/// The categories are told apart by color, starting from the report color.
+fn error_rate_series(report: &Report, color_index: usize) -> Vec<Series> {
+    ErrorCategory::ALL
+        .into_iter()
+        .filter(|c| report.result.log.iter().any(|s| s.error_counts.get(*c) > 0))
+        .enumerate()
+        .map(|(i, category)| Series {
+            tags: report.conf.tags.clone(),
+            label: format!("{category} errors"),
+            color_index: (color_index + i) % COLOR_COUNT,
+            symbol_index: 0,
+            kind: ErrorRate,
+            data: report
+                .result
+                .log
+                .iter()
+                .map(|s| (s.time_s, s.error_counts.get(category) as f32 / s.duration_s))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Returns the series of the rate the rate limiter aimed at, if the rate was limited
+fn target_rate_series(report: &Report, color_index: usize) -> Option<Series> {
+    let data = report
+        .result
+        .log
+        .iter()
+        .filter_map(|s| Some((s.time_s, s.target_rate?)))
+        .collect_vec();
+    if data.is_empty() {
+        return None;
+    }
+    Some(Series {
+        tags: report.conf.tags.clone(),
+        label: String::from("target rate"),
+        color_index,
+        symbol_index: 1,
+        kind: Throughput,
+        data,
+    })
+}
@@ -0,0 +1,343 @@
+use std::fmt::{Display, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::error::{LatteError, Result};
+use crate::stats::errors::{ErrorCategory, ErrorCounts};
+use crate::stats::latency::LatencyDistribution;
+use crate::stats::percentiles::Percentile;
+use crate::stats::Sample;
+
+/// Percentiles of the latency distributions exported with their `quantile` labels.
+/// The labels are fixed, so that they don't carry floating-point rounding errors.
+const EXPORTED_PERCENTILES: &[(Percentile, &str)] = &[
+    (Percentile::P50, "0.5"),
+    (Percentile::P75, "0.75"),
+    (Percentile::P90, "0.9"),
+    (Percentile::P95, "0.95"),
+    (Percentile::P99, "0.99"),
+    (Percentile::P99_9, "0.999"),
+    (Percentile::Max, "1"),
+];
+
+/// Set once the metrics endpoint is started.
+/// Keeps the latest statistics in the Prometheus text exposition format.
+static LIVE_METRICS: OnceCell<Mutex<LiveMetrics>> = OnceCell::new();
+
+/// Counters accumulated over all samples published so far
+#[derive(Default)]
+struct Totals {
+    cycle_count: u64,
+    cycle_error_count: u64,
+    request_count: u64,
+    req_error_count: u64,
+    req_retry_count: u64,
+    row_count: u64,
+    error_counts: ErrorCounts,
+}
+
+#[derive(Default)]
+struct LiveMetrics {
+    totals: Totals,
+    text: String,
+}
+
+impl LiveMetrics {
+    fn new() -> Self {
+        let mut metrics = LiveMetrics::default();
+        metrics.text = render(&metrics.totals, None);
+        metrics
+    }
+
+    fn update(&mut self, sample: &Sample) {
+        let t = &mut self.totals;
+        t.cycle_count += sample.cycle_count;
+        t.cycle_error_count += sample.cycle_error_count;
+        t.request_count += sample.request_count;
+        t.req_error_count += sample.req_error_count;
+        t.req_retry_count += sample.req_retry_count;
+        t.row_count += sample.row_count;
+        t.error_counts.add(&sample.error_counts);
+        self.text = render(&self.totals, Some(sample));
+    }
+}
+
+/// Makes the sample available to the metrics endpoint.
+/// Does nothing if the endpoint has not been started.
+pub fn publish(sample: &Sample) {
+    if let Some(metrics) = LIVE_METRICS.get() {
+        metrics.lock().unwrap().update(sample);
+    }
+}
+
+/// Clears the counters, so they don't include the requests executed before the main phase,
+/// e.g. during the warmup.
+pub fn reset() {
+    if let Some(metrics) = LIVE_METRICS.get() {
+        *metrics.lock().unwrap() = LiveMetrics::new();
+    }
+}
+
+/// Starts serving the statistics of the samples published with [`publish`] over HTTP
+/// at the `/metrics` path. Returns the address the server is listening on.
+pub async fn serve(addr: SocketAddr) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| LatteError::MetricsServer(addr, e))?;
+    let local_addr = listener
+        .local_addr()
+        .map_err(|e| LatteError::MetricsServer(addr, e))?;
+    LIVE_METRICS.get_or_init(|| Mutex::new(LiveMetrics::new()));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle_connection(stream));
+        }
+    });
+    Ok(local_addr)
+}
+
+async fn handle_connection(mut stream: TcpStream) {
+    // We serve only simple GET requests, so we don't need to read more than the request line.
+    let mut buf = [0u8; 1024];
+    let Ok(len) = stream.read(&mut buf).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, content_type, body) = if path == "/metrics" {
+        let body = LIVE_METRICS
+            .get()
+            .map(|m| m.lock().unwrap().text.clone())
+            .unwrap_or_default();
+        ("200 OK", "text/plain; version=0.0.4; charset=utf-8", body)
+    } else {
+        ("404 Not Found", "text/plain", "Not Found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+    write_header(out, name, kind, help);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn write_latency(out: &mut String, name: &str, labels: &str, latency: &LatencyDistribution) {
+    for (p, quantile) in EXPORTED_PERCENTILES {
+        let seconds = latency.percentiles.get(*p).value / 1000.0;
+        let _ = writeln!(out, "{name}{{{labels}quantile=\"{quantile}\"}} {seconds}");
+    }
+}
+
+/// Formats the statistics in the Prometheus text exposition format.
+/// Counters are totals since the start of the main phase, gauges, including the latency
+/// percentiles, come from the latest sample.
+fn render(totals: &Totals, sample: Option<&Sample>) -> String {
+    let mut out = String::new();
+    let counters = [
+        (
+            "latte_cycles_total",
+            "Number of executed cycles",
+            totals.cycle_count,
+        ),
+        (
+            "latte_cycle_errors_total",
+            "Number of failed cycles",
+            totals.cycle_error_count,
+        ),
+        (
+            "latte_requests_total",
+            "Number of executed requests",
+            totals.request_count,
+        ),
+        (
+            "latte_request_errors_total",
+            "Number of failed requests",
+            totals.req_error_count,
+        ),
+        (
+            "latte_request_retries_total",
+            "Number of retried requests",
+            totals.req_retry_count,
+        ),
+        (
+            "latte_rows_total",
+            "Number of returned rows",
+            totals.row_count,
+        ),
+    ];
+    for (name, help, value) in counters {
+        write_metric(&mut out, name, "counter", help, value);
+    }
+    let name = "latte_errors_total";
+    write_header(
+        &mut out,
+        name,
+        "counter",
+        "Number of failed attempts of requests and validations by error category",
+    );
+    for category in ErrorCategory::ALL {
+        let count = totals.error_counts.get(category);
+        let _ = writeln!(out, "{name}{{category=\"{}\"}} {count}", category.name());
+    }
+
+    let Some(sample) = sample else {
+        return out;
+    };
+    let gauges = [
+        (
+            "latte_elapsed_seconds",
+            "Time since the start of the run",
+            sample.time_s + sample.duration_s,
+        ),
+        (
+            "latte_cycle_throughput",
+            "Cycles per second in the last sample",
+            sample.cycle_throughput,
+        ),
+        (
+            "latte_request_throughput",
+            "Requests per second in the last sample",
+            sample.req_throughput,
+        ),
+        (
+            "latte_row_throughput",
+            "Rows per second in the last sample",
+            sample.row_throughput,
+        ),
+        (
+            "latte_mean_queue_length",
+            "Mean number of requests in flight in the last sample",
+            sample.mean_queue_len,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        write_metric(&mut out, name, "gauge", help, value);
+    }
+
+    let name = "latte_cycle_latency_seconds";
+    write_header(
+        &mut out,
+        name,
+        "gauge",
+        "Cycle latency percentiles in the last sample",
+    );
+    write_latency(&mut out, name, "", &sample.cycle_latency);
+
+    let name = "latte_request_latency_seconds";
+    write_header(
+        &mut out,
+        name,
+        "gauge",
+        "Request latency percentiles in the last sample",
+    );
+    write_latency(&mut out, name, "", &sample.request_latency);
+
+    let name = "latte_function_latency_seconds";
+    write_header(
+        &mut out,
+        name,
+        "gauge",
+        "Cycle latency percentiles of each function in the last sample",
+    );
+    for (function, latency) in sample.cycle_latency_by_fn.iter().sorted_by_key(|(f, _)| *f) {
+        let labels = format!("function=\"{}\",", escape_label(function));
+        write_latency(&mut out, name, &labels, latency);
+    }
+
+    let name = "latte_node_request_latency_seconds";
+    write_header(
+        &mut out,
+        name,
+        "gauge",
+        "Latency percentiles of the requests served by each node in the last sample",
+    );
+    for (node, latency) in sample
+        .request_latency_by_node
+        .iter()
+        .sorted_by_key(|(n, _)| *n)
+    {
+        let labels = format!("node=\"{}\",", escape_label(node));
+        write_latency(&mut out, name, &labels, latency);
+    }
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::workload::{FnRef, FnStats, WorkloadStats};
+    use crate::stats::session::SessionStats;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::time::{Duration, Instant};
+
+    async fn http_get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn scrape_metrics_endpoint() {
+        let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+        let addr = serve(addr).await.unwrap();
+
+        let response = http_get(addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("# TYPE latte_cycles_total counter"));
+        assert!(response.contains("\nlatte_requests_total "));
+
+        let response = http_get(addr, "/other").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn latency_percentiles_are_quantile_gauges() {
+        let start_time = Instant::now();
+        let mut fn_stats = FnStats::new(FnRef::new("read"));
+        fn_stats.operation_completed(Duration::from_millis(2));
+        let stats = WorkloadStats {
+            start_time,
+            end_time: start_time + Duration::from_secs(1),
+            function_stats: vec![fn_stats],
+            session_stats: SessionStats::new(),
+        };
+        let sample = Sample::new(start_time, &[stats]);
+        let text = render(&Totals::default(), Some(&sample));
+        assert!(text.contains("# TYPE latte_request_latency_seconds gauge"));
+        assert!(text.contains("latte_request_latency_seconds{quantile=\"0.99\"} "));
+        assert!(text.contains("latte_request_latency_seconds{quantile=\"0.999\"} "));
+        assert!(
+            text.contains("latte_function_latency_seconds{function=\"read\",quantile=\"0.999\"} ")
+        );
+    }
+
+    #[test]
+    fn escape_label_values() {
+        assert_eq!(escape_label(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
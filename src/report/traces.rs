@@ -0,0 +1,200 @@
+//! Server-side traces of the slow and sampled requests, saved apart from the report.
+
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local};
+use console::style;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::fmt_section_header;
+
+/// Why the request was traced
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceReason {
+    /// The request took longer than `--trace-slow` and was sent once again with tracing
+    Slow,
+    /// The request was traced in-line, because it was chosen by the tracing probability
+    /// or its statement had tracing enabled
+    Sampled,
+}
+
+impl Display for TraceReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceReason::Slow => write!(f, "slow"),
+            TraceReason::Sampled => write!(f, "sampled"),
+        }
+    }
+}
+
+/// A single event recorded by the server while executing the traced request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    /// Microseconds since the start of the request on the node that recorded the event
+    pub elapsed_us: Option<i64>,
+    pub source: Option<String>,
+    pub thread: Option<String>,
+    pub activity: Option<String>,
+}
+
+/// A traced request together with its trace fetched from `system_traces`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TracedRequest {
+    /// Time the request was completed at
+    pub time: DateTime<Local>,
+    /// Key of the statement in the statistics, e.g. the key of the prepared statement
+    pub statement: String,
+    pub cql: String,
+    pub reason: TraceReason,
+    /// Response time measured by latte; for slow requests, that of the original request
+    pub latency_ms: f64,
+    pub session_id: String,
+    pub coordinator: Option<String>,
+    /// Duration of the traced request reported by the coordinator
+    pub duration_us: Option<i64>,
+    pub events: Vec<TraceEvent>,
+}
+
+/// Appends the traced requests to a JSON Lines file.
+/// The file is created when the first trace is written.
+#[cfg_attr(not(feature = "cql"), allow(dead_code))]
+pub struct TraceLogWriter {
+    path: PathBuf,
+    out: Mutex<Option<BufWriter<File>>>,
+}
+
+#[cfg_attr(not(feature = "cql"), allow(dead_code))]
+impl TraceLogWriter {
+    pub fn new(path: PathBuf) -> TraceLogWriter {
+        TraceLogWriter {
+            path,
+            out: Mutex::new(None),
+        }
+    }
+
+    pub fn write(&self, trace: &TracedRequest) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        if out.is_none() {
+            *out = Some(BufWriter::new(File::create(&self.path)?));
+        }
+        let out = out.as_mut().unwrap();
+        serde_json::to_writer(&mut *out, trace)?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
+
+/// Reads all traced requests from the trace log
+pub fn read_trace_log(path: &Path) -> io::Result<Vec<TracedRequest>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut traces = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            traces.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(traces)
+}
+
+/// Lists the slowest traced requests with the timelines of their events
+pub struct TraceSummary<'a> {
+    pub traces: &'a [TracedRequest],
+    pub limit: usize,
+}
+
+impl Display for TraceSummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", fmt_section_header("SLOWEST TRACED REQUESTS"))?;
+        let slowest = self
+            .traces
+            .iter()
+            .sorted_by(|a, b| b.latency_ms.total_cmp(&a.latency_ms))
+            .take(self.limit);
+        for (i, trace) in slowest.enumerate() {
+            writeln!(
+                f,
+                "{} {:>10.3} ms   {}   {}   {}   coordinator: {}   session: {}",
+                style(format!("#{:<3}", i + 1)).yellow().bold().for_stdout(),
+                trace.latency_ms,
+                style(&trace.statement).bold().for_stdout(),
+                trace.reason,
+                trace.time.format("%Y-%m-%d %H:%M:%S%.3f"),
+                trace.coordinator.as_deref().unwrap_or("?"),
+                trace.session_id,
+            )?;
+            writeln!(f, "     {}", style(&trace.cql).dim().for_stdout())?;
+            for event in &trace.events {
+                let elapsed = event
+                    .elapsed_us
+                    .map(|us| format!("{us:>10} µs"))
+                    .unwrap_or_else(|| format!("{:>13}", "?"));
+                writeln!(
+                    f,
+                    "     {elapsed}   {:<15}  {:<10}  {}",
+                    event.source.as_deref().unwrap_or(""),
+                    event.thread.as_deref().unwrap_or(""),
+                    event.activity.as_deref().unwrap_or(""),
+                )?;
+            }
+            if let Some(duration) = trace.duration_us {
+                writeln!(f, "     {duration:>10} µs   total")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(statement: &str, latency_ms: f64) -> TracedRequest {
+        TracedRequest {
+            time: Local::now(),
+            statement: statement.to_string(),
+            cql: "SELECT * FROM t WHERE pk = ?".to_string(),
+            reason: TraceReason::Slow,
+            latency_ms,
+            session_id: "2f0d6c1e-8e4f-11ee-b9d1-0242ac120002".to_string(),
+            coordinator: Some("10.0.0.1".to_string()),
+            duration_us: Some(1200),
+            events: vec![TraceEvent {
+                elapsed_us: Some(15),
+                source: Some("10.0.0.1".to_string()),
+                thread: Some("shard 0".to_string()),
+                activity: Some("Parsing a statement".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn write_and_read_trace_log() {
+        let path = std::env::temp_dir().join(format!("latte-traces-{}.jsonl", std::process::id()));
+        let writer = TraceLogWriter::new(path.clone());
+        let written = vec![trace("read", 12.5), trace("write", 80.0)];
+        for t in &written {
+            writer.write(t).unwrap();
+        }
+        let traces = read_trace_log(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(traces, written);
+
+        let summary = TraceSummary {
+            traces: &traces,
+            limit: 1,
+        }
+        .to_string();
+        assert!(summary.contains("write"));
+        assert!(!summary.contains("read"));
+        assert!(summary.contains("Parsing a statement"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::stats::errors::ErrorCategory;
 use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
 use rune::alloc::fmt::TryWrite;
 use rune::runtime::{VmError, VmResult};
@@ -16,6 +17,10 @@ pub enum AlternatorErrorKind {
     CustomError(String),
     Error(String),
     SdkError(String),
+    Timeout(String),
+    Throttled(String),
+    Unavailable(String),
+    ConnectionError(String),
     BadInput(String),
     ConversionError(String),
     ValidationError(String),
@@ -32,6 +37,22 @@ impl AlternatorError {
         )))
     }
 
+    /// Returns the category this error is counted under in the statistics
+    pub fn category(&self) -> ErrorCategory {
+        match &self.0 {
+            AlternatorErrorKind::FailedToConnect(_, _)
+            | AlternatorErrorKind::ConnectionError(_) => ErrorCategory::Connection,
+            AlternatorErrorKind::Overloaded(_) => ErrorCategory::Overloaded,
+            AlternatorErrorKind::Timeout(_) => ErrorCategory::Timeout,
+            AlternatorErrorKind::Throttled(_) => ErrorCategory::Throttling,
+            AlternatorErrorKind::Unavailable(_) => ErrorCategory::Unavailable,
+            AlternatorErrorKind::ValidationError(_) | AlternatorErrorKind::CustomError(_) => {
+                ErrorCategory::Validation
+            }
+            _ => ErrorCategory::Other,
+        }
+    }
+
     #[rune::function(protocol = DISPLAY_FMT)]
     pub fn string_display(&self, f: &mut rune::runtime::Formatter) -> VmResult<()> {
         let _ = vm_write!(f, "{}", self.to_string());
@@ -54,6 +75,10 @@ impl Display for AlternatorError {
             }
             AlternatorErrorKind::BadInput(s) => write!(f, "BadInput: {s}"),
             AlternatorErrorKind::SdkError(s) => write!(f, "SdkError: {s}"),
+            AlternatorErrorKind::Timeout(s) => write!(f, "Timeout: {s}"),
+            AlternatorErrorKind::Throttled(s) => write!(f, "Throttled: {s}"),
+            AlternatorErrorKind::Unavailable(s) => write!(f, "Unavailable: {s}"),
+            AlternatorErrorKind::ConnectionError(s) => write!(f, "ConnectionError: {s}"),
             AlternatorErrorKind::ConversionError(s) => write!(f, "ConversionError: {s}"),
             AlternatorErrorKind::ValidationError(s) => write!(f, "ValidationError: {s}"),
         }
@@ -85,9 +110,24 @@ where
     E: ProvideErrorMetadata,
 {
     fn from(err: SdkError<E, R>) -> Self {
-        AlternatorError::new(AlternatorErrorKind::SdkError(
-            err.message().unwrap_or("No message").to_string(),
-        ))
+        let message = err.message().unwrap_or("No message").to_string();
+        let kind = match &err {
+            SdkError::TimeoutError(_) => AlternatorErrorKind::Timeout(message),
+            SdkError::DispatchFailure(e) if e.is_timeout() => AlternatorErrorKind::Timeout(message),
+            SdkError::DispatchFailure(_) => AlternatorErrorKind::ConnectionError(message),
+            _ => match err.code() {
+                Some(
+                    "ThrottlingException"
+                    | "ProvisionedThroughputExceededException"
+                    | "RequestLimitExceeded",
+                ) => AlternatorErrorKind::Throttled(message),
+                Some("InternalServerError" | "ServiceUnavailable") => {
+                    AlternatorErrorKind::Unavailable(message)
+                }
+                _ => AlternatorErrorKind::SdkError(message),
+            },
+        };
+        AlternatorError::new(kind)
     }
 }
 
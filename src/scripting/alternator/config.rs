@@ -13,7 +13,7 @@ pub struct DbConnectionConf {
     pub access_key_id: String,
 
     /// Secret access key.
-    #[serde(skip_serializing)] // Don't save the secret to generated reports.
+    #[serde(skip_serializing, default)] // Don't save the secret to generated reports.
     #[clap(long("secret-access-key"), default_value = "")]
     pub secret_access_key: String,
 
@@ -21,3 +21,12 @@ pub struct DbConnectionConf {
     #[clap(long("region"), default_value = "us-east-1")]
     pub region: String,
 }
+
+impl DbConnectionConf {
+    /// Fills in the secret access key, which is never serialized, from the environment.
+    pub fn secrets_from_env(&mut self) {
+        if let Ok(secret) = std::env::var("AWS_SECRET_ACCESS_KEY") {
+            self.secret_access_key = secret;
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use super::alternator_error::{AlternatorError, AlternatorErrorKind};
 use super::context::Context;
 use crate::config::ConnectionConf;
+use crate::scripting::retry_error::RetryPolicy;
 use aws_config::retry::RetryConfig;
 use aws_config::BehaviorVersion;
 use aws_sdk_dynamodb::config::{Credentials, Region};
@@ -49,9 +50,7 @@ pub async fn connect(conf: &ConnectionConf) -> Result<Context, AlternatorError>
 
     Ok(Context::new(
         Some(client),
-        conf.retry_number,
-        conf.retry_interval,
-        conf.validation_strategy,
+        RetryPolicy::from_conf(conf),
         conf.page_size.get() as u64,
     ))
 }
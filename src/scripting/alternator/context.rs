@@ -1,7 +1,7 @@
 use super::alternator_error::{AlternatorError, AlternatorErrorKind};
-use crate::config::{RetryInterval, ValidationStrategy};
 use crate::error::LatteError;
 use crate::scripting::cluster_info::ClusterInfo;
+use crate::scripting::retry_error::RetryPolicy;
 use crate::scripting::row_distribution::RowDistributionPreset;
 use crate::stats::session::SessionStats;
 use aws_sdk_dynamodb::Client;
@@ -18,9 +18,7 @@ pub struct Context {
     page_size: u64,
     pub stats: Arc<TryLock<SessionStats>>,
     pub start_time: TryLock<Instant>,
-    pub retry_number: u64,
-    pub retry_interval: RetryInterval,
-    pub validation_strategy: ValidationStrategy,
+    pub retry_policy: RetryPolicy,
     pub partition_row_presets: Arc<TryLock<HashMap<String, RowDistributionPreset>>>,
     #[rune(get, set, add_assign, copy)]
     pub load_cycle_count: u64,
@@ -32,21 +30,13 @@ unsafe impl Send for Context {}
 unsafe impl Sync for Context {}
 
 impl Context {
-    pub fn new(
-        client: Option<Client>,
-        retry_number: u64,
-        retry_interval: RetryInterval,
-        validation_strategy: ValidationStrategy,
-        page_size: u64,
-    ) -> Context {
+    pub fn new(client: Option<Client>, retry_policy: RetryPolicy, page_size: u64) -> Context {
         Context {
             client,
             page_size,
             stats: Arc::new(TryLock::new(SessionStats::new())),
             start_time: TryLock::new(Instant::now()),
-            retry_number,
-            retry_interval,
-            validation_strategy,
+            retry_policy,
             partition_row_presets: Arc::new(TryLock::new(HashMap::new())),
             load_cycle_count: 0,
             data: Value::new(Object::new()).unwrap(),
@@ -61,9 +51,7 @@ impl Context {
             page_size: self.page_size,
             stats: Arc::new(TryLock::new(SessionStats::default())),
             start_time: TryLock::new(*self.start_time.try_lock().unwrap()),
-            retry_number: self.retry_number,
-            retry_interval: self.retry_interval,
-            validation_strategy: self.validation_strategy,
+            retry_policy: self.retry_policy.clone(),
             partition_row_presets: Arc::new(TryLock::new(
                 self.partition_row_presets.try_lock().unwrap().clone(),
             )),
@@ -80,9 +68,7 @@ impl Context {
             page_size: self.page_size,
             stats: Arc::clone(&self.stats),
             start_time: TryLock::new(*self.start_time.try_lock().unwrap()),
-            retry_number: self.retry_number,
-            retry_interval: self.retry_interval,
-            validation_strategy: self.validation_strategy,
+            retry_policy: self.retry_policy.clone(),
             partition_row_presets: Arc::clone(&self.partition_row_presets),
             load_cycle_count: self.load_cycle_count,
             data: self.data.clone(),
@@ -1,9 +1,9 @@
-use crate::config::ValidationStrategy;
+use crate::config::ErrorAction;
 use crate::scripting::alternator::traits::{
     AlternatorRequest, IntoAlternatorOutput, PaginationToken,
 };
 use crate::scripting::functions_common::{extract_validation_args, ValidationArgs};
-use crate::scripting::retry_error::handle_retry_error;
+use crate::scripting::retry_error::{handle_retry_error, speculate};
 
 use super::alternator_error::{AlternatorError, AlternatorErrorKind};
 use super::context::Context;
@@ -96,9 +96,9 @@ fn extract_attribute_names(object: &Object) -> Result<HashMap<String, String>, A
         .collect::<Result<_, _>>()
 }
 
-async fn handle_request_with_pagination(
+async fn handle_request_with_pagination<B: AlternatorRequest>(
     ctx: &Context,
-    builder: impl AlternatorRequest,
+    builder: B,
     auto_paginate: bool,
 ) -> Result<(Vec<Value>, Option<PaginationToken>), AlternatorError> {
     let mut token: Option<PaginationToken> = None;
@@ -108,7 +108,7 @@ async fn handle_request_with_pagination(
     let mut total_item_count = 0;
     let query_limit = builder.get_limit_val();
 
-    while current_attempt_num <= ctx.retry_number {
+    while current_attempt_num <= ctx.retry_policy.retry_number {
         let mut current_builder = builder.clone();
         if builder.has_pagination() {
             let page_size = match query_limit {
@@ -119,7 +119,10 @@ async fn handle_request_with_pagination(
         }
 
         let start_time = ctx.stats.try_lock().unwrap().start_request();
-        let resp = current_builder.send().await;
+        let resp = speculate(&ctx.retry_policy, B::IDEMPOTENT, || {
+            current_builder.clone().send()
+        })
+        .await;
         let duration = Instant::now() - start_time;
 
         match resp.into_output() {
@@ -131,10 +134,11 @@ async fn handle_request_with_pagination(
 
                 if let Some(limit) = query_limit {
                     if total_item_count as i32 >= limit {
-                        ctx.stats
-                            .try_lock()
-                            .unwrap()
-                            .complete_request(all_pages_duration, total_item_count);
+                        ctx.stats.try_lock().unwrap().complete_request(
+                            B::OPERATION,
+                            all_pages_duration,
+                            total_item_count,
+                        );
                         return Ok((all_items, token));
                     }
                 }
@@ -144,29 +148,40 @@ async fn handle_request_with_pagination(
                         current_attempt_num = 0; // reset retries for next page
                         continue;
                     } else {
-                        ctx.stats
-                            .try_lock()
-                            .unwrap()
-                            .complete_request(all_pages_duration, total_item_count);
+                        ctx.stats.try_lock().unwrap().complete_request(
+                            B::OPERATION,
+                            all_pages_duration,
+                            total_item_count,
+                        );
                         return Ok((all_items, token));
                     }
                 }
 
-                ctx.stats
-                    .try_lock()
-                    .unwrap()
-                    .complete_request(all_pages_duration, total_item_count);
+                ctx.stats.try_lock().unwrap().complete_request(
+                    B::OPERATION,
+                    all_pages_duration,
+                    total_item_count,
+                );
                 return Ok((all_items, token));
             }
             Err(e) => {
                 let current_error = e;
-                handle_retry_error(ctx, current_attempt_num, current_error).await;
+                let retried = ctx
+                    .retry_policy
+                    .will_retry(current_error.category(), current_attempt_num);
+                ctx.stats
+                    .try_lock()
+                    .unwrap()
+                    .fail_request(B::OPERATION, retried);
+                handle_retry_error(ctx, current_attempt_num, current_error).await?;
                 current_attempt_num += 1;
                 continue; // try again the same page
             }
         };
     }
-    Err(AlternatorError::query_retries_exceeded(ctx.retry_number))
+    Err(AlternatorError::query_retries_exceeded(
+        ctx.retry_policy.retry_number,
+    ))
 }
 
 async fn handle_request(
@@ -201,19 +216,16 @@ async fn handle_request_with_validation(
             validation.expected_min, validation.expected_max, validation.custom_err_msg
         )));
 
-        match ctx.validation_strategy {
-            ValidationStrategy::Retry => {
-                if current_attempt_num >= ctx.retry_number {
-                    return Err(current_error);
-                }
-                handle_retry_error(ctx, current_attempt_num, current_error).await;
+        match ctx.retry_policy.action(current_error.category()) {
+            ErrorAction::Retry => {
+                handle_retry_error(ctx, current_attempt_num, current_error).await?;
                 current_attempt_num += 1;
             }
-            ValidationStrategy::FailFast => {
+            ErrorAction::Fail => {
                 return Err(current_error);
             }
-            ValidationStrategy::Ignore => {
-                handle_retry_error(ctx, current_attempt_num, current_error).await;
+            ErrorAction::Ignore => {
+                let _ = handle_retry_error(ctx, current_attempt_num, current_error).await;
                 return Ok(result);
             }
         }
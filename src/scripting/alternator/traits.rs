@@ -144,6 +144,10 @@ pub(super) trait SendRequest {
 }
 
 pub(super) trait AlternatorRequest: SendRequest + Clone {
+    /// Name of the operation the request statistics are recorded under
+    const OPERATION: &'static str;
+    /// Whether the request can be sent more than once without changing its effect
+    const IDEMPOTENT: bool;
     fn set_pagination(self, token: Option<PaginationToken>, limit: Option<i32>) -> Self;
     fn has_pagination(&self) -> bool;
     fn get_limit_val(&self) -> Option<i32>;
@@ -166,10 +170,12 @@ macro_rules! impl_send_request {
 }
 
 macro_rules! impl_alternator_request_no_pagination {
-    ($($t:ty),*) => {
+    ($($t:ty => $operation:literal, $idempotent:literal),*) => {
         $(
             impl_send_request!($t);
             impl AlternatorRequest for $t {
+                const OPERATION: &'static str = $operation;
+                const IDEMPOTENT: bool = $idempotent;
                 fn set_pagination(self, _: Option<PaginationToken>, _: Option<i32>) -> Self { self }
                 fn has_pagination(&self) -> bool { false }
                 fn get_limit_val(&self) -> Option<i32> { None }
@@ -179,12 +185,12 @@ macro_rules! impl_alternator_request_no_pagination {
 }
 
 impl_alternator_request_no_pagination!(
-    aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder,
-    aws_sdk_dynamodb::operation::delete_table::builders::DeleteTableFluentBuilder,
-    aws_sdk_dynamodb::operation::put_item::builders::PutItemFluentBuilder,
-    aws_sdk_dynamodb::operation::delete_item::builders::DeleteItemFluentBuilder,
-    aws_sdk_dynamodb::operation::get_item::builders::GetItemFluentBuilder,
-    aws_sdk_dynamodb::operation::update_item::builders::UpdateItemFluentBuilder
+    aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder => "create_table", false,
+    aws_sdk_dynamodb::operation::delete_table::builders::DeleteTableFluentBuilder => "delete_table", false,
+    aws_sdk_dynamodb::operation::put_item::builders::PutItemFluentBuilder => "put", false,
+    aws_sdk_dynamodb::operation::delete_item::builders::DeleteItemFluentBuilder => "delete", false,
+    aws_sdk_dynamodb::operation::get_item::builders::GetItemFluentBuilder => "get", true,
+    aws_sdk_dynamodb::operation::update_item::builders::UpdateItemFluentBuilder => "update", false
 );
 
 impl_send_request!(
@@ -195,6 +201,8 @@ impl_send_request!(
 );
 
 impl AlternatorRequest for aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder {
+    const OPERATION: &'static str = "query";
+    const IDEMPOTENT: bool = true;
     fn set_pagination(self, token: Option<PaginationToken>, limit: Option<i32>) -> Self {
         let mut b = self.set_exclusive_start_key(match token {
             Some(PaginationToken::LastEvaluatedKey(key)) => Some(key),
@@ -214,6 +222,8 @@ impl AlternatorRequest for aws_sdk_dynamodb::operation::query::builders::QueryFl
 }
 
 impl AlternatorRequest for aws_sdk_dynamodb::operation::scan::builders::ScanFluentBuilder {
+    const OPERATION: &'static str = "scan";
+    const IDEMPOTENT: bool = true;
     fn set_pagination(self, token: Option<PaginationToken>, limit: Option<i32>) -> Self {
         let mut b = self.set_exclusive_start_key(match token {
             Some(PaginationToken::LastEvaluatedKey(key)) => Some(key),
@@ -235,6 +245,8 @@ impl AlternatorRequest for aws_sdk_dynamodb::operation::scan::builders::ScanFlue
 impl AlternatorRequest
     for aws_sdk_dynamodb::operation::batch_get_item::builders::BatchGetItemFluentBuilder
 {
+    const OPERATION: &'static str = "batch_get_item";
+    const IDEMPOTENT: bool = true;
     fn set_pagination(self, token: Option<PaginationToken>, _limit: Option<i32>) -> Self {
         if let Some(PaginationToken::UnprocessedKeys(keys)) = token {
             self.set_request_items(Some(keys))
@@ -253,6 +265,8 @@ impl AlternatorRequest
 impl AlternatorRequest
     for aws_sdk_dynamodb::operation::batch_write_item::builders::BatchWriteItemFluentBuilder
 {
+    const OPERATION: &'static str = "batch_write_item";
+    const IDEMPOTENT: bool = false;
     fn set_pagination(self, token: Option<PaginationToken>, _limit: Option<i32>) -> Self {
         if let Some(PaginationToken::UnprocessedItems(items)) = token {
             self.set_request_items(Some(items))
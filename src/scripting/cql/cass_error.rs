@@ -1,10 +1,12 @@
+use crate::stats::errors::ErrorCategory;
 use openssl::error::ErrorStack;
 use rune::alloc::error::Error as RuneAllocError;
 use rune::alloc::fmt::TryWrite;
 use rune::runtime::{TypeInfo, VmResult};
 use rune::{vm_write, Any, Value};
 use scylla::errors::{
-    DeserializationError, ExecutionError, NewSessionError, PrepareError, RowsError,
+    DbError as ScyllaDbError, DeserializationError, ExecutionError, NewSessionError, PrepareError,
+    RequestAttemptError, RowsError,
 };
 use scylla::response::query_result::{FirstRowError, IntoRowsResultError};
 use scylla::value::ValueOverflow;
@@ -31,6 +33,10 @@ impl CassError {
             cql: cql.to_string(),
             params: rune_value_to_param_strings(params),
         };
+        CassError::execution_error(query, err)
+    }
+
+    fn execution_error(query: QueryInfo, err: ExecutionError) -> CassError {
         let kind = match err {
             ExecutionError::RequestTimeout(_) => CassErrorKind::Overloaded(query, err),
             _ => CassErrorKind::QueryExecution(query, err),
@@ -38,6 +44,27 @@ impl CassError {
         CassError(kind)
     }
 
+    /// Creates an error of a batch, reporting the text and the parameters of each statement
+    pub fn batch_execution_error(
+        batch_type: &str,
+        cqls: &[String],
+        params: &[Value],
+        err: ExecutionError,
+    ) -> CassError {
+        let query = QueryInfo {
+            cql: format!(
+                "BEGIN {} BATCH {}; APPLY BATCH",
+                batch_type.to_uppercase(),
+                cqls.join("; ")
+            ),
+            params: params
+                .iter()
+                .map(|p| format!("[{}]", rune_value_to_param_strings(Some(p)).join(", ")))
+                .collect(),
+        };
+        CassError::execution_error(query, err)
+    }
+
     pub fn query_validation_error(
         cql: &str,
         params: Option<&Value>,
@@ -77,6 +104,64 @@ impl CassError {
             "Max retry attempts ({retry_number}) reached",
         )))
     }
+
+    /// Returns the category this error is counted under in the statistics
+    pub fn category(&self) -> ErrorCategory {
+        match &self.0 {
+            CassErrorKind::FailedToConnect(_, _) => ErrorCategory::Connection,
+            CassErrorKind::Prepare(_, e) => prepare_error_category(e),
+            CassErrorKind::Overloaded(_, e) | CassErrorKind::QueryExecution(_, e) => {
+                execution_error_category(e)
+            }
+            CassErrorKind::QueryResponseValidationError(..)
+            | CassErrorKind::QueryResponseValidationNotApplicableError(_)
+            | CassErrorKind::CustomError(_) => ErrorCategory::Validation,
+            _ => ErrorCategory::Other,
+        }
+    }
+}
+
+fn execution_error_category(err: &ExecutionError) -> ErrorCategory {
+    match err {
+        ExecutionError::RequestTimeout(_) => ErrorCategory::Timeout,
+        ExecutionError::ConnectionPoolError(_) => ErrorCategory::Connection,
+        ExecutionError::PrepareError(e) => prepare_error_category(e),
+        ExecutionError::LastAttemptError(e) => attempt_error_category(e),
+        _ => ErrorCategory::Other,
+    }
+}
+
+fn prepare_error_category(err: &PrepareError) -> ErrorCategory {
+    match err {
+        PrepareError::ConnectionPoolError(_) => ErrorCategory::Connection,
+        PrepareError::AllAttemptsFailed { first_attempt } => attempt_error_category(first_attempt),
+        _ => ErrorCategory::Other,
+    }
+}
+
+fn attempt_error_category(err: &RequestAttemptError) -> ErrorCategory {
+    match err {
+        RequestAttemptError::UnableToAllocStreamId
+        | RequestAttemptError::BrokenConnectionError(_) => ErrorCategory::Connection,
+        RequestAttemptError::DbError(e, _) => db_error_category(e),
+        _ => ErrorCategory::Other,
+    }
+}
+
+fn db_error_category(err: &ScyllaDbError) -> ErrorCategory {
+    match err {
+        ScyllaDbError::ReadTimeout { .. } | ScyllaDbError::WriteTimeout { .. } => {
+            ErrorCategory::Timeout
+        }
+        ScyllaDbError::Unavailable { .. } | ScyllaDbError::IsBootstrapping => {
+            ErrorCategory::Unavailable
+        }
+        ScyllaDbError::Overloaded => ErrorCategory::Overloaded,
+        ScyllaDbError::ReadFailure { .. } => ErrorCategory::ReadFailure,
+        ScyllaDbError::WriteFailure { .. } => ErrorCategory::WriteFailure,
+        ScyllaDbError::RateLimitReached { .. } => ErrorCategory::Throttling,
+        _ => ErrorCategory::Other,
+    }
 }
 
 impl From<IntoRowsResultError> for CassError {
@@ -92,6 +177,8 @@ pub enum CassErrorKind {
     SslConfiguration(ErrorStack),
     FailedToConnect(Vec<String>, NewSessionError),
     PreparedStatementNotFound(String),
+    ExecutionProfileNotFound(String),
+    InvalidStatementOptions(String),
     PartitionRowPresetNotFound(String),
     QueryRetriesExceeded(String),
     QueryParamConversion(String, String, Option<String>),
@@ -134,6 +221,12 @@ impl CassError {
             CassErrorKind::PreparedStatementNotFound(s) => {
                 write!(buf, "Prepared statement not found: {s}")
             }
+            CassErrorKind::ExecutionProfileNotFound(s) => {
+                write!(buf, "Execution profile not found: {s}")
+            }
+            CassErrorKind::InvalidStatementOptions(s) => {
+                write!(buf, "Invalid statement options: {s}")
+            }
             CassErrorKind::PartitionRowPresetNotFound(s) => {
                 write!(buf, "Partition-row preset not found: {s}")
             }
@@ -311,3 +404,47 @@ impl Display for QueryInfo {
 
 pub type DbError = CassError;
 pub type DbErrorKind = CassErrorKind;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn execution_error(err: ExecutionError) -> CassError {
+        CassError::query_execution_error("SELECT * FROM t", None, err)
+    }
+
+    fn db_error(err: ScyllaDbError) -> CassError {
+        execution_error(ExecutionError::LastAttemptError(
+            RequestAttemptError::DbError(err, String::new()),
+        ))
+    }
+
+    #[test]
+    fn classify_errors() {
+        assert_eq!(
+            execution_error(ExecutionError::RequestTimeout(Duration::from_secs(1))).category(),
+            ErrorCategory::Timeout
+        );
+        assert_eq!(
+            db_error(ScyllaDbError::Overloaded).category(),
+            ErrorCategory::Overloaded
+        );
+        assert_eq!(
+            execution_error(ExecutionError::LastAttemptError(
+                RequestAttemptError::UnableToAllocStreamId
+            ))
+            .category(),
+            ErrorCategory::Connection
+        );
+        assert_eq!(
+            db_error(ScyllaDbError::SyntaxError).category(),
+            ErrorCategory::Other
+        );
+        assert_eq!(
+            CassError::query_validation_error("SELECT * FROM t", None, 1, 1, 0, String::new())
+                .category(),
+            ErrorCategory::Validation
+        );
+    }
+}
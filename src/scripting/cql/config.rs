@@ -1,8 +1,23 @@
 use clap::builder::PossibleValue;
 use clap::{Parser, ValueEnum};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use scylla::policies::retry::{
+    DefaultRetryPolicy, DowngradingConsistencyRetryPolicy, FallthroughRetryPolicy, RetryPolicy,
+};
+use scylla::policies::speculative_execution::{
+    PercentileSpeculativeExecutionPolicy, SimpleSpeculativeExecutionPolicy,
+    SpeculativeExecutionPolicy,
+};
+
+use crate::config::parse_duration;
 
 #[derive(Parser, Debug, Serialize, Deserialize)]
 pub struct DbConnectionConf {
@@ -20,7 +35,7 @@ pub struct DbConnectionConf {
     pub user: String,
 
     /// Password to use if password authentication is required by the server
-    #[serde(skip_serializing)] // Don't save the password to generated reports.
+    #[serde(skip_serializing, default)] // Don't save the password to generated reports.
     #[clap(long, env("CASSANDRA_PASSWORD"), default_value = "")]
     pub password: String,
 
@@ -65,6 +80,80 @@ pub struct DbConnectionConf {
         default_value = "LOCAL_SERIAL"
     )]
     pub serial_consistency: SerialConsistency,
+
+    /// Named execution profile that statements can select in the workload script.
+    /// Can be used multiple times.
+    ///
+    /// A profile is defined by a comma separated list of options: `name` (required),
+    /// `consistency`, `serial-consistency` and `timeout`. Options not given are taken from
+    /// the default profile set by `--consistency`, `--serial-consistency` and `--request-timeout`.
+    ///
+    /// Example: `--execution-profile name=reads,consistency=LOCAL_ONE,timeout=500ms`
+    #[clap(
+        long("execution-profile"),
+        value_name = "PROFILE",
+        number_of_values = 1
+    )]
+    #[serde(default)]
+    pub execution_profiles: Vec<ExecutionProfileConf>,
+
+    /// Policy used by the driver to choose the nodes the requests are sent to
+    #[clap(
+        long("load-balancing"),
+        default_value = "token-aware",
+        value_name = "POLICY"
+    )]
+    #[serde(default)]
+    pub load_balancing: LoadBalancing,
+
+    /// Makes the driver send the request to another node if the response doesn't come in time.
+    ///
+    /// `simple:DELAY[:MAX]` sends up to MAX (default 1) additional requests, DELAY apart.
+    /// `percentile:P[:MAX]` sends them when the request takes longer than the P-th percentile
+    /// of the latency observed by the driver.
+    ///
+    /// Example: `--speculative-execution simple:20ms:2`
+    #[clap(long("speculative-execution"), value_name = "POLICY")]
+    #[serde(default)]
+    pub speculative_execution: Option<SpeculativeExecution>,
+
+    /// Retry policy of the driver, applied before the retries configured with `--retry-number`
+    #[clap(
+        long("driver-retry-policy"),
+        default_value = "default",
+        value_name = "POLICY"
+    )]
+    #[serde(default)]
+    pub driver_retry_policy: DriverRetryPolicy,
+
+    /// Records the latency of the requests served by each shard of each node,
+    /// in addition to the latency of each node. Applies to Scylla only.
+    #[clap(long("shard-stats"))]
+    #[serde(default)]
+    pub shard_stats: bool,
+
+    /// Traces the requests that take longer than given time.
+    /// A slow request is sent once again with tracing enabled and its trace is saved
+    /// to the trace log. Only SELECTs and idempotent statements are sent again;
+    /// for the other statements the next execution of the same statement is traced instead.
+    #[clap(long("trace-slow"), value_name = "DURATION", value_parser = parse_duration)]
+    #[serde(default)]
+    pub trace_slow: Option<Duration>,
+
+    /// Path of the file the traces of the slow and sampled requests are saved to.
+    /// Defaults to the path of the report with the `.traces.jsonl` extension.
+    #[clap(long("trace-log"), value_name = "PATH")]
+    #[serde(default)]
+    pub trace_log: Option<PathBuf>,
+}
+
+impl DbConnectionConf {
+    /// Fills in the password, which is never serialized, from the environment.
+    pub fn secrets_from_env(&mut self) {
+        if let Ok(password) = std::env::var("CASSANDRA_PASSWORD") {
+            self.password = password;
+        }
+    }
 }
 
 #[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -190,3 +279,254 @@ impl ValueEnum for SerialConsistency {
         }
     }
 }
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum LoadBalancing {
+    /// Sends requests to the replicas owning the data and, on Scylla, to the shard owning it
+    #[default]
+    #[value(alias = "shard-aware")]
+    TokenAware,
+    /// Sends requests to all nodes in turn, regardless of the data they own
+    RoundRobin,
+    /// Like token-aware, but avoids the nodes that respond much slower than the fastest one
+    LatencyAware,
+}
+
+/// Speculative execution policy of the driver
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SpeculativeExecution {
+    /// Sends the next request after a fixed delay
+    Simple { delay: Duration, max_retries: usize },
+    /// Sends the next request after the given percentile of the latency
+    Percentile { percentile: f64, max_retries: usize },
+}
+
+impl SpeculativeExecution {
+    pub fn policy(&self) -> Arc<dyn SpeculativeExecutionPolicy> {
+        match *self {
+            Self::Simple { delay, max_retries } => Arc::new(SimpleSpeculativeExecutionPolicy {
+                max_retry_count: max_retries,
+                retry_interval: delay,
+            }),
+            Self::Percentile {
+                percentile,
+                max_retries,
+            } => Arc::new(PercentileSpeculativeExecutionPolicy {
+                max_retry_count: max_retries,
+                percentile,
+            }),
+        }
+    }
+}
+
+impl FromStr for SpeculativeExecution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let (kind, threshold, max_retries) = match parts.as_slice() {
+            [kind, threshold] => (*kind, *threshold, 1),
+            [kind, threshold, max] => {
+                let max = max
+                    .parse()
+                    .map_err(|e| format!("Invalid max speculative executions `{max}`: {e}"))?;
+                (*kind, *threshold, max)
+            }
+            _ => {
+                return Err(format!(
+                    "Invalid speculative execution policy `{s}`. \
+                     Expected simple:DELAY[:MAX] or percentile:P[:MAX]"
+                ))
+            }
+        };
+        match kind {
+            "simple" => {
+                let delay = parse_duration(threshold).map_err(|e| {
+                    format!("Invalid speculative execution delay `{threshold}`: {e}")
+                })?;
+                Ok(Self::Simple { delay, max_retries })
+            }
+            "percentile" => {
+                let percentile: f64 = threshold
+                    .parse()
+                    .map_err(|e| format!("Invalid percentile `{threshold}`: {e}"))?;
+                if !(percentile > 0.0 && percentile < 100.0) {
+                    return Err(format!(
+                        "Percentile must be between 0 and 100, got {percentile}"
+                    ));
+                }
+                Ok(Self::Percentile {
+                    percentile,
+                    max_retries,
+                })
+            }
+            other => Err(format!(
+                "Unknown speculative execution policy `{other}`. Expected simple or percentile"
+            )),
+        }
+    }
+}
+
+impl Display for SpeculativeExecution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Simple { delay, max_retries } => {
+                write!(
+                    f,
+                    "simple:{}:{max_retries}",
+                    humantime::format_duration(*delay)
+                )
+            }
+            Self::Percentile {
+                percentile,
+                max_retries,
+            } => write!(f, "percentile:{percentile}:{max_retries}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum DriverRetryPolicy {
+    /// Retries once on timeouts and unavailable errors if it is likely to help
+    #[default]
+    Default,
+    /// Like default, but also retries with a lower consistency level
+    /// if not enough replicas responded
+    DowngradingConsistency,
+    /// Never retries; all errors are handled by the retries of latte
+    Fallthrough,
+}
+
+impl DriverRetryPolicy {
+    pub fn policy(&self) -> Arc<dyn RetryPolicy> {
+        match self {
+            Self::Default => Arc::new(DefaultRetryPolicy::new()),
+            Self::DowngradingConsistency => Arc::new(DowngradingConsistencyRetryPolicy::new()),
+            Self::Fallthrough => Arc::new(FallthroughRetryPolicy::new()),
+        }
+    }
+}
+
+/// Named set of statement execution options, declared on the command line
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionProfileConf {
+    pub name: String,
+    pub consistency: Option<Consistency>,
+    pub serial_consistency: Option<SerialConsistency>,
+    pub request_timeout: Option<Duration>,
+}
+
+impl FromStr for ExecutionProfileConf {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut profile = ExecutionProfileConf {
+            name: String::new(),
+            consistency: None,
+            serial_consistency: None,
+            request_timeout: None,
+        };
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part.split_once('=').ok_or_else(|| {
+                format!("Invalid execution profile option `{part}`. Expected KEY=VALUE")
+            })?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "consistency" => {
+                    profile.consistency = Some(<Consistency as ValueEnum>::from_str(value, true)?)
+                }
+                "serial-consistency" | "serial_consistency" => {
+                    profile.serial_consistency =
+                        Some(<SerialConsistency as ValueEnum>::from_str(value, true)?)
+                }
+                "timeout" | "request-timeout" | "request_timeout" => {
+                    profile.request_timeout = Some(
+                        parse_duration(value)
+                            .map_err(|e| format!("Invalid request timeout `{value}`: {e}"))?,
+                    )
+                }
+                other => return Err(format!("Unknown execution profile option `{other}`")),
+            }
+        }
+        profile.name = name
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| "Execution profile name is required, e.g. name=reads".to_string())?;
+        Ok(profile)
+    }
+}
+
+impl Display for ExecutionProfileConf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut options = vec![format!("name={}", self.name)];
+        if let Some(consistency) = self.consistency {
+            let consistency = consistency.to_possible_value().unwrap();
+            options.push(format!("consistency={}", consistency.get_name()));
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            let serial_consistency = serial_consistency.to_possible_value().unwrap();
+            options.push(format!(
+                "serial-consistency={}",
+                serial_consistency.get_name()
+            ));
+        }
+        if let Some(timeout) = self.request_timeout {
+            options.push(format!("timeout={}", humantime::format_duration(timeout)));
+        }
+        write!(f, "{}", options.iter().join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_execution_profile() {
+        let profile: ExecutionProfileConf =
+            "name=reads, consistency=l1, timeout=500ms".parse().unwrap();
+        assert_eq!(profile.name, "reads");
+        assert_eq!(profile.consistency, Some(Consistency::LocalOne));
+        assert_eq!(profile.serial_consistency, None);
+        assert_eq!(profile.request_timeout, Some(Duration::from_millis(500)));
+        assert_eq!(
+            profile.to_string(),
+            "name=reads,consistency=LOCAL_ONE,timeout=500ms"
+        );
+        assert_eq!(
+            profile.to_string().parse::<ExecutionProfileConf>(),
+            Ok(profile)
+        );
+
+        assert!("consistency=ONE".parse::<ExecutionProfileConf>().is_err());
+        assert!("name=x,retries=3".parse::<ExecutionProfileConf>().is_err());
+    }
+
+    #[test]
+    fn parse_speculative_execution() {
+        let simple: SpeculativeExecution = "simple:20ms".parse().unwrap();
+        assert_eq!(
+            simple,
+            SpeculativeExecution::Simple {
+                delay: Duration::from_millis(20),
+                max_retries: 1
+            }
+        );
+        assert_eq!(simple.to_string(), "simple:20ms:1");
+
+        let percentile: SpeculativeExecution = "percentile:99.5:3".parse().unwrap();
+        assert_eq!(
+            percentile,
+            SpeculativeExecution::Percentile {
+                percentile: 99.5,
+                max_retries: 3
+            }
+        );
+        assert_eq!(percentile.to_string().parse(), Ok(percentile));
+
+        assert!("percentile:100".parse::<SpeculativeExecution>().is_err());
+        assert!("simple".parse::<SpeculativeExecution>().is_err());
+        assert!("eager:10ms".parse::<SpeculativeExecution>().is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use super::cass_error::{CassError, CassErrorKind};
+use super::config::LoadBalancing;
 use super::context::Context;
 use crate::config::ConnectionConf;
+use crate::scripting::retry_error::RetryPolicy;
 use openssl::ssl::{SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode};
 use scylla::client::session::TlsContext;
 use scylla::client::PoolSize;
-use scylla::policies::load_balancing::DefaultPolicy;
+use scylla::policies::load_balancing::{DefaultPolicy, LatencyAwarenessBuilder};
 
 use scylla::client::execution_profile::ExecutionProfile;
 use scylla::client::session_builder::SessionBuilder;
@@ -32,7 +34,13 @@ fn tls_context(conf: &&ConnectionConf) -> Result<Option<TlsContext>, Box<CassErr
 
 /// Configures connection to Cassandra.
 pub async fn connect(conf: &ConnectionConf) -> Result<Context, CassError> {
-    let mut policy_builder = DefaultPolicy::builder().token_aware(true);
+    let mut policy_builder = match conf.db.load_balancing {
+        LoadBalancing::TokenAware => DefaultPolicy::builder().token_aware(true),
+        LoadBalancing::RoundRobin => DefaultPolicy::builder().token_aware(false),
+        LoadBalancing::LatencyAware => DefaultPolicy::builder()
+            .token_aware(true)
+            .latency_awareness(LatencyAwarenessBuilder::new()),
+    };
     let mut datacenter: String = "".to_string();
     let mut rack: String = "".to_string();
     if let Some(dc) = &conf.db.datacenter {
@@ -50,29 +58,53 @@ pub async fn connect(conf: &ConnectionConf) -> Result<Context, CassError> {
     } else if let Some(_rack) = &conf.db.rack {
         panic!("Datacenter must also be defined when rack is defined");
     }
-    let profile = ExecutionProfile::builder()
+    let default_profile = ExecutionProfile::builder()
         .consistency(conf.db.consistency.consistency())
         .serial_consistency(Some(conf.db.serial_consistency.serial_consistency()))
         .load_balancing_policy(policy_builder.build())
         .request_timeout(Some(conf.request_timeout))
+        .speculative_execution_policy(conf.db.speculative_execution.map(|s| s.policy()))
+        .retry_policy(conf.db.driver_retry_policy.policy())
         .build();
+    let profiles = conf
+        .db
+        .execution_profiles
+        .iter()
+        .map(|p| {
+            let mut builder = default_profile.to_builder();
+            if let Some(consistency) = p.consistency {
+                builder = builder.consistency(consistency.consistency());
+            }
+            if let Some(serial_consistency) = p.serial_consistency {
+                builder = builder.serial_consistency(Some(serial_consistency.serial_consistency()));
+            }
+            if let Some(timeout) = p.request_timeout {
+                builder = builder.request_timeout(Some(timeout));
+            }
+            (p.name.clone(), builder.build().into_handle())
+        })
+        .collect();
 
     let scylla_session = SessionBuilder::new()
         .known_nodes(&conf.addresses)
         .pool_size(PoolSize::PerShard(conf.db.count))
         .user(&conf.db.user, &conf.db.password)
         .tls_context(tls_context(&conf)?)
-        .default_execution_profile_handle(profile.into_handle())
+        .default_execution_profile_handle(default_profile.clone().into_handle())
         .build()
         .await
         .map_err(|e| CassError(CassErrorKind::FailedToConnect(conf.addresses.clone(), e)))?;
-    Ok(Context::new(
+    let context = Context::new(
         Some(scylla_session),
         conf.page_size.get() as u64,
         datacenter,
         rack,
-        conf.retry_number,
-        conf.retry_interval,
-        conf.validation_strategy,
-    ))
+        RetryPolicy::from_conf(conf),
+    )
+    .with_execution_profiles(profiles)
+    .with_shard_stats(conf.db.shard_stats);
+    Ok(match &conf.db.trace_log {
+        Some(path) => context.with_tracer(conf.db.trace_slow, path.clone()),
+        None => context,
+    })
 }
@@ -1,29 +1,39 @@
 use super::cass_error::{CassError, CassErrorKind};
 use super::deserialize::RuneRow;
-use super::serialize::RuneQueryParams;
-use crate::config::{RetryInterval, ValidationStrategy};
+use super::options::{BatchOptions, StatementOptions};
+use super::serialize::{to_cql_values, RuneQueryParams};
+use super::trace::Tracer;
+use crate::config::ErrorAction;
 use crate::error::LatteError;
+use crate::report::traces::TraceReason;
 use crate::scripting::cluster_info::ClusterInfo;
-use crate::scripting::retry_error::handle_retry_error;
+use crate::scripting::retry_error::{handle_retry_error, speculate, RetryPolicy};
 use crate::scripting::row_distribution::RowDistributionPreset;
 use crate::stats::session::SessionStats;
 
+use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use rune::runtime::{Object, Vec as RuneVec};
 use rune::{Any, Value};
+use scylla::client::execution_profile::ExecutionProfileHandle;
 use scylla::client::session::Session;
-use scylla::response::PagingState;
-use scylla::statement::batch::{Batch, BatchType};
+use scylla::response::{Coordinator, PagingState};
+use scylla::statement::batch::Batch;
 use scylla::statement::prepared::PreparedStatement;
 use scylla::statement::unprepared::Statement;
 use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::ops::ControlFlow;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::Instant;
 use try_lock::TryLock;
 
+/// Key of the request statistics of the statements executed without preparing them first
+const UNPREPARED_STATEMENT: &str = "<unprepared>";
+
 static IS_SELECT_QUERY: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*select\b").unwrap());
 static IS_SELECT_COUNT_QUERY: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?i)^\s*select\s+count\s*\(\s*[^)]*\s*\)").unwrap());
@@ -38,10 +48,16 @@ pub struct Context {
     session: Option<Arc<Session>>,
     page_size: u64,
     statements: Arc<TryLock<HashMap<String, Arc<PreparedStatement>>>>,
+    /// Execution profiles that statements can select by name
+    profiles: Arc<HashMap<String, ExecutionProfileHandle>>,
     pub stats: Arc<TryLock<SessionStats>>,
-    pub retry_number: u64,
-    pub retry_interval: RetryInterval,
-    pub validation_strategy: ValidationStrategy,
+    /// Whether to record the requests of each shard apart from those of each node
+    shard_stats: bool,
+    /// Saves the traces of the slow and sampled requests; `None` if tracing is disabled
+    tracer: Option<Arc<Tracer>>,
+    /// Probability of tracing the requests of each prepared statement
+    trace_probabilities: Arc<TryLock<HashMap<String, f64>>>,
+    pub retry_policy: RetryPolicy,
     pub partition_row_presets: Arc<TryLock<HashMap<String, RowDistributionPreset>>>,
     #[rune(get, set, add_assign, copy)]
     pub load_cycle_count: u64,
@@ -68,9 +84,7 @@ impl Context {
         page_size: u64,
         preferred_datacenter: String,
         preferred_rack: String,
-        retry_number: u64,
-        retry_interval: RetryInterval,
-        validation_strategy: ValidationStrategy,
+        retry_policy: RetryPolicy,
     ) -> Context {
         let data = Value::new(Object::new()).unwrap();
         Context {
@@ -78,10 +92,12 @@ impl Context {
             session: session.map(Arc::new),
             page_size,
             statements: Arc::new(TryLock::new(HashMap::new())),
+            profiles: Arc::new(HashMap::new()),
             stats: Arc::new(TryLock::new(SessionStats::new())),
-            retry_number,
-            retry_interval,
-            validation_strategy,
+            shard_stats: false,
+            tracer: None,
+            trace_probabilities: Arc::new(TryLock::new(HashMap::new())),
+            retry_policy,
             partition_row_presets: Arc::new(TryLock::new(HashMap::new())),
             load_cycle_count: 0,
             preferred_datacenter,
@@ -90,6 +106,51 @@ impl Context {
         }
     }
 
+    /// Sets the execution profiles that statements can select by name
+    pub fn with_execution_profiles(
+        mut self,
+        profiles: HashMap<String, ExecutionProfileHandle>,
+    ) -> Self {
+        self.profiles = Arc::new(profiles);
+        self
+    }
+
+    /// Enables recording the requests of each shard
+    pub fn with_shard_stats(mut self, shard_stats: bool) -> Self {
+        self.shard_stats = shard_stats;
+        self
+    }
+
+    /// Enables saving the traces of the requests slower than `slow_threshold`
+    /// and of the requests sampled by the tracing probability to the file at `path`.
+    /// Does nothing if there is no session.
+    pub fn with_tracer(mut self, slow_threshold: Option<Duration>, path: PathBuf) -> Self {
+        self.tracer = self
+            .session
+            .as_ref()
+            .map(|session| Arc::new(Tracer::new(session.clone(), slow_threshold, path)));
+        self
+    }
+
+    /// Waits until the traces of the requests executed so far are saved
+    pub async fn finish_tracing(&self) {
+        if let Some(tracer) = &self.tracer {
+            tracer.finish(Duration::from_secs(30)).await;
+        }
+    }
+
+    /// Records the response time of a request in the statistics of its coordinator
+    fn record_coordinator(&self, coordinator: &Coordinator, duration: Duration, row_count: u64) {
+        let address = &coordinator.node().address;
+        let shard = coordinator.shard().filter(|_| self.shard_stats);
+        self.stats.try_lock().unwrap().record_coordinator(
+            SocketAddr::new(address.ip(), address.port()),
+            shard,
+            duration,
+            row_count,
+        );
+    }
+
     /// Clones the context for use by another thread.
     /// The new clone gets fresh statistics.
     /// The user data gets passed through serialization and deserialization to avoid
@@ -101,10 +162,14 @@ impl Context {
             session: self.session.clone(),
             page_size: self.page_size,
             statements: Arc::new(TryLock::new(self.statements.try_lock().unwrap().clone())),
+            profiles: self.profiles.clone(),
             stats: Arc::new(TryLock::new(SessionStats::default())),
-            retry_number: self.retry_number,
-            retry_interval: self.retry_interval,
-            validation_strategy: self.validation_strategy,
+            shard_stats: self.shard_stats,
+            tracer: self.tracer.clone(),
+            trace_probabilities: Arc::new(TryLock::new(
+                self.trace_probabilities.try_lock().unwrap().clone(),
+            )),
+            retry_policy: self.retry_policy.clone(),
             partition_row_presets: Arc::new(TryLock::new(
                 self.partition_row_presets.try_lock().unwrap().clone(),
             )),
@@ -125,10 +190,12 @@ impl Context {
             session: self.session.clone(),
             page_size: self.page_size,
             statements: Arc::clone(&self.statements),
+            profiles: Arc::clone(&self.profiles),
             stats: Arc::clone(&self.stats),
-            retry_number: self.retry_number,
-            retry_interval: self.retry_interval,
-            validation_strategy: self.validation_strategy,
+            shard_stats: self.shard_stats,
+            tracer: self.tracer.clone(),
+            trace_probabilities: Arc::clone(&self.trace_probabilities),
+            retry_policy: self.retry_policy.clone(),
             partition_row_presets: Arc::clone(&self.partition_row_presets),
             load_cycle_count: self.load_cycle_count,
             preferred_datacenter: self.preferred_datacenter.clone(),
@@ -220,12 +287,31 @@ impl Context {
 
     /// Prepares a statement and stores it in an internal statement map for future use.
     pub async fn prepare(&self, key: &str, cql: &str) -> Result<(), CassError> {
+        self.prepare_with_options(key, cql, &StatementOptions::default())
+            .await
+    }
+
+    /// Prepares a statement with given execution options and stores it in an internal
+    /// statement map for future use.
+    pub async fn prepare_with_options(
+        &self,
+        key: &str,
+        cql: &str,
+        options: &StatementOptions,
+    ) -> Result<(), CassError> {
         match &self.session {
             Some(session) => {
-                let statement = session
+                let mut statement = session
                     .prepare(Statement::new(cql).with_page_size(self.page_size as i32))
                     .await
                     .map_err(|e| CassError::prepare_error(cql, e))?;
+                options.apply(&mut statement, &self.profiles)?;
+                let mut trace_probabilities = self.trace_probabilities.try_lock().unwrap();
+                match options.trace_probability {
+                    Some(p) => trace_probabilities.insert(key.to_string(), p),
+                    None => trace_probabilities.remove(key),
+                };
+                drop(trace_probabilities);
                 self.statements
                     .try_lock()
                     .unwrap()
@@ -240,7 +326,7 @@ impl Context {
 
     /// Executes an ad-hoc CQL statement with no parameters. Does not prepare.
     pub async fn execute(&self, cql: &str) -> Result<Value, CassError> {
-        self._execute(Some(cql), None, None, None, None, None, false)
+        self._execute(Some(cql), None, None, None, None, None, None, false)
             .await
     }
 
@@ -263,6 +349,7 @@ impl Context {
             Some(cql),
             None,
             None,
+            None,
             Some(expected_rows_num_min),
             Some(expected_rows_num_max),
             Some(custom_err_msg),
@@ -273,10 +360,31 @@ impl Context {
 
     /// Executes a statement prepared and registered earlier by a call to `prepare`.
     pub async fn execute_prepared(&self, key: &str, params: Value) -> Result<Value, CassError> {
-        self._execute(None, Some(key), Some(params), None, None, None, false)
+        self._execute(None, Some(key), Some(params), None, None, None, None, false)
             .await
     }
 
+    /// Executes a statement prepared and registered earlier by a call to `prepare`,
+    /// overriding its execution options.
+    pub async fn execute_prepared_with_options(
+        &self,
+        key: &str,
+        params: Value,
+        options: &StatementOptions,
+    ) -> Result<Value, CassError> {
+        self._execute(
+            None,
+            Some(key),
+            Some(params),
+            Some(options),
+            None,
+            None,
+            None,
+            false,
+        )
+        .await
+    }
+
     /// Executes a statement prepared and registered earlier by a call to `prepare` validating
     /// returning rows for `select` queries.
     pub async fn execute_prepared_with_validation(
@@ -297,6 +405,7 @@ impl Context {
             None,
             Some(key),
             Some(params),
+            None,
             Some(expected_rows_num_min),
             Some(expected_rows_num_max),
             Some(custom_err_msg),
@@ -307,7 +416,7 @@ impl Context {
 
     /// Executes an ad-hoc CQL statement and returns the result data.
     pub async fn execute_with_result(&self, cql: &str) -> Result<Value, CassError> {
-        self._execute(Some(cql), None, None, None, None, None, true)
+        self._execute(Some(cql), None, None, None, None, None, None, true)
             .await
     }
 
@@ -317,7 +426,7 @@ impl Context {
         key: &str,
         params: Value,
     ) -> Result<Value, CassError> {
-        self._execute(None, Some(key), Some(params), None, None, None, true)
+        self._execute(None, Some(key), Some(params), None, None, None, None, true)
             .await
     }
 
@@ -327,6 +436,7 @@ impl Context {
         cql: Option<&str>,
         key: Option<&str>,
         params: Option<Value>,
+        options: Option<&StatementOptions>,
         expected_rows_num_min: Option<u64>,
         expected_rows_num_max: Option<u64>,
         custom_err_msg: Option<&str>,
@@ -345,7 +455,7 @@ impl Context {
                 "Either 'cql' or 'key' is allowed, not both".to_string(),
             )));
         }
-        let stmt = if let Some(key) = key {
+        let mut stmt = if let Some(key) = key {
             self.statements
                 .try_lock()
                 .unwrap()
@@ -363,6 +473,29 @@ impl Context {
                     .map_err(|e| CassError::prepare_error(cql, e))?,
             )
         };
+        if let Some(options) = options {
+            let mut statement = (*stmt).clone();
+            options.apply(&mut statement, &self.profiles)?;
+            stmt = Arc::new(statement);
+        }
+        let trace_probability = options.and_then(|o| o.trace_probability).or_else(|| {
+            key.and_then(|key| {
+                self.trace_probabilities
+                    .try_lock()
+                    .unwrap()
+                    .get(key)
+                    .copied()
+            })
+        });
+        let stats_key = key.unwrap_or(UNPREPARED_STATEMENT);
+        let slow_latency = self.tracer.as_ref().and_then(|t| t.take_next(stats_key));
+        if slow_latency.is_some()
+            || self.tracer.is_some() && trace_probability.is_some_and(|p| rand::random::<f64>() < p)
+        {
+            let mut statement = (*stmt).clone();
+            statement.set_tracing(true);
+            stmt = Arc::new(statement);
+        }
         let cql = stmt.get_statement();
         let query_params = RuneQueryParams::new(params.as_ref());
         if (expected_rows_num_min.is_some() || expected_rows_num_max.is_some())
@@ -388,22 +521,58 @@ impl Context {
         let mut rows_num: u64 = 0;
         let mut last_rows_result = None;
         let mut current_attempt_num = 0;
-        while current_attempt_num <= self.retry_number {
+        while current_attempt_num <= self.retry_policy.retry_number {
             let start_time = self.stats.try_lock().unwrap().start_request();
-            let rs = session
-                .execute_single_page(&stmt, &query_params, paging_state.clone())
-                .await;
+            let rs = speculate(&self.retry_policy, stmt.get_is_idempotent(), || {
+                session.execute_single_page(&stmt, &query_params, paging_state.clone())
+            })
+            .await;
             let current_duration = Instant::now() - start_time;
             let (page, paging_state_response) = match rs {
                 Ok(result) => result,
                 Err(e) => {
-                    let current_error =
-                        CassError::query_execution_error(cql, params.as_ref(), e.clone());
-                    handle_retry_error(self, current_attempt_num, current_error).await;
+                    let current_error = CassError::query_execution_error(cql, params.as_ref(), e);
+                    let retried = self
+                        .retry_policy
+                        .will_retry(current_error.category(), current_attempt_num);
+                    self.stats
+                        .try_lock()
+                        .unwrap()
+                        .fail_request(stats_key, retried);
+                    handle_retry_error(self, current_attempt_num, current_error).await?;
                     current_attempt_num += 1;
                     continue; // try again the same query
                 }
             };
+            let coordinator = page.request_coordinator().clone();
+            if let Some(tracer) = &self.tracer {
+                if let Some(tracing_id) = page.tracing_id() {
+                    let (reason, latency) = match slow_latency {
+                        Some(latency) => (TraceReason::Slow, latency),
+                        None => (TraceReason::Sampled, current_duration),
+                    };
+                    tracer.save(tracing_id, stats_key, cql, reason, latency);
+                } else if tracer.is_slow(current_duration)
+                    && !stmt.get_is_idempotent()
+                    && !IS_SELECT_QUERY.is_match(cql)
+                {
+                    // Sending a write once again could apply it twice
+                    tracer.trace_next(stats_key, current_duration);
+                } else if tracer.is_slow(current_duration) {
+                    let columns = stmt.get_variable_col_specs();
+                    match to_cql_values(params.as_ref(), columns.as_slice()) {
+                        Ok(values) => tracer.rerun(
+                            stats_key,
+                            &stmt,
+                            values,
+                            paging_state.clone(),
+                            current_duration,
+                        ),
+                        Err(e) => eprintln!("WARNING: Failed to trace the slow request: {e}"),
+                    }
+                }
+            }
+            let rows_before = rows_num;
             let rows_result = page.into_rows_result();
             if process_and_return_data {
                 let rows_result = rows_result?;
@@ -438,12 +607,14 @@ impl Context {
                 }
             }
             all_pages_duration += current_duration;
+            self.record_coordinator(&coordinator, current_duration, rows_num - rows_before);
             match paging_state_response.into_paging_control_flow() {
                 ControlFlow::Break(()) => {
-                    self.stats
-                        .try_lock()
-                        .unwrap()
-                        .complete_request(all_pages_duration, rows_num);
+                    self.stats.try_lock().unwrap().complete_request(
+                        stats_key,
+                        all_pages_duration,
+                        rows_num,
+                    );
                     if process_and_return_data {
                         return Value::vec(rune_rows.into_inner()).map_err(|_| {
                             CassError(CassErrorKind::Error(
@@ -477,23 +648,25 @@ impl Context {
                             rows_cnt,
                             custom_err_msg.unwrap_or("").to_string(),
                         );
-                        if self.validation_strategy == ValidationStrategy::Retry {
-                            handle_retry_error(self, current_attempt_num, current_error).await;
-                            current_attempt_num += 1;
-                            rows_num = 0; // we retry all pages, so reset cnt
-                            last_rows_result = None;
-                            continue; // try again the same query
-                        } else if self.validation_strategy == ValidationStrategy::FailFast {
-                            return Err(current_error); // stop stress execution
-                        } else if self.validation_strategy == ValidationStrategy::Ignore {
-                            handle_retry_error(self, current_attempt_num, current_error).await;
-                            return Ok(empty_rune_vec); // handle/print error and go on.
-                        } else {
-                            // should never reach this code branch
-                            return Err(CassError(CassErrorKind::Error(format!(
-                                "Unexpected value for the validation strategy param: {:?}",
-                                self.validation_strategy,
-                            ))));
+                        match self.retry_policy.action(current_error.category()) {
+                            ErrorAction::Retry => {
+                                handle_retry_error(self, current_attempt_num, current_error)
+                                    .await?;
+                                current_attempt_num += 1;
+                                rows_num = 0; // we retry all pages, so reset cnt
+                                last_rows_result = None;
+                                continue; // try again the same query
+                            }
+                            ErrorAction::Fail => {
+                                return Err(current_error); // stop stress execution
+                            }
+                            ErrorAction::Ignore => {
+                                // handle/print error and go on.
+                                let _ =
+                                    handle_retry_error(self, current_attempt_num, current_error)
+                                        .await;
+                                return Ok(empty_rune_vec);
+                            }
                         }
                     }
                 }
@@ -504,64 +677,140 @@ impl Context {
                 }
             }
         }
-        Err(CassError::query_retries_exceeded(self.retry_number))
+        Err(CassError::query_retries_exceeded(
+            self.retry_policy.retry_number,
+        ))
     }
 
+    /// Executes a logged batch of the statements prepared and registered earlier by calls
+    /// to `prepare`.
     pub async fn batch_prepared(
         &self,
         keys: Vec<&str>,
         params: Vec<Value>,
     ) -> Result<(), CassError> {
-        let keys_len = keys.len();
+        self.batch(keys, params, &BatchOptions::default()).await
+    }
+
+    /// Executes a batch of statements. Each statement is given either by the key of
+    /// a statement prepared earlier or by a CQL text, which is sent unprepared.
+    /// The batch is recorded in the statistics of batches, apart from the statements.
+    pub async fn batch(
+        &self,
+        statements: Vec<&str>,
+        params: Vec<Value>,
+        options: &BatchOptions,
+    ) -> Result<(), CassError> {
+        let statements_len = statements.len();
         let params_len = params.len();
-        if keys_len != params_len {
+        if statements_len != params_len {
             return Err(CassError(CassErrorKind::Error(format!(
-                "Number of prepared statements ({keys_len}) and values ({params_len}) must be equal"
+                "Number of statements ({statements_len}) and values ({params_len}) must be equal"
             ))));
-        } else if keys_len == 0 {
+        } else if statements_len == 0 {
             return Err(CassError(CassErrorKind::Error("Empty batch".to_string())));
         }
-        let mut batch: Batch = Batch::new(BatchType::Logged);
-        let mut batch_values: Vec<RuneQueryParams<'_>> = Vec::with_capacity(keys_len);
-        for (i, key) in keys.into_iter().enumerate() {
-            let statement = self
-                .statements
-                .try_lock()
-                .unwrap()
-                .get(key)
-                .cloned()
-                .ok_or_else(|| {
-                    CassError(CassErrorKind::PreparedStatementNotFound(key.to_string()))
-                })?;
-            batch.append_statement((*statement).clone());
+        let mut batch: Batch = Batch::new(options.batch_type.batch_type());
+        options.statement.apply(&mut batch, &self.profiles)?;
+        if self.tracer.is_some()
+            && options
+                .statement
+                .trace_probability
+                .is_some_and(|p| rand::random::<f64>() < p)
+        {
+            batch.set_tracing(true);
+        }
+        let mut batch_values: Vec<RuneQueryParams<'_>> = Vec::with_capacity(statements_len);
+        let mut names = Vec::with_capacity(statements_len);
+        let mut cqls = Vec::with_capacity(statements_len);
+        for (i, statement) in statements.into_iter().enumerate() {
+            let prepared = self.statements.try_lock().unwrap().get(statement).cloned();
+            match prepared {
+                Some(prepared) => {
+                    names.push(statement);
+                    cqls.push(prepared.get_statement().to_string());
+                    batch.append_statement((*prepared).clone());
+                }
+                // Not a key of a prepared statement, but looks like a CQL text
+                None if statement.contains(char::is_whitespace) => {
+                    names.push(UNPREPARED_STATEMENT);
+                    cqls.push(statement.to_string());
+                    batch.append_statement(statement);
+                }
+                None => {
+                    return Err(CassError(CassErrorKind::PreparedStatementNotFound(
+                        statement.to_string(),
+                    )))
+                }
+            }
             batch_values.push(RuneQueryParams::new(params.get(i)));
         }
+        let stats_key = format!(
+            "{}({})",
+            options.batch_type.name(),
+            names.iter().unique().join(",")
+        );
         match &self.session {
             Some(session) => {
                 let mut current_attempt_num = 0;
-                while current_attempt_num <= self.retry_number {
+                while current_attempt_num <= self.retry_policy.retry_number {
                     let start_time = self.stats.try_lock().unwrap().start_request();
-                    let rs = session.batch(&batch, &batch_values).await;
+                    let rs = speculate(&self.retry_policy, batch.get_is_idempotent(), || {
+                        session.batch(&batch, &batch_values)
+                    })
+                    .await;
                     let duration = Instant::now() - start_time;
                     match rs {
-                        Ok(_) => {
-                            self.stats
-                                .try_lock()
-                                .unwrap()
-                                .complete_request(duration, batch_values.len() as u64);
+                        Ok(result) => {
+                            let coordinator = result.request_coordinator().clone();
+                            if let (Some(tracer), Some(tracing_id)) =
+                                (&self.tracer, result.tracing_id())
+                            {
+                                tracer.save(
+                                    tracing_id,
+                                    &stats_key,
+                                    &cqls.join("; "),
+                                    TraceReason::Sampled,
+                                    duration,
+                                );
+                            }
+                            // Only conditional batches return rows
+                            let row_count = result
+                                .into_rows_result()
+                                .map(|rows| rows.rows_num() as u64)
+                                .unwrap_or_default();
+                            self.stats.try_lock().unwrap().complete_batch(
+                                &stats_key,
+                                duration,
+                                statements_len as u64,
+                                row_count,
+                            );
+                            self.record_coordinator(&coordinator, duration, row_count);
                             return Ok(());
                         }
                         Err(e) => {
-                            let current_error = CassError(CassErrorKind::Error(format!(
-                                "batch execution failed: {e}"
-                            )));
-                            handle_retry_error(self, current_attempt_num, current_error).await;
+                            let current_error = CassError::batch_execution_error(
+                                options.batch_type.name(),
+                                &cqls,
+                                &params,
+                                e,
+                            );
+                            let retried = self
+                                .retry_policy
+                                .will_retry(current_error.category(), current_attempt_num);
+                            self.stats
+                                .try_lock()
+                                .unwrap()
+                                .fail_batch(&stats_key, retried);
+                            handle_retry_error(self, current_attempt_num, current_error).await?;
                             current_attempt_num += 1;
                             continue;
                         }
                     }
                 }
-                Err(CassError::query_retries_exceeded(self.retry_number))
+                Err(CassError::query_retries_exceeded(
+                    self.retry_policy.retry_number,
+                ))
             }
             None => Err(CassError(CassErrorKind::Error(
                 "'session' is not defined".to_string(),
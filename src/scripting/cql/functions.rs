@@ -2,6 +2,7 @@ use crate::scripting::functions_common::extract_validation_args;
 
 use super::cass_error::{CassError, CassErrorKind};
 use super::context::Context;
+use super::options::{BatchOptions, StatementOptions};
 use rune::runtime::Ref;
 use rune::Value;
 use std::ops::Deref;
@@ -11,6 +12,17 @@ pub async fn prepare(ctx: Ref<Context>, key: Ref<str>, cql: Ref<str>) -> Result<
     ctx.prepare(&key, &cql).await
 }
 
+#[rune::function(instance)]
+pub async fn prepare_with_options(
+    ctx: Ref<Context>,
+    key: Ref<str>,
+    cql: Ref<str>,
+    options: Value,
+) -> Result<(), CassError> {
+    let options = StatementOptions::from_rune(&options)?;
+    ctx.prepare_with_options(&key, &cql, &options).await
+}
+
 #[rune::function(instance)]
 pub async fn execute(ctx: Ref<Context>, cql: Ref<str>) -> Result<Value, CassError> {
     ctx.execute(cql.deref()).await
@@ -50,6 +62,18 @@ pub async fn execute_prepared(
     ctx.execute_prepared(&key, params).await
 }
 
+#[rune::function(instance)]
+pub async fn execute_prepared_with_options(
+    ctx: Ref<Context>,
+    key: Ref<str>,
+    params: Value,
+    options: Value,
+) -> Result<Value, CassError> {
+    let options = StatementOptions::from_rune(&options)?;
+    ctx.execute_prepared_with_options(&key, params, &options)
+        .await
+}
+
 #[rune::function(instance)]
 pub async fn execute_prepared_with_validation(
     ctx: Ref<Context>,
@@ -92,6 +116,22 @@ pub async fn batch_prepared(
         .await
 }
 
+#[rune::function(instance)]
+pub async fn batch(
+    ctx: Ref<Context>,
+    statements: Vec<Ref<str>>,
+    params: Vec<Value>,
+    options: Value,
+) -> Result<(), CassError> {
+    let options = BatchOptions::from_rune(&options)?;
+    ctx.batch(
+        statements.iter().map(|s| s.deref()).collect(),
+        params,
+        &options,
+    )
+    .await
+}
+
 #[rune::function(instance)]
 pub async fn get_datacenters(ctx: Ref<Context>) -> Result<Vec<String>, CassError> {
     ctx.get_datacenters().await
@@ -5,4 +5,6 @@ pub mod context;
 pub mod cql_types;
 mod deserialize;
 pub mod functions;
+pub mod options;
 mod serialize;
+pub mod trace;
@@ -0,0 +1,335 @@
+//! Execution options of the statements, set by the workload script.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use rune::runtime::{BorrowRef, Object};
+use rune::Value;
+use scylla::client::execution_profile::ExecutionProfileHandle;
+use scylla::statement::batch::Batch;
+use scylla::statement::prepared::PreparedStatement;
+
+use super::cass_error::{CassError, CassErrorKind};
+use super::config::{Consistency, SerialConsistency};
+use crate::config::parse_duration;
+
+/// Options overriding the execution profile of a statement.
+/// Options not given are taken from the execution profile selected by `profile`
+/// or, if no profile is selected, from the default profile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatementOptions {
+    /// Name of the execution profile declared with `--execution-profile`
+    pub profile: Option<String>,
+    pub consistency: Option<Consistency>,
+    pub serial_consistency: Option<SerialConsistency>,
+    pub request_timeout: Option<Duration>,
+    pub idempotent: Option<bool>,
+    /// Client-side timestamp of the writes, in microseconds since the Unix epoch
+    pub timestamp: Option<i64>,
+    pub tracing: Option<bool>,
+    /// Probability of tracing a request, between 0 and 1.
+    /// Applies only if the traced requests are logged.
+    pub trace_probability: Option<f64>,
+}
+
+impl StatementOptions {
+    const NAMES: [&'static str; 8] = [
+        "profile",
+        "consistency",
+        "serial_consistency",
+        "timeout",
+        "idempotent",
+        "timestamp",
+        "tracing",
+        "trace_probability",
+    ];
+
+    /// Reads the options from an object given by the workload script, e.g.
+    /// `#{consistency: "LOCAL_ONE", timeout: "500ms", idempotent: true}`.
+    pub fn from_rune(options: &Value) -> Result<StatementOptions, Box<CassError>> {
+        let mut result = StatementOptions::default();
+        for (key, value) in as_object(options)?.deref() {
+            if !result.set(key, value)? {
+                return Err(unknown_option(key, &Self::NAMES));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Sets the option of given name.
+    /// Returns false if there is no such option.
+    fn set(&mut self, key: &str, value: &Value) -> Result<bool, Box<CassError>> {
+        match key {
+            "profile" => self.profile = Some(as_str(key, value)?),
+            "consistency" => {
+                let value = as_str(key, value)?;
+                self.consistency =
+                    Some(<Consistency as ValueEnum>::from_str(&value, true).map_err(invalid)?);
+            }
+            "serial_consistency" => {
+                let value = as_str(key, value)?;
+                self.serial_consistency = Some(
+                    <SerialConsistency as ValueEnum>::from_str(&value, true).map_err(invalid)?,
+                );
+            }
+            "timeout" => {
+                let value = as_str(key, value)?;
+                self.request_timeout = Some(
+                    parse_duration(&value)
+                        .map_err(|e| invalid(format!("invalid timeout `{value}`: {e}")))?,
+                );
+            }
+            "idempotent" => self.idempotent = Some(as_bool(key, value)?),
+            "timestamp" => {
+                self.timestamp = Some(
+                    value
+                        .as_signed()
+                        .map_err(|_| invalid("`timestamp` must be an integer".to_string()))?,
+                )
+            }
+            "tracing" => self.tracing = Some(as_bool(key, value)?),
+            "trace_probability" => {
+                let p = value
+                    .as_float()
+                    .or_else(|_| value.as_signed().map(|i| i as f64))
+                    .map_err(|_| invalid("`trace_probability` must be a number".to_string()))?;
+                if !(0.0..=1.0).contains(&p) {
+                    return Err(invalid(format!(
+                        "`trace_probability` must be between 0 and 1, got {p}"
+                    )));
+                }
+                self.trace_probability = Some(p);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Sets the options on the statement or batch.
+    /// Returns an error if the selected execution profile is not in `profiles`.
+    pub fn apply(
+        &self,
+        target: &mut impl Configurable,
+        profiles: &HashMap<String, ExecutionProfileHandle>,
+    ) -> Result<(), Box<CassError>> {
+        let profile = match &self.profile {
+            Some(name) => Some(profiles.get(name).cloned().ok_or_else(|| {
+                Box::new(CassError(CassErrorKind::ExecutionProfileNotFound(
+                    name.to_string(),
+                )))
+            })?),
+            None => None,
+        };
+        target.configure(self, profile);
+        Ok(())
+    }
+}
+
+/// Type of a batch
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BatchType {
+    #[default]
+    Logged,
+    Unlogged,
+    Counter,
+}
+
+impl BatchType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchType::Logged => "logged",
+            BatchType::Unlogged => "unlogged",
+            BatchType::Counter => "counter",
+        }
+    }
+
+    pub fn batch_type(&self) -> scylla::statement::batch::BatchType {
+        match self {
+            BatchType::Logged => scylla::statement::batch::BatchType::Logged,
+            BatchType::Unlogged => scylla::statement::batch::BatchType::Unlogged,
+            BatchType::Counter => scylla::statement::batch::BatchType::Counter,
+        }
+    }
+}
+
+/// Options of a batch, given by the workload script
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchOptions {
+    pub batch_type: BatchType,
+    /// Execution options of the whole batch
+    pub statement: StatementOptions,
+}
+
+impl BatchOptions {
+    /// Reads the options from an object given by the workload script, e.g.
+    /// `#{type: "unlogged", consistency: "ONE"}`.
+    /// Accepts the batch type and all the options of a statement.
+    pub fn from_rune(options: &Value) -> Result<BatchOptions, Box<CassError>> {
+        let mut result = BatchOptions::default();
+        for (key, value) in as_object(options)?.deref() {
+            if key.as_str() == "type" {
+                let value = as_str(key, value)?;
+                result.batch_type = match value.to_lowercase().as_str() {
+                    "logged" => BatchType::Logged,
+                    "unlogged" => BatchType::Unlogged,
+                    "counter" => BatchType::Counter,
+                    _ => {
+                        return Err(invalid(format!(
+                        "unknown batch type `{value}`. Expected one of: logged, unlogged, counter"
+                    )))
+                    }
+                };
+            } else if !result.statement.set(key, value)? {
+                let names = StatementOptions::NAMES;
+                return Err(unknown_option(key, &[&["type"], names.as_slice()].concat()));
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Statement or batch of the driver the execution options can be set on
+pub trait Configurable {
+    fn configure(&mut self, options: &StatementOptions, profile: Option<ExecutionProfileHandle>);
+}
+
+/// The statements and the batches of the driver have the same setters, but no common trait
+macro_rules! impl_configurable {
+    ($($t:ty),*) => {
+        $(impl Configurable for $t {
+            fn configure(
+                &mut self,
+                options: &StatementOptions,
+                profile: Option<ExecutionProfileHandle>,
+            ) {
+                if profile.is_some() {
+                    self.set_execution_profile_handle(profile);
+                }
+                if let Some(consistency) = options.consistency {
+                    self.set_consistency(consistency.consistency());
+                }
+                if let Some(serial_consistency) = options.serial_consistency {
+                    self.set_serial_consistency(Some(serial_consistency.serial_consistency()));
+                }
+                if let Some(timeout) = options.request_timeout {
+                    self.set_request_timeout(Some(timeout));
+                }
+                if let Some(idempotent) = options.idempotent {
+                    self.set_is_idempotent(idempotent);
+                }
+                if let Some(timestamp) = options.timestamp {
+                    self.set_timestamp(Some(timestamp));
+                }
+                if let Some(tracing) = options.tracing {
+                    self.set_tracing(tracing);
+                }
+            }
+        })*
+    };
+}
+
+impl_configurable!(PreparedStatement, Batch);
+
+fn invalid(msg: String) -> Box<CassError> {
+    Box::new(CassError(CassErrorKind::InvalidStatementOptions(msg)))
+}
+
+fn unknown_option(key: &str, names: &[&str]) -> Box<CassError> {
+    invalid(format!(
+        "unknown option `{key}`. Expected one of: {}",
+        names.join(", ")
+    ))
+}
+
+fn as_object(value: &Value) -> Result<BorrowRef<'_, Object>, Box<CassError>> {
+    value
+        .borrow_ref::<Object>()
+        .map_err(|_| invalid("expected an object".to_string()))
+}
+
+fn as_str(key: &str, value: &Value) -> Result<String, Box<CassError>> {
+    value
+        .borrow_ref::<rune::alloc::String>()
+        .map(|s| s.as_str().to_string())
+        .map_err(|_| invalid(format!("`{key}` must be a string")))
+}
+
+fn as_bool(key: &str, value: &Value) -> Result<bool, Box<CassError>> {
+    value
+        .as_bool()
+        .map_err(|_| invalid(format!("`{key}` must be a boolean")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn options(entries: &[(&str, Value)]) -> Value {
+        let mut object = Object::new();
+        for (key, value) in entries {
+            object
+                .insert(rune::alloc::String::try_from(*key).unwrap(), value.clone())
+                .unwrap();
+        }
+        Value::new(object).unwrap()
+    }
+
+    fn string(s: &str) -> Value {
+        rune::to_value(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn read_statement_options() {
+        let value = options(&[
+            ("profile", string("reads")),
+            ("consistency", string("LOCAL_ONE")),
+            ("serial_consistency", string("serial")),
+            ("timeout", string("500ms")),
+            ("idempotent", Value::from(true)),
+            ("timestamp", Value::from(1_700_000_000_000_000i64)),
+            ("tracing", Value::from(false)),
+            ("trace_probability", Value::from(0.01)),
+        ]);
+        let parsed = StatementOptions::from_rune(&value).unwrap();
+        assert_eq!(
+            parsed,
+            StatementOptions {
+                profile: Some("reads".to_string()),
+                consistency: Some(Consistency::LocalOne),
+                serial_consistency: Some(SerialConsistency::Serial),
+                request_timeout: Some(Duration::from_millis(500)),
+                idempotent: Some(true),
+                timestamp: Some(1_700_000_000_000_000),
+                tracing: Some(false),
+                trace_probability: Some(0.01),
+            }
+        );
+        let empty = StatementOptions::from_rune(&options(&[])).unwrap();
+        assert_eq!(empty, StatementOptions::default());
+    }
+
+    #[test]
+    fn reject_invalid_statement_options() {
+        let invalid = |entries: &[(&str, Value)]| {
+            let err = StatementOptions::from_rune(&options(entries)).unwrap_err();
+            matches!(err.0, CassErrorKind::InvalidStatementOptions(_))
+        };
+        assert!(invalid(&[("consistency", string("TWO_AND_A_HALF"))]));
+        assert!(invalid(&[("timeout", Value::from(true))]));
+        assert!(invalid(&[("retries", Value::from(3i64))]));
+        assert!(invalid(&[("trace_probability", Value::from(1.5))]));
+        assert!(StatementOptions::from_rune(&Value::from(1i64)).is_err());
+    }
+
+    #[test]
+    fn read_batch_options() {
+        let value = options(&[("type", string("Unlogged")), ("consistency", string("ONE"))]);
+        let parsed = BatchOptions::from_rune(&value).unwrap();
+        assert_eq!(parsed.batch_type, BatchType::Unlogged);
+        assert_eq!(parsed.statement.consistency, Some(Consistency::One));
+        assert!(BatchOptions::from_rune(&options(&[("type", string("atomic"))])).is_err());
+        assert!(StatementOptions::from_rune(&options(&[("type", string("counter"))])).is_err());
+    }
+}
@@ -114,6 +114,49 @@ fn serialize_rune_params(
     )))
 }
 
+/// Converts the rune parameters to owned CQL values.
+/// Unlike `RuneQueryParams`, the result can be sent to another task.
+pub fn to_cql_values(
+    value: Option<&Value>,
+    columns: &[ColumnSpec<'_>],
+) -> Result<Vec<Option<CqlValue>>, Box<CassError>> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+    if let Ok(tuple) = value.borrow_ref::<OwnedTuple>() {
+        return tuple
+            .iter()
+            .zip(columns)
+            .map(|(v, col)| to_scylla_value(v, col.typ()))
+            .collect();
+    }
+    if let Ok(vec) = value.borrow_ref::<RuneVec>() {
+        return vec
+            .iter()
+            .zip(columns)
+            .map(|(v, col)| to_scylla_value(v, col.typ()))
+            .collect();
+    }
+    let field = |get: &dyn Fn(&str) -> Option<Value>| {
+        columns
+            .iter()
+            .map(|col| match get(col.name()) {
+                Some(v) => to_scylla_value(&v, col.typ()),
+                None => Ok(Some(CqlValue::Empty)),
+            })
+            .collect()
+    };
+    if let Ok(obj) = value.borrow_ref::<Object>() {
+        return field(&|name| obj.get(name).cloned());
+    }
+    if let Ok(rune::runtime::TypeValue::Struct(s)) = value.as_type_value() {
+        return field(&|name| s.get(name).cloned());
+    }
+    Err(Box::new(CassError(
+        CassErrorKind::InvalidQueryParamsObject(value.type_info()),
+    )))
+}
+
 /// Serializes a single rune value as a CQL cell.
 fn serialize_rune_cell(
     v: &Value,
@@ -1129,6 +1172,25 @@ mod tests {
         assert!(!buf.is_empty());
     }
 
+    #[test]
+    fn test_owned_values_serialize_like_rune_params() {
+        let cols = [
+            col_spec("a", ColumnType::Native(NativeType::Int)),
+            col_spec("b", ColumnType::Native(NativeType::Text)),
+        ];
+        for val in [
+            rune_object(vec![("a", rune_int(1))]),
+            rune_tuple(vec![rune_int(1), rune_string("hello")]),
+        ] {
+            let values = to_cql_values(Some(&val), &cols).unwrap();
+            let ctx = RowSerializationContext::from_specs(&cols);
+            let mut buf = Vec::new();
+            SerializeRow::serialize(&values, &ctx, &mut RowWriter::new(&mut buf)).unwrap();
+            assert_eq!(buf, do_serialize(&RuneQueryParams::new(Some(&val)), &cols));
+        }
+        assert!(to_cql_values(Some(&rune_bool(true)), &cols).is_err());
+    }
+
     #[test]
     fn test_serialize_invalid_param_type_errors() {
         let val = rune_bool(true);
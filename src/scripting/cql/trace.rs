@@ -0,0 +1,186 @@
+//! Tracing of the slow and sampled requests.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Local;
+use scylla::client::session::Session;
+use scylla::response::PagingState;
+use scylla::statement::prepared::PreparedStatement;
+use scylla::value::CqlValue;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::report::traces::{TraceEvent, TraceLogWriter, TraceReason, TracedRequest};
+
+/// Maximum number of traces being fetched at once.
+/// Further requests are not traced until some of the pending ones complete,
+/// so that tracing can't overload the cluster.
+const MAX_PENDING_TRACES: usize = 64;
+
+/// Fetches the traces of the requests from `system_traces` in the background
+/// and saves them to the trace log.
+pub struct Tracer {
+    session: Arc<Session>,
+    slow_threshold: Option<Duration>,
+    log: Arc<TraceLogWriter>,
+    pending: Mutex<JoinSet<()>>,
+    /// Latencies of the slow requests, by statement, that couldn't be sent once again
+    /// and are traced on the next execution of the statement instead
+    traced_next: Mutex<HashMap<String, Duration>>,
+}
+
+impl Tracer {
+    pub fn new(session: Arc<Session>, slow_threshold: Option<Duration>, path: PathBuf) -> Tracer {
+        Tracer {
+            session,
+            slow_threshold,
+            log: Arc::new(TraceLogWriter::new(path)),
+            pending: Mutex::new(JoinSet::new()),
+            traced_next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns true if the request that took given time must be traced
+    pub fn is_slow(&self, duration: Duration) -> bool {
+        self.slow_threshold.is_some_and(|t| duration > t)
+    }
+
+    /// Fetches the trace of a request executed with tracing enabled and saves it to the log
+    pub fn save(
+        &self,
+        tracing_id: Uuid,
+        statement: &str,
+        cql: &str,
+        reason: TraceReason,
+        latency: Duration,
+    ) {
+        let session = self.session.clone();
+        let log = self.log.clone();
+        let statement = statement.to_string();
+        let cql = cql.to_string();
+        self.spawn(async move {
+            fetch_and_save(&session, &log, tracing_id, statement, cql, reason, latency).await
+        });
+    }
+
+    /// Marks the statement of a slow request that is not safe to send once again,
+    /// so that its next execution is traced instead.
+    pub fn trace_next(&self, statement: &str, latency: Duration) {
+        self.traced_next
+            .lock()
+            .unwrap()
+            .insert(statement.to_string(), latency);
+    }
+
+    /// Returns the latency of the slow request of the statement if the current execution
+    /// of the statement must be traced in its place, and clears the mark.
+    pub fn take_next(&self, statement: &str) -> Option<Duration> {
+        self.traced_next.lock().unwrap().remove(statement)
+    }
+
+    /// Sends the slow request once again with tracing enabled and saves its trace to the log.
+    /// The original latency is recorded, because the traced request may complete faster.
+    /// Must be used only for the requests that can be safely sent twice.
+    pub fn rerun(
+        &self,
+        statement: &str,
+        prepared: &PreparedStatement,
+        values: Vec<Option<CqlValue>>,
+        paging_state: PagingState,
+        latency: Duration,
+    ) {
+        let session = self.session.clone();
+        let log = self.log.clone();
+        let statement = statement.to_string();
+        let mut prepared = prepared.clone();
+        prepared.set_tracing(true);
+        self.spawn(async move {
+            let cql = prepared.get_statement().to_string();
+            match session
+                .execute_single_page(&prepared, values, paging_state)
+                .await
+            {
+                Ok((result, _)) => match result.tracing_id() {
+                    Some(id) => {
+                        fetch_and_save(
+                            &session,
+                            &log,
+                            id,
+                            statement,
+                            cql,
+                            TraceReason::Slow,
+                            latency,
+                        )
+                        .await
+                    }
+                    None => eprintln!("WARNING: No trace of the slow request returned: {cql}"),
+                },
+                Err(e) => eprintln!("WARNING: Failed to trace the slow request {cql}: {e}"),
+            }
+        });
+    }
+
+    fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut pending = self.pending.lock().unwrap();
+        while pending.try_join_next().is_some() {}
+        if pending.len() < MAX_PENDING_TRACES {
+            pending.spawn(task);
+        }
+    }
+
+    /// Waits until the pending traces are saved, but no longer than given time
+    pub async fn finish(&self, timeout: Duration) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if tokio::time::timeout(timeout, pending.join_all())
+            .await
+            .is_err()
+        {
+            eprintln!("WARNING: Timed out waiting for the traces of the requests");
+        }
+    }
+}
+
+async fn fetch_and_save(
+    session: &Session,
+    log: &TraceLogWriter,
+    tracing_id: Uuid,
+    statement: String,
+    cql: String,
+    reason: TraceReason,
+    latency: Duration,
+) {
+    let info = match session.get_tracing_info(&tracing_id).await {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("WARNING: Failed to fetch the trace {tracing_id}: {e}");
+            return;
+        }
+    };
+    let trace = TracedRequest {
+        time: Local::now(),
+        statement,
+        cql,
+        reason,
+        latency_ms: latency.as_secs_f64() * 1000.0,
+        session_id: tracing_id.to_string(),
+        coordinator: info.coordinator.map(|ip| ip.to_string()),
+        duration_us: info.duration.map(i64::from),
+        events: info
+            .events
+            .into_iter()
+            .map(|e| TraceEvent {
+                elapsed_us: e.source_elapsed.map(i64::from),
+                source: e.source.map(|ip| ip.to_string()),
+                thread: e.thread,
+                activity: e.activity,
+            })
+            .collect(),
+    };
+    if let Err(e) = log.write(&trace) {
+        eprintln!("WARNING: Failed to write the trace {tracing_id}: {e}");
+    }
+}
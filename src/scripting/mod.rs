@@ -50,6 +50,7 @@ fn try_install(
 
     let mut context_module = init_context_module()?;
     context_module.function_meta(functions::prepare)?;
+    context_module.function_meta(functions::prepare_with_options)?;
 
     // NOTE: 1st group of query-oriented functions - without usage of prepared statements
     context_module.function_meta(functions::execute)?;
@@ -57,10 +58,12 @@ fn try_install(
     context_module.function_meta(functions::execute_with_result)?;
     // NOTE: 2nd group of query-oriented functions - with usage of prepared statements
     context_module.function_meta(functions::execute_prepared)?;
+    context_module.function_meta(functions::execute_prepared_with_options)?;
     context_module.function_meta(functions::execute_prepared_with_validation)?;
     context_module.function_meta(functions::execute_prepared_with_result)?;
 
     context_module.function_meta(functions::batch_prepared)?;
+    context_module.function_meta(functions::batch)?;
     context_module.function_meta(functions::get_datacenters)?;
 
     let err_module = init_error_module()?;
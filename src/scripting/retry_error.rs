@@ -1,19 +1,116 @@
 use chrono::Utc;
+use futures::future::select_ok;
 use rand::random;
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::time::Duration;
 use tracing::error;
 
 use super::context::Context;
 use super::db_error::DbError;
+use crate::config::{ConnectionConf, ErrorAction, RetryBackoff, RetryInterval, ValidationStrategy};
+use crate::stats::errors::ErrorCategory;
 
-pub fn get_exponential_retry_interval(
+/// Decides which failed requests are retried, how many times and how long to wait between
+/// the attempts. Shared by the requests and by the calls of the workload functions.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub retry_number: u64,
+    pub retry_interval: RetryInterval,
+    pub backoff: RetryBackoff,
+    actions: BTreeMap<ErrorCategory, ErrorAction>,
+    pub speculative_delay: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// Creates a policy retrying all errors with exponential backoff, except validation errors
+    /// which are handled according to the validation strategy.
+    pub fn new(
+        retry_number: u64,
+        retry_interval: RetryInterval,
+        validation_strategy: ValidationStrategy,
+    ) -> RetryPolicy {
+        RetryPolicy {
+            retry_number,
+            retry_interval,
+            backoff: RetryBackoff::Exponential,
+            actions: BTreeMap::from([(
+                ErrorCategory::Validation,
+                validation_strategy.error_action(),
+            )]),
+            speculative_delay: None,
+        }
+    }
+
+    pub fn from_conf(conf: &ConnectionConf) -> RetryPolicy {
+        let mut policy = RetryPolicy::new(
+            conf.retry_number,
+            conf.retry_interval,
+            conf.validation_strategy,
+        )
+        .with_backoff(conf.retry_backoff)
+        .with_speculative_delay(conf.speculative_retry);
+        for rule in &conf.error_rules {
+            policy = policy.with_action(rule.category, rule.action);
+        }
+        policy
+    }
+
+    pub fn with_backoff(mut self, backoff: RetryBackoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the action taken on the errors of given category
+    pub fn with_action(mut self, category: ErrorCategory, action: ErrorAction) -> Self {
+        self.actions.insert(category, action);
+        self
+    }
+
+    pub fn with_speculative_delay(mut self, delay: Option<Duration>) -> Self {
+        self.speculative_delay = delay;
+        self
+    }
+
+    /// Returns the action taken on the errors of given category
+    pub fn action(&self, category: ErrorCategory) -> ErrorAction {
+        self.actions
+            .get(&category)
+            .copied()
+            .unwrap_or(ErrorAction::Retry)
+    }
+
+    /// Returns true if the attempt that failed with an error of given category
+    /// is going to be followed by another attempt
+    pub fn will_retry(&self, category: ErrorCategory, attempt_num: u64) -> bool {
+        self.action(category) == ErrorAction::Retry && attempt_num < self.retry_number
+    }
+
+    /// Returns the time to wait before the next attempt
+    pub fn interval(&self, attempt_num: u64) -> Duration {
+        get_retry_interval(
+            self.backoff,
+            self.retry_interval.min,
+            self.retry_interval.max,
+            attempt_num,
+        )
+    }
+}
+
+pub fn get_retry_interval(
+    backoff: RetryBackoff,
     min_interval: Duration,
     max_interval: Duration,
     current_attempt_num: u64,
 ) -> Duration {
     let min_interval_float: f64 = min_interval.as_secs_f64();
-    let mut current_interval: f64 =
-        min_interval_float * (2u64.pow(current_attempt_num.try_into().unwrap_or(0)) as f64);
+    let mut current_interval: f64 = match backoff {
+        RetryBackoff::Exponential => {
+            min_interval_float * (2u64.pow(current_attempt_num.try_into().unwrap_or(0)) as f64)
+        }
+        RetryBackoff::Linear => min_interval_float * (current_attempt_num + 1) as f64,
+        RetryBackoff::Constant => min_interval_float,
+    };
 
     // Add jitter
     current_interval += random::<f64>() * min_interval_float;
@@ -22,31 +119,140 @@ pub fn get_exponential_retry_interval(
     Duration::from_secs_f64(current_interval.min(max_interval.as_secs_f64()))
 }
 
-pub async fn handle_retry_error(ctxt: &Context, current_attempt_num: u64, current_error: DbError) {
-    let current_retry_interval = get_exponential_retry_interval(
-        ctxt.retry_interval.min,
-        ctxt.retry_interval.max,
-        current_attempt_num,
-    );
+/// Handles a failed attempt of a request or of a validation of its response.
+/// Logs and counts the error and, if the request is going to be retried,
+/// waits for the backoff interval and returns `Ok`.
+/// Returns the error if the request must not be retried, either because of the action
+/// configured for the category of the error or because all the attempts have been used.
+pub async fn handle_retry_error(
+    ctxt: &Context,
+    current_attempt_num: u64,
+    current_error: DbError,
+) -> Result<(), DbError> {
+    let policy = &ctxt.retry_policy;
+    let category = current_error.category();
+    let will_retry = policy.will_retry(category, current_attempt_num);
+    let current_retry_interval = policy.interval(current_attempt_num);
 
     let mut next_attempt_str = String::new();
-    let is_last_attempt = current_attempt_num == ctxt.retry_number;
-    if !is_last_attempt {
+    if will_retry {
         next_attempt_str += &format!("[Retry in {} ms]", current_retry_interval.as_millis());
     }
     let err_msg = format!(
         "{}: [ERROR][Attempt {}/{}]{} {}",
         Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
         current_attempt_num,
-        ctxt.retry_number,
+        policy.retry_number,
         next_attempt_str,
         current_error,
     );
     error!("{}", err_msg);
-    if !is_last_attempt {
+    ctxt.stats.try_lock().unwrap().record_error(category);
+    if will_retry {
         ctxt.stats.try_lock().unwrap().store_retry_error(err_msg);
         tokio::time::sleep(current_retry_interval).await;
-    } else {
-        eprintln!("{err_msg}");
+        return Ok(());
+    }
+    eprintln!("{err_msg}");
+    ctxt.stats.try_lock().unwrap().store_error(err_msg);
+    match policy.action(category) {
+        ErrorAction::Retry => Err(DbError::query_retries_exceeded(policy.retry_number)),
+        ErrorAction::Ignore | ErrorAction::Fail => Err(current_error),
+    }
+}
+
+/// Sends the request and, if it doesn't complete within the speculative delay of the policy,
+/// sends it once again. Returns the first successful response, or the last error
+/// if both attempts fail. Requests that are not idempotent are sent only once,
+/// because sending them twice could apply their effects twice.
+pub async fn speculate<F, Fut, T, E>(
+    policy: &RetryPolicy,
+    idempotent: bool,
+    request: F,
+) -> Result<T, E>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let Some(delay) = policy.speculative_delay.filter(|_| idempotent) else {
+        return request().await;
+    };
+    let mut first = Box::pin(request());
+    if let Ok(result) = tokio::time::timeout(delay, &mut first).await {
+        return result;
+    }
+    let second = Box::pin(request());
+    select_ok([first, second]).await.map(|(result, _)| result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(
+            3,
+            RetryInterval::new("100ms,1s").unwrap(),
+            ValidationStrategy::FailFast,
+        )
+    }
+
+    #[test]
+    fn actions_of_error_categories() {
+        let policy = policy().with_action(ErrorCategory::Timeout, ErrorAction::Ignore);
+        assert_eq!(policy.action(ErrorCategory::Timeout), ErrorAction::Ignore);
+        assert_eq!(policy.action(ErrorCategory::Validation), ErrorAction::Fail);
+        assert_eq!(policy.action(ErrorCategory::Overloaded), ErrorAction::Retry);
+        assert!(policy.will_retry(ErrorCategory::Overloaded, 2));
+        assert!(!policy.will_retry(ErrorCategory::Overloaded, 3));
+        assert!(!policy.will_retry(ErrorCategory::Timeout, 0));
+    }
+
+    #[test]
+    fn backoff_shapes() {
+        let min = Duration::from_millis(100);
+        let max = Duration::from_secs(10);
+        let interval = |backoff, attempt| get_retry_interval(backoff, min, max, attempt);
+        let around = |d: Duration, ms: u64| {
+            d >= Duration::from_millis(ms - 50) && d <= Duration::from_millis(ms + 50)
+        };
+        assert!(around(interval(RetryBackoff::Exponential, 3), 800));
+        assert!(around(interval(RetryBackoff::Linear, 3), 400));
+        assert!(around(interval(RetryBackoff::Constant, 3), 100));
+        assert_eq!(interval(RetryBackoff::Exponential, 20), max);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn speculative_retry_takes_first_response() {
+        let policy = policy().with_speculative_delay(Some(Duration::from_millis(10)));
+        let attempts = AtomicU32::new(0);
+        let result: Result<u32, ()> = speculate(&policy, true, || async {
+            let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+            // The first attempt is slow, the speculative one is fast
+            let delay = if attempt == 0 { 1000 } else { 5 };
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            Ok(attempt)
+        })
+        .await;
+        assert_eq!(result, Ok(1));
+
+        let policy = policy.with_speculative_delay(None);
+        let result: Result<u32, ()> = speculate(&policy, true, || async { Ok(7) }).await;
+        assert_eq!(result, Ok(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_speculative_retry_of_non_idempotent_requests() {
+        let policy = policy().with_speculative_delay(Some(Duration::from_millis(10)));
+        let attempts = AtomicU32::new(0);
+        let result: Result<u32, ()> = speculate(&policy, false, || async {
+            let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+            Ok(attempt)
+        })
+        .await;
+        assert_eq!(result, Ok(0));
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
     }
 }
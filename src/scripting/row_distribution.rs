@@ -494,6 +494,7 @@ fn max_gcd_with_tail(n1: u64, n2: u64) -> (
 #[cfg(test)]
 mod tests {
     use crate::config::{RetryInterval, ValidationStrategy};
+    use crate::scripting::retry_error::RetryPolicy;
 
     use super::*;
 
@@ -511,18 +512,24 @@ mod tests {
     #[cfg(feature = "cql")]
     fn create_test_context() -> Context {
         Context::new(
-            None, 501, "foo-dc".to_string(), "foo-rack".to_string(), 0,
-            RetryInterval::new("1,2").expect("failed to parse retry interval"),
-            ValidationStrategy::Ignore,
+            None, 501, "foo-dc".to_string(), "foo-rack".to_string(),
+            RetryPolicy::new(
+                0,
+                RetryInterval::new("1,2").expect("failed to parse retry interval"),
+                ValidationStrategy::Ignore,
+            ),
         )
     }
 
     #[cfg(feature = "alternator")]
     fn create_test_context() -> Context {
         Context::new(
-            None, 0,
-            RetryInterval::new("1,2").expect("failed to parse retry interval"),
-            ValidationStrategy::Ignore,
+            None,
+            RetryPolicy::new(
+                0,
+                RetryInterval::new("1,2").expect("failed to parse retry interval"),
+                ValidationStrategy::Ignore,
+            ),
             0,
         )
     }
@@ -0,0 +1,420 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::stats::percentiles::Percentile;
+use crate::stats::{t_test, BenchmarkCmp, BenchmarkStats, Mean};
+
+/// A statistic of a benchmark run that can be checked by an assertion.
+/// Latencies are expressed in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Metric {
+    CycleThroughput,
+    ReqThroughput,
+    RowThroughput,
+    ErrorCount,
+    /// Fraction of failed cycles, in range 0.0..1.0
+    ErrorsRatio,
+    CycleLatencyMean,
+    CycleLatency(f64),
+    ReqLatencyMean,
+    ReqLatency(f64),
+}
+
+impl Metric {
+    /// Returns the mean of the metric with its standard error, if the metric has one.
+    fn mean(&self, stats: &BenchmarkStats) -> Option<Mean> {
+        match self {
+            Metric::CycleThroughput => Some(stats.cycle_throughput),
+            Metric::ReqThroughput => Some(stats.req_throughput),
+            Metric::RowThroughput => Some(stats.row_throughput),
+            Metric::ErrorCount | Metric::ErrorsRatio => None,
+            Metric::CycleLatencyMean => Some(stats.cycle_latency.mean),
+            Metric::CycleLatency(p) => stats.cycle_latency.percentiles.find(*p),
+            Metric::ReqLatencyMean => stats.request_latency.as_ref().map(|l| l.mean),
+            Metric::ReqLatency(p) => stats.request_latency.as_ref()?.percentiles.find(*p),
+        }
+    }
+
+    fn value(&self, stats: &BenchmarkStats) -> Option<f64> {
+        match self {
+            Metric::ErrorCount => Some(stats.error_count as f64),
+            Metric::ErrorsRatio => stats.errors_ratio.map(|r| r / 100.0),
+            _ => self.mean(stats).map(|m| m.value),
+        }
+    }
+
+    /// Returns the latency percentile the metric refers to, if any
+    fn percentile(&self) -> Option<f64> {
+        match self {
+            Metric::CycleLatency(p) | Metric::ReqLatency(p) => Some(*p),
+            _ => None,
+        }
+    }
+
+    fn is_latency(&self) -> bool {
+        matches!(
+            self,
+            Metric::CycleLatencyMean
+                | Metric::CycleLatency(_)
+                | Metric::ReqLatencyMean
+                | Metric::ReqLatency(_)
+        )
+    }
+
+    fn higher_is_better(&self) -> bool {
+        matches!(
+            self,
+            Metric::CycleThroughput | Metric::ReqThroughput | Metric::RowThroughput
+        )
+    }
+
+    fn parse_latency(s: &str) -> Option<(bool, f64)> {
+        let (is_req, s) = match s.strip_prefix("req_") {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let p = match s {
+            "mean" => return Some((is_req, -1.0)),
+            "min" => 0.0,
+            "max" => 100.0,
+            _ => s.strip_prefix('p')?.parse().ok()?,
+        };
+        Some((is_req, p))
+    }
+}
+
+/// Returns the percentiles that are always computed, regardless of `--percentiles`
+fn well_known_percentiles() -> impl Iterator<Item = f64> {
+    Percentile::iter().map(|p| p.value())
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let metric = match s {
+            "cycle_throughput" | "throughput" => Metric::CycleThroughput,
+            "req_throughput" => Metric::ReqThroughput,
+            "row_throughput" => Metric::RowThroughput,
+            "error_count" | "errors" => Metric::ErrorCount,
+            "errors_ratio" => Metric::ErrorsRatio,
+            _ => match Metric::parse_latency(s) {
+                Some((false, p)) if p < 0.0 => Metric::CycleLatencyMean,
+                Some((true, p)) if p < 0.0 => Metric::ReqLatencyMean,
+                Some((is_req, p)) if (0.0..=100.0).contains(&p) => {
+                    if is_req {
+                        Metric::ReqLatency(p)
+                    } else {
+                        Metric::CycleLatency(p)
+                    }
+                }
+                Some(_) => return Err(format!("Percentile in '{s}' out of range 0 to 100")),
+                None => return Err(format!("Unknown metric '{s}'")),
+            },
+        };
+        Ok(metric)
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let fmt_percentile = |p: f64| match p {
+            0.0 => "min".to_string(),
+            100.0 => "max".to_string(),
+            p => format!("p{p}"),
+        };
+        match self {
+            Metric::CycleThroughput => write!(f, "cycle_throughput"),
+            Metric::ReqThroughput => write!(f, "req_throughput"),
+            Metric::RowThroughput => write!(f, "row_throughput"),
+            Metric::ErrorCount => write!(f, "error_count"),
+            Metric::ErrorsRatio => write!(f, "errors_ratio"),
+            Metric::CycleLatencyMean => write!(f, "mean"),
+            Metric::CycleLatency(p) => write!(f, "{}", fmt_percentile(*p)),
+            Metric::ReqLatencyMean => write!(f, "req_mean"),
+            Metric::ReqLatency(p) => write!(f, "req_{}", fmt_percentile(*p)),
+        }
+    }
+}
+
+/// Tells what value derived from a metric is compared with the threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Check {
+    /// The value of the metric
+    Value,
+    /// Relative change of the metric from the baseline, in percent
+    Change,
+    /// Probability that the metric did not get worse than in the baseline (p-value).
+    /// Equals 1.0 if the metric did not get worse.
+    Regression,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CmpOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn apply(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CmpOp::Lt => write!(f, "<"),
+            CmpOp::Le => write!(f, "<="),
+            CmpOp::Gt => write!(f, ">"),
+            CmpOp::Ge => write!(f, ">="),
+        }
+    }
+}
+
+/// A condition that the results of a run must satisfy, e.g. `p99<20ms`.
+/// Conditions referring to the baseline, e.g. `regression(cycle_throughput)>0.01`,
+/// are evaluated only if the baseline is given.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Assertion {
+    pub check: Check,
+    pub metric: Metric,
+    pub op: CmpOp,
+    pub threshold: f64,
+}
+
+/// Result of evaluating an assertion against the results of a run
+pub struct AssertionOutcome {
+    pub assertion: Assertion,
+    /// The value compared with the threshold; `None` if it could not be computed
+    pub value: Option<f64>,
+    pub passed: bool,
+}
+
+impl Assertion {
+    /// Returns true if the assertion can be evaluated only with the baseline.
+    pub fn needs_baseline(&self) -> bool {
+        self.check != Check::Value
+    }
+
+    /// Returns an error if the assertion refers to a latency percentile that is not computed,
+    /// i.e. neither a well-known percentile nor one of the `reported` percentiles.
+    pub fn check_percentile(&self, reported: &[f64]) -> Result<(), String> {
+        let Some(p) = self.metric.percentile() else {
+            return Ok(());
+        };
+        let mut supported = well_known_percentiles().chain(reported.iter().copied());
+        if supported.any(|s| (s - p).abs() < 1e-9) {
+            return Ok(());
+        }
+        let supported = well_known_percentiles()
+            .chain(reported.iter().copied())
+            .sorted_by(f64::total_cmp)
+            .dedup()
+            .join(", ");
+        Err(format!(
+            "Unsupported percentile in '{self}'. Add it to --percentiles or use one of: {supported}"
+        ))
+    }
+
+    pub fn evaluate(&self, cmp: &BenchmarkCmp) -> AssertionOutcome {
+        let value = match self.check {
+            Check::Value => self.metric.value(cmp.v1),
+            Check::Change => cmp.v2.and_then(|v2| {
+                let v1 = self.metric.value(cmp.v1)?;
+                let v2 = self.metric.value(v2)?;
+                Some(100.0 * (v1 - v2) / v2).filter(|c| c.is_finite())
+            }),
+            Check::Regression => cmp.v2.and_then(|v2| {
+                let m1 = self.metric.mean(cmp.v1)?;
+                let m2 = self.metric.mean(v2)?;
+                let got_worse = if self.metric.higher_is_better() {
+                    m1.value < m2.value
+                } else {
+                    m1.value > m2.value
+                };
+                Some(if got_worse { t_test(&m1, &m2) } else { 1.0 })
+            }),
+        };
+        AssertionOutcome {
+            assertion: self.clone(),
+            value,
+            passed: value.is_some_and(|v| self.op.apply(v, self.threshold)),
+        }
+    }
+}
+
+impl FromStr for Assertion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pos = s
+            .find(['<', '>'])
+            .ok_or_else(|| format!("Missing comparison operator in '{s}'"))?;
+        let (lhs, rhs) = s.split_at(pos);
+        let (op, rhs) = match (rhs.starts_with('<'), &rhs[1..]) {
+            (true, r) if r.starts_with('=') => (CmpOp::Le, &r[1..]),
+            (true, r) => (CmpOp::Lt, r),
+            (false, r) if r.starts_with('=') => (CmpOp::Ge, &r[1..]),
+            (false, r) => (CmpOp::Gt, r),
+        };
+        let lhs = lhs.trim();
+        let rhs = rhs.trim();
+
+        let (check, metric) = if let Some(m) = strip_call(lhs, "change") {
+            (Check::Change, m)
+        } else if let Some(m) = strip_call(lhs, "regression") {
+            (Check::Regression, m)
+        } else {
+            (Check::Value, lhs)
+        };
+        let metric: Metric = metric.parse()?;
+        if check == Check::Regression && matches!(metric, Metric::ErrorCount | Metric::ErrorsRatio)
+        {
+            return Err(format!(
+                "Significance of a regression cannot be computed for {metric}"
+            ));
+        }
+
+        let threshold = match check {
+            Check::Value if metric.is_latency() => parse_latency_ms(rhs),
+            Check::Change => rhs.trim_end_matches('%').trim().parse().ok(),
+            _ => rhs.parse().ok(),
+        };
+        let threshold = threshold.ok_or_else(|| format!("Invalid threshold value '{rhs}'"))?;
+
+        Ok(Assertion {
+            check,
+            metric,
+            op,
+            threshold,
+        })
+    }
+}
+
+/// If `s` has form `name(arg)`, returns the trimmed `arg`.
+fn strip_call<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+        .map(str::trim)
+}
+
+/// Parses a latency given as a duration with a unit or as a bare number of milliseconds.
+fn parse_latency_ms(s: &str) -> Option<f64> {
+    s.parse().ok().or_else(|| {
+        humantime::parse_duration(s)
+            .ok()
+            .map(|d| d.as_secs_f64() * 1000.0)
+    })
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.check {
+            Check::Value => write!(f, "{}", self.metric)?,
+            Check::Change => write!(f, "change({})", self.metric)?,
+            Check::Regression => write!(f, "regression({})", self.metric)?,
+        }
+        write!(f, " {} {}", self.op, self.threshold)?;
+        match self.check {
+            Check::Value if self.metric.is_latency() => write!(f, " ms"),
+            Check::Change => write!(f, "%"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_latency_assertion() {
+        let a: Assertion = "p99<20ms".parse().unwrap();
+        assert_eq!(a.check, Check::Value);
+        assert_eq!(a.metric, Metric::CycleLatency(99.0));
+        assert_eq!(a.op, CmpOp::Lt);
+        assert_eq!(a.threshold, 20.0);
+
+        let a: Assertion = "req_p99.9 <= 1.5s".parse().unwrap();
+        assert_eq!(a.metric, Metric::ReqLatency(99.9));
+        assert_eq!(a.op, CmpOp::Le);
+        assert_eq!(a.threshold, 1500.0);
+
+        let a: Assertion = "mean<2.5".parse().unwrap();
+        assert_eq!(a.metric, Metric::CycleLatencyMean);
+        assert_eq!(a.threshold, 2.5);
+    }
+
+    #[test]
+    fn parse_value_assertion() {
+        let a: Assertion = "cycle_throughput>50000".parse().unwrap();
+        assert_eq!(a.metric, Metric::CycleThroughput);
+        assert_eq!(a.op, CmpOp::Gt);
+        assert_eq!(a.threshold, 50000.0);
+
+        let a: Assertion = "errors_ratio<0.001".parse().unwrap();
+        assert_eq!(a.metric, Metric::ErrorsRatio);
+        assert_eq!(a.threshold, 0.001);
+    }
+
+    #[test]
+    fn parse_baseline_assertion() {
+        let a: Assertion = "regression(cycle_throughput)>=0.01".parse().unwrap();
+        assert_eq!(a.check, Check::Regression);
+        assert_eq!(a.metric, Metric::CycleThroughput);
+        assert_eq!(a.op, CmpOp::Ge);
+        assert!(a.needs_baseline());
+
+        let a: Assertion = "change(p99) < 10%".parse().unwrap();
+        assert_eq!(a.check, Check::Change);
+        assert_eq!(a.metric, Metric::CycleLatency(99.0));
+        assert_eq!(a.threshold, 10.0);
+    }
+
+    #[test]
+    fn reject_invalid_assertions() {
+        assert!("p99".parse::<Assertion>().is_err());
+        assert!("p142<10ms".parse::<Assertion>().is_err());
+        assert!("foo<10".parse::<Assertion>().is_err());
+        assert!("p99<fast".parse::<Assertion>().is_err());
+        assert!("regression(errors_ratio)>0.01"
+            .parse::<Assertion>()
+            .is_err());
+    }
+
+    #[test]
+    fn check_custom_percentiles() {
+        let a: Assertion = "p99.95<5ms".parse().unwrap();
+        assert_eq!(a.metric, Metric::CycleLatency(99.95));
+        assert!(a.check_percentile(&[50.0, 99.95]).is_ok());
+        assert!(a.check_percentile(&[50.0, 99.0]).is_err());
+        let a: Assertion = "req_p99<5ms".parse().unwrap();
+        assert!(a.check_percentile(&[]).is_ok());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in [
+            "p99.9 < 20 ms",
+            "regression(req_throughput) >= 0.01",
+            "change(max) <= 5%",
+        ] {
+            let a: Assertion = s.parse().unwrap();
+            assert_eq!(a.to_string(), s);
+        }
+    }
+}
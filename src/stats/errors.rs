@@ -0,0 +1,159 @@
+//! Classification of the database errors into categories stable across drivers.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Category of a failed request or of a failed validation of a response
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// The client or a replica timed out waiting for the response
+    Timeout,
+    /// Not enough replicas were alive to serve the request
+    Unavailable,
+    /// The coordinator was overloaded and rejected the request
+    Overloaded,
+    /// Replicas failed to apply the write
+    WriteFailure,
+    /// Replicas failed to serve the read
+    ReadFailure,
+    /// The request exceeded the rate or capacity limits set on the server
+    Throttling,
+    /// The response did not pass the validation done by the workload
+    Validation,
+    /// The request could not be sent or the connection broke
+    Connection,
+    /// Any other error
+    Other,
+}
+
+impl ErrorCategory {
+    pub const ALL: [ErrorCategory; 9] = [
+        ErrorCategory::Timeout,
+        ErrorCategory::Unavailable,
+        ErrorCategory::Overloaded,
+        ErrorCategory::WriteFailure,
+        ErrorCategory::ReadFailure,
+        ErrorCategory::Throttling,
+        ErrorCategory::Validation,
+        ErrorCategory::Connection,
+        ErrorCategory::Other,
+    ];
+
+    /// Returns the name used in the sample log columns
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCategory::Timeout => "timeout",
+            ErrorCategory::Unavailable => "unavailable",
+            ErrorCategory::Overloaded => "overloaded",
+            ErrorCategory::WriteFailure => "write_failure",
+            ErrorCategory::ReadFailure => "read_failure",
+            ErrorCategory::Throttling => "throttling",
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Connection => "connection",
+            ErrorCategory::Other => "other",
+        }
+    }
+}
+
+impl Display for ErrorCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            ErrorCategory::Timeout => "Timeout",
+            ErrorCategory::Unavailable => "Unavailable",
+            ErrorCategory::Overloaded => "Overloaded",
+            ErrorCategory::WriteFailure => "Write failure",
+            ErrorCategory::ReadFailure => "Read failure",
+            ErrorCategory::Throttling => "Throttling",
+            ErrorCategory::Validation => "Validation",
+            ErrorCategory::Connection => "Connection",
+            ErrorCategory::Other => "Other",
+        };
+        write!(f, "{label}")
+    }
+}
+
+impl FromStr for ErrorCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase().replace('-', "_");
+        ErrorCategory::ALL
+            .into_iter()
+            .find(|c| c.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown error category '{s}'. Expected one of: {}",
+                    ErrorCategory::ALL.map(|c| c.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// Number of errors of each category
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorCounts(BTreeMap<ErrorCategory, u64>);
+
+impl ErrorCounts {
+    pub fn record(&mut self, category: ErrorCategory) {
+        *self.0.entry(category).or_default() += 1;
+    }
+
+    pub fn add(&mut self, other: &ErrorCounts) {
+        for (category, count) in &other.0 {
+            *self.0.entry(*category).or_default() += count;
+        }
+    }
+
+    pub fn get(&self, category: ErrorCategory) -> u64 {
+        self.0.get(&category).copied().unwrap_or_default()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl<'a> FromIterator<&'a ErrorCounts> for ErrorCounts {
+    fn from_iter<T: IntoIterator<Item = &'a ErrorCounts>>(iter: T) -> Self {
+        let mut result = ErrorCounts::default();
+        for counts in iter {
+            result.add(counts);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_errors_by_category() {
+        let mut a = ErrorCounts::default();
+        a.record(ErrorCategory::Timeout);
+        a.record(ErrorCategory::Timeout);
+        let mut b = ErrorCounts::default();
+        b.record(ErrorCategory::Unavailable);
+        let sum: ErrorCounts = [&a, &b].into_iter().collect();
+        assert_eq!(sum.get(ErrorCategory::Timeout), 2);
+        assert_eq!(sum.get(ErrorCategory::Unavailable), 1);
+        assert_eq!(sum.get(ErrorCategory::Overloaded), 0);
+        assert_eq!(sum.total(), 3);
+        assert_eq!(
+            serde_json::to_string(&sum).unwrap(),
+            r#"{"timeout":2,"unavailable":1}"#
+        );
+    }
+}
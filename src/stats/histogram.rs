@@ -19,15 +19,33 @@ impl Serialize for SerializableHistogram {
     where
         S: serde::Serializer,
     {
-        let mut serialized_histogram = Vec::new();
-        V2DeflateSerializer::new()
-            .serialize(&self.0, &mut serialized_histogram)
-            .unwrap();
-        let encoded = base64_engine::STANDARD.encode(serialized_histogram);
-        serializer.serialize_str(encoded.as_str())
+        serialize_histogram(&self.0, serializer)
     }
 }
 
+/// Serializes a bare histogram the same way as [`SerializableHistogram`].
+/// To be used with `#[serde(serialize_with)]`.
+pub fn serialize_histogram<S>(histogram: &Histogram<u64>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut serialized_histogram = Vec::new();
+    V2DeflateSerializer::new()
+        .serialize(histogram, &mut serialized_histogram)
+        .unwrap();
+    let encoded = base64_engine::STANDARD.encode(serialized_histogram);
+    serializer.serialize_str(encoded.as_str())
+}
+
+/// Deserializes a bare histogram serialized by [`serialize_histogram`].
+/// To be used with `#[serde(deserialize_with)]`.
+pub fn deserialize_histogram<'de, D>(deserializer: D) -> Result<Histogram<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(deserializer.deserialize_str(HistogramVisitor)?.0)
+}
+
 struct HistogramVisitor;
 
 impl Visitor<'_> for HistogramVisitor {
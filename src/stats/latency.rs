@@ -1,10 +1,66 @@
-use crate::stats::histogram::SerializableHistogram;
-use crate::stats::percentiles::Percentiles;
+use crate::config::LatencyConf;
+use crate::stats::histogram::{deserialize_histogram, serialize_histogram, SerializableHistogram};
+use crate::stats::percentiles::{Percentile, Percentiles};
 use crate::stats::timeseries::TimeSeriesStats;
 use crate::stats::Mean;
 use hdrhistogram::Histogram;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
+use strum::IntoEnumIterator;
+
+/// Latency recording settings of the current run, shared by all recorders in the process.
+/// All histograms of a run must be created with the same settings, so they can be merged.
+static SETTINGS: Lazy<RwLock<Arc<LatencySettings>>> =
+    Lazy::new(|| RwLock::new(Arc::new(LatencySettings::new(LatencyConf::default()))));
+
+struct LatencySettings {
+    conf: LatencyConf,
+    /// Percentiles to compute.
+    /// Apart from the reported percentiles, includes all well-known percentiles,
+    /// because some outputs, e.g. the progress log, rely on them.
+    computed_percentiles: Vec<f64>,
+}
+
+impl LatencySettings {
+    fn new(conf: LatencyConf) -> LatencySettings {
+        let computed_percentiles = Percentile::iter()
+            .map(|p| p.value())
+            .chain(conf.percentiles())
+            .sorted_by(f64::total_cmp)
+            .dedup()
+            .collect();
+        LatencySettings {
+            conf,
+            computed_percentiles,
+        }
+    }
+}
+
+/// Sets up the latency histograms and the reported percentiles of the next run.
+/// Must be called before recording any latencies of the run.
+/// A process running many benchmarks one after another, e.g. an agent,
+/// configures the settings again for each of them.
+/// Unit tests must not call it, because it changes the settings of all tests running in parallel.
+pub fn configure(conf: &LatencyConf) {
+    *SETTINGS.write().unwrap() = Arc::new(LatencySettings::new(conf.clone()));
+}
+
+fn settings() -> Arc<LatencySettings> {
+    SETTINGS.read().unwrap().clone()
+}
+
+/// Returns the percentiles chosen to be reported
+pub fn reported_percentiles() -> Vec<f64> {
+    settings().conf.percentiles()
+}
+
+/// Returns the delay after which a cycle is considered to have started late
+pub fn late_threshold() -> Duration {
+    settings().conf.late_threshold
+}
 
 /// Captures latency mean and percentiles, with uncertainty estimates.
 #[derive(Serialize, Deserialize, Debug)]
@@ -14,18 +70,69 @@ pub struct LatencyDistribution {
     pub histogram: SerializableHistogram,
 }
 
+impl LatencyDistribution {
+    /// Merges the distributions of latencies recorded independently, e.g. by different clients.
+    /// The histograms are added up and the percentiles are computed from the sum.
+    /// The errors are estimated only if all distributions come with errors.
+    pub fn merge<'a>(
+        distributions: impl IntoIterator<Item = &'a LatencyDistribution>,
+    ) -> LatencyDistribution {
+        let distributions = distributions.into_iter().collect_vec();
+        let settings = settings();
+        let mut histogram = Histogram::new(settings.conf.histogram_precision).unwrap();
+        histogram.auto(true);
+        for d in &distributions {
+            histogram.add(&d.histogram.0).unwrap();
+        }
+
+        let scale = 1e-6;
+        let total_count = histogram.len() as f64;
+        let n = distributions.iter().map(|d| d.mean.n).sum();
+        let std_err = distributions
+            .iter()
+            .map(|d| {
+                let weight = d.histogram.0.len() as f64 / total_count;
+                d.mean.std_err.map(|e| (weight * e).powi(2))
+            })
+            .sum::<Option<f64>>()
+            .filter(|_| total_count > 0.0)
+            .map(f64::sqrt);
+        let percentiles = if std_err.is_some() {
+            Percentiles::compute_with_errors(&histogram, scale, n, &settings.computed_percentiles)
+        } else {
+            Percentiles::compute(&histogram, scale, &settings.computed_percentiles)
+        };
+        LatencyDistribution {
+            mean: Mean {
+                n,
+                value: histogram.mean() * scale,
+                std_err,
+            },
+            percentiles,
+            histogram: SerializableHistogram(histogram),
+        }
+    }
+}
+
 /// Builds TimeDistribution from a stream of durations.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LatencyDistributionRecorder {
+    #[serde(
+        serialize_with = "serialize_histogram",
+        deserialize_with = "deserialize_histogram"
+    )]
     histogram_ns: Histogram<u64>,
     ess_estimator: TimeSeriesStats,
 }
 
 impl LatencyDistributionRecorder {
     pub fn record(&mut self, time: Duration) {
-        self.histogram_ns
-            .record(time.as_nanos().clamp(1, u64::MAX as u128) as u64)
-            .unwrap();
+        let time_ns = time.as_nanos().clamp(1, u64::MAX as u128) as u64;
+        if self.histogram_ns.is_auto_resize() {
+            self.histogram_ns.record(time_ns).unwrap();
+        } else {
+            self.histogram_ns.saturating_record(time_ns);
+        }
         self.ess_estimator.record(time.as_secs_f64(), 1.0);
     }
 
@@ -42,7 +149,11 @@ impl LatencyDistributionRecorder {
     pub fn distribution(&self) -> LatencyDistribution {
         LatencyDistribution {
             mean: self.mean(1),
-            percentiles: Percentiles::compute(&self.histogram_ns, 1e-6),
+            percentiles: Percentiles::compute(
+                &self.histogram_ns,
+                1e-6,
+                &settings().computed_percentiles,
+            ),
             histogram: SerializableHistogram(self.histogram_ns.clone()),
         }
     }
@@ -51,7 +162,12 @@ impl LatencyDistributionRecorder {
         let ess = self.ess_estimator.effective_sample_size();
         LatencyDistribution {
             mean: self.mean(ess),
-            percentiles: Percentiles::compute_with_errors(&self.histogram_ns, 1e-6, ess),
+            percentiles: Percentiles::compute_with_errors(
+                &self.histogram_ns,
+                1e-6,
+                ess,
+                &settings().computed_percentiles,
+            ),
             histogram: SerializableHistogram(self.histogram_ns.clone()),
         }
     }
@@ -70,11 +186,48 @@ impl LatencyDistributionRecorder {
     }
 }
 
+/// Creates an empty recorder with the histogram set up as configured with [`configure`]
 impl Default for LatencyDistributionRecorder {
     fn default() -> Self {
+        let settings = settings();
+        let conf = &settings.conf;
+        let precision = conf.histogram_precision;
+        let min_ns = (conf.histogram_min.as_nanos() as u64).max(1);
+        let histogram_ns = match conf.histogram_max {
+            Some(max) => {
+                let max_ns = (max.as_nanos() as u64).max(2 * min_ns);
+                Histogram::new_with_bounds(min_ns, max_ns, precision).unwrap()
+            }
+            None => {
+                let mut histogram =
+                    Histogram::new_with_bounds(min_ns, 2 * min_ns, precision).unwrap();
+                histogram.auto(true);
+                histogram
+            }
+        };
         Self {
-            histogram_ns: Histogram::new(3).unwrap(),
+            histogram_ns,
             ess_estimator: Default::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn computed_percentiles_include_the_reported_ones() {
+        let conf = LatencyConf {
+            percentiles: vec![99.95, 50.0],
+            ..LatencyConf::default()
+        };
+        let settings = LatencySettings::new(conf);
+        assert_eq!(settings.conf.percentiles(), vec![50.0, 99.95]);
+        assert!(settings.computed_percentiles.contains(&99.95));
+        assert!(settings
+            .computed_percentiles
+            .contains(&Percentile::P99_9.value()));
+        assert!(settings.computed_percentiles.is_sorted());
+    }
+}
@@ -0,0 +1,320 @@
+//! Merging of the statistics of benchmarks run at the same time by independent clients.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Local};
+use itertools::Itertools;
+
+use crate::stats::latency::{reported_percentiles, LatencyDistribution};
+use crate::stats::{
+    not_nan, BenchmarkStats, FnCounts, Mean, Sample, StatementSummary, MAX_KEPT_ERRORS,
+};
+
+/// Returns the mean of the sum of independent variables
+fn sum(means: impl IntoIterator<Item = Mean>) -> Mean {
+    let means = means.into_iter().collect_vec();
+    Mean {
+        n: means.iter().map(|m| m.n).min().unwrap_or_default(),
+        value: means.iter().map(|m| m.value).sum(),
+        std_err: means
+            .iter()
+            .map(|m| m.std_err.map(|e| e * e))
+            .sum::<Option<f64>>()
+            .map(f64::sqrt),
+    }
+}
+
+/// Merges the latency distributions of the same functions, nodes or shards
+fn merge_by_fn<'a>(
+    distributions: impl IntoIterator<Item = &'a HashMap<String, LatencyDistribution>>,
+) -> HashMap<String, LatencyDistribution> {
+    let mut by_fn: HashMap<&str, Vec<&LatencyDistribution>> = HashMap::new();
+    for (name, distribution) in distributions.into_iter().flatten() {
+        by_fn.entry(name).or_default().push(distribution);
+    }
+    by_fn
+        .into_iter()
+        .map(|(name, d)| (name.to_string(), LatencyDistribution::merge(d)))
+        .collect()
+}
+
+/// Sums the counts of the same functions
+fn sum_by_fn<'a>(
+    counts: impl IntoIterator<Item = &'a HashMap<String, FnCounts>>,
+) -> HashMap<String, FnCounts> {
+    let mut by_fn: HashMap<String, FnCounts> = HashMap::new();
+    for (name, c) in counts.into_iter().flatten() {
+        by_fn.entry(name.clone()).or_default().add(c);
+    }
+    by_fn
+}
+
+/// Sums the requests of the same statements or batches and merges their response times
+fn merge_statements(
+    stats: &[&BenchmarkStats],
+    select: fn(&BenchmarkStats) -> &BTreeMap<String, StatementSummary>,
+) -> BTreeMap<String, StatementSummary> {
+    let mut by_key: BTreeMap<&str, Vec<&StatementSummary>> = BTreeMap::new();
+    for (key, summary) in stats.iter().flat_map(|s| select(s)) {
+        by_key.entry(key).or_default().push(summary);
+    }
+    by_key
+        .into_iter()
+        .map(|(key, summaries)| {
+            let latencies = summaries
+                .iter()
+                .filter_map(|s| s.latency.as_ref())
+                .collect_vec();
+            let summary = StatementSummary {
+                request_count: summaries.iter().map(|s| s.request_count).sum(),
+                error_count: summaries.iter().map(|s| s.error_count).sum(),
+                retry_count: summaries.iter().map(|s| s.retry_count).sum(),
+                row_count: summaries.iter().map(|s| s.row_count).sum(),
+                statement_count: summaries.iter().map(|s| s.statement_count).sum(),
+                latency: (!latencies.is_empty()).then(|| LatencyDistribution::merge(latencies)),
+            };
+            (key.to_string(), summary)
+        })
+        .collect()
+}
+
+/// Merges the distributions, unless any of them is missing
+fn merge_optional<'a>(
+    distributions: impl IntoIterator<Item = Option<&'a LatencyDistribution>>,
+) -> Option<LatencyDistribution> {
+    let distributions: Option<Vec<_>> = distributions.into_iter().collect();
+    distributions.map(LatencyDistribution::merge)
+}
+
+fn union<'a>(sets: impl IntoIterator<Item = &'a HashSet<String>>) -> HashSet<String> {
+    sets.into_iter().flatten().cloned().collect()
+}
+
+impl Sample {
+    /// Merges the samples recorded in the same period of time by independent clients
+    pub fn merge(time_s: f32, samples: &[&Sample]) -> Sample {
+        let count = samples.len() as f32;
+        Sample {
+            time_s,
+            duration_s: samples.iter().map(|s| s.duration_s).sum::<f32>() / count,
+            cycle_count: samples.iter().map(|s| s.cycle_count).sum(),
+            cycle_error_count: samples.iter().map(|s| s.cycle_error_count).sum(),
+            request_count: samples.iter().map(|s| s.request_count).sum(),
+            req_retry_errors: union(samples.iter().map(|s| &s.req_retry_errors)),
+            req_retry_count: samples.iter().map(|s| s.req_retry_count).sum(),
+            req_errors: union(samples.iter().map(|s| &s.req_errors)),
+            req_error_count: samples.iter().map(|s| s.req_error_count).sum(),
+            error_counts: samples.iter().map(|s| &s.error_counts).collect(),
+            row_count: samples.iter().map(|s| s.row_count).sum(),
+            mean_queue_len: samples.iter().map(|s| s.mean_queue_len).sum::<f32>() / count,
+            cycle_throughput: samples.iter().map(|s| s.cycle_throughput).sum(),
+            req_throughput: samples.iter().map(|s| s.req_throughput).sum(),
+            row_throughput: samples.iter().map(|s| s.row_throughput).sum(),
+            target_rate: samples.iter().map(|s| s.target_rate).sum(),
+            late_cycle_count: samples.iter().map(|s| s.late_cycle_count).sum(),
+            cycle_latency: LatencyDistribution::merge(samples.iter().map(|s| &s.cycle_latency)),
+            cycle_latency_by_fn: merge_by_fn(samples.iter().map(|s| &s.cycle_latency_by_fn)),
+            counts_by_fn: sum_by_fn(samples.iter().map(|s| &s.counts_by_fn)),
+            request_latency: LatencyDistribution::merge(samples.iter().map(|s| &s.request_latency)),
+            request_latency_by_node: merge_by_fn(
+                samples.iter().map(|s| &s.request_latency_by_node),
+            ),
+            request_latency_by_shard: merge_by_fn(
+                samples.iter().map(|s| &s.request_latency_by_shard),
+            ),
+            start_delay: merge_optional(samples.iter().map(|s| s.start_delay.as_ref())),
+        }
+    }
+}
+
+/// Aligns the samples of all runs by their wall-clock time and merges the samples
+/// falling into the same sampling period.
+/// The sampling period is estimated from the durations of the samples.
+/// At most one sample of each run falls into a period, so the throughput of a run is never
+/// counted twice, even if the times of its samples jitter around the period boundaries.
+fn merge_logs(stats: &[&BenchmarkStats], start_time: DateTime<Local>) -> Vec<Sample> {
+    let samples = stats.iter().flat_map(|s| &s.log).collect_vec();
+    if samples.is_empty() {
+        return Vec::new();
+    }
+    let period = samples.iter().map(|s| s.duration_s as f64).sum::<f64>() / samples.len() as f64;
+    let period = if period > 0.0 { period } else { 1.0 };
+
+    let mut groups: BTreeMap<i64, Vec<(f64, &Sample)>> = BTreeMap::new();
+    for s in stats {
+        let offset = (s.start_time - start_time).num_microseconds().unwrap_or(0) as f64 / 1e6;
+        let mut last_period_index = None;
+        for sample in &s.log {
+            let time = offset + sample.time_s as f64;
+            let period_index = match last_period_index {
+                Some(last) => ((time / period).round() as i64).max(last + 1),
+                None => (time / period).round() as i64,
+            };
+            last_period_index = Some(period_index);
+            groups.entry(period_index).or_default().push((time, sample));
+        }
+    }
+    groups
+        .into_values()
+        .map(|group| {
+            let time_s = group.iter().map(|(t, _)| *t).fold(f64::MAX, f64::min);
+            let samples = group.iter().map(|(_, s)| *s).collect_vec();
+            Sample::merge(time_s as f32, &samples)
+        })
+        .collect()
+}
+
+impl BenchmarkStats {
+    /// Merges the statistics of benchmarks run at the same time by independent clients.
+    /// Counts and throughputs are added up and latency histograms are merged.
+    /// The samples of the runs are aligned by their wall-clock time.
+    pub fn merge(stats: &[&BenchmarkStats]) -> BenchmarkStats {
+        assert!(!stats.is_empty());
+        let count = stats.len() as f64;
+        let start_time = stats.iter().map(|s| s.start_time).min().unwrap();
+        let end_time = stats.iter().map(|s| s.end_time).max().unwrap();
+        let elapsed_time_s = (end_time - start_time).num_microseconds().unwrap_or(0) as f64 / 1e6;
+
+        let cycle_count: u64 = stats.iter().map(|s| s.cycle_count).sum();
+        let error_count: u64 = stats.iter().map(|s| s.error_count).sum();
+        let request_count: u64 = stats.iter().map(|s| s.request_count).sum();
+        let request_retry_count: u64 = stats.iter().map(|s| s.request_retry_count).sum();
+        let row_count: u64 = stats.iter().map(|s| s.row_count).sum();
+
+        let cycle_throughput = sum(stats.iter().map(|s| s.cycle_throughput));
+        // The rate limits are not stored directly, but can be recovered from the ratios
+        let rate_limit = stats
+            .iter()
+            .map(|s| {
+                s.cycle_throughput_ratio
+                    .map(|r| 100.0 * s.cycle_throughput.value / r)
+            })
+            .sum::<Option<f64>>();
+        let concurrency = sum(stats.iter().map(|s| s.concurrency)) * (1.0 / count);
+
+        let request_latencies = stats
+            .iter()
+            .filter_map(|s| s.request_latency.as_ref())
+            .collect_vec();
+        let late_cycle_count: u64 = stats.iter().map(|s| s.late_cycle_count).sum();
+
+        BenchmarkStats {
+            start_time,
+            end_time,
+            elapsed_time_s,
+            cpu_time_s: stats.iter().map(|s| s.cpu_time_s).sum(),
+            cpu_util: stats.iter().map(|s| s.cpu_util).sum::<f64>() / count,
+            cycle_count,
+            request_count,
+            requests_per_cycle: request_count as f64 / cycle_count as f64,
+            request_retry_count,
+            request_retry_per_request: not_nan(request_retry_count as f64 / request_count as f64),
+            errors: stats
+                .iter()
+                .flat_map(|s| &s.errors)
+                .unique()
+                .take(MAX_KEPT_ERRORS)
+                .cloned()
+                .collect(),
+            error_count,
+            errors_ratio: not_nan(100.0 * error_count as f64 / cycle_count as f64),
+            error_counts: stats.iter().map(|s| &s.error_counts).collect(),
+            row_count,
+            row_count_per_req: not_nan(row_count as f64 / request_count as f64),
+            cycle_throughput,
+            cycle_throughput_ratio: rate_limit.map(|r| 100.0 * cycle_throughput.value / r),
+            req_throughput: sum(stats.iter().map(|s| s.req_throughput)),
+            row_throughput: sum(stats.iter().map(|s| s.row_throughput)),
+            cycle_latency: LatencyDistribution::merge(stats.iter().map(|s| &s.cycle_latency)),
+            cycle_latency_by_fn: merge_by_fn(stats.iter().map(|s| &s.cycle_latency_by_fn)),
+            counts_by_fn: sum_by_fn(stats.iter().map(|s| &s.counts_by_fn)),
+            request_latency: if request_latencies.is_empty() {
+                None
+            } else {
+                Some(LatencyDistribution::merge(request_latencies))
+            },
+            statements: merge_statements(stats, |s| &s.statements),
+            batches: merge_statements(stats, |s| &s.batches),
+            nodes: merge_statements(stats, |s| &s.nodes),
+            shards: merge_statements(stats, |s| &s.shards),
+            start_delay: merge_optional(stats.iter().map(|s| s.start_delay.as_ref())),
+            late_cycle_count,
+            late_cycle_ratio: not_nan(100.0 * late_cycle_count as f64 / cycle_count as f64),
+            percentiles: reported_percentiles(),
+            concurrency,
+            concurrency_ratio: stats.iter().map(|s| s.concurrency_ratio).sum::<f64>() / count,
+            log: merge_logs(stats, start_time),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::workload::{FnRef, FnStats, WorkloadStats};
+    use crate::stats::session::SessionStats;
+    use crate::stats::{Recorder, SampleWriters};
+    use std::num::NonZeroUsize;
+    use std::time::{Duration, Instant};
+
+    fn stats(latency_ms: u64, sample_count: u64) -> BenchmarkStats {
+        let mut writers = SampleWriters::default();
+        let mut recorder = Recorder::start(None, NonZeroUsize::MIN, true, &mut writers);
+        let start_time = Instant::now();
+        for i in 0..sample_count {
+            let mut fn_stats = FnStats::new(FnRef::new("read"));
+            for _ in 0..100 {
+                fn_stats.operation_completed(Duration::from_millis(latency_ms));
+            }
+            recorder.record(&[WorkloadStats {
+                start_time: start_time + Duration::from_secs(i),
+                end_time: start_time + Duration::from_secs(i + 1),
+                function_stats: vec![fn_stats],
+                session_stats: SessionStats::new(),
+            }]);
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn merge_stats_of_clients() {
+        let a = stats(1, 3);
+        let mut b = stats(100, 2);
+        b.start_time = a.start_time;
+        let merged = BenchmarkStats::merge(&[&a, &b]);
+
+        assert_eq!(merged.cycle_count, 500);
+        assert_eq!(merged.cycle_latency.histogram.0.len(), 500);
+        assert_eq!(merged.cycle_latency_by_fn["read"].histogram.0.len(), 500);
+        assert!((merged.cycle_latency.mean.value - 40.6).abs() < 0.1);
+        assert!(merged.cycle_latency.mean.std_err.is_some());
+        assert_eq!(
+            merged.cycle_throughput.value,
+            a.cycle_throughput.value + b.cycle_throughput.value
+        );
+
+        assert_eq!(merged.log.len(), 3);
+        assert_eq!(merged.log[0].cycle_count, 200);
+        assert_eq!(merged.log[2].cycle_count, 100);
+        assert_eq!(merged.log[0].cycle_latency.histogram.0.len(), 200);
+        assert_eq!(
+            merged.log[0].cycle_throughput,
+            a.log[0].cycle_throughput + b.log[0].cycle_throughput
+        );
+    }
+    #[test]
+    fn one_sample_of_each_client_per_period() {
+        let mut a = stats(1, 3);
+        let mut b = stats(100, 3);
+        b.start_time = a.start_time;
+        // Samples 1 and 2 of the first client both round to the period 1
+        a.log[1].time_s = 0.6;
+        a.log[2].time_s = 1.4;
+        let merged = BenchmarkStats::merge(&[&a, &b]);
+
+        assert_eq!(merged.log.len(), 3);
+        for sample in &merged.log {
+            assert_eq!(sample.cycle_count, 200);
+        }
+    }
+}
@@ -1,23 +1,33 @@
 use chrono::{DateTime, Local};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZeroUsize;
 use std::ops::Mul;
 use std::time::{Duration, Instant, SystemTime};
 
-use crate::exec::workload::WorkloadStats;
-use crate::stats::latency::{LatencyDistribution, LatencyDistributionRecorder};
+use crate::config::RateProfile;
+use crate::exec::workload::{FnStats, WorkloadStats};
+use crate::stats::errors::ErrorCounts;
+use crate::stats::latency::{
+    reported_percentiles, LatencyDistribution, LatencyDistributionRecorder,
+};
+use crate::stats::percentiles::default_reported_percentiles;
+use crate::stats::session::StatementStats;
 use cpu_time::ProcessTime;
 use hdrhistogram::serialization::interval_log;
-use percentiles::Percentile;
 use serde::{Deserialize, Serialize};
 use throughput::ThroughputMeter;
 use timeseries::TimeSeriesStats;
 
 use crate::stats::histogram::HistogramWriter;
+use crate::stats::sample_log::SampleWriter;
 
+pub mod assertion;
+pub mod errors;
 pub mod histogram;
 pub mod latency;
+pub mod merge;
 pub mod percentiles;
+pub mod sample_log;
 pub mod session;
 pub mod throughput;
 pub mod timeseries;
@@ -224,15 +234,36 @@ pub struct Sample {
     pub req_retry_count: u64,
     pub req_errors: HashSet<String>,
     pub req_error_count: u64,
+    /// Number of failed attempts of requests and validations in each error category
+    #[serde(default)]
+    pub error_counts: ErrorCounts,
     pub row_count: u64,
     pub mean_queue_len: f32,
     pub cycle_throughput: f32,
     pub req_throughput: f32,
     pub row_throughput: f32,
+    /// Cycle throughput the rate limiter aimed at, if the rate was limited
+    #[serde(default)]
+    pub target_rate: Option<f32>,
+    /// Number of cycles started later than the late threshold after their scheduled time
+    #[serde(default)]
+    pub late_cycle_count: u64,
 
     pub cycle_latency: LatencyDistribution,
     pub cycle_latency_by_fn: HashMap<String, LatencyDistribution>,
+    /// Calls, errors and requests of each function
+    #[serde(default)]
+    pub counts_by_fn: HashMap<String, FnCounts>,
     pub request_latency: LatencyDistribution,
+    /// Latency of the requests served by each coordinator node
+    #[serde(default)]
+    pub request_latency_by_node: HashMap<String, LatencyDistribution>,
+    /// Latency of the requests served by each shard, if recorded
+    #[serde(default)]
+    pub request_latency_by_shard: HashMap<String, LatencyDistribution>,
+    /// Delays between the scheduled and the actual start times of the cycles
+    #[serde(default)]
+    pub start_delay: Option<LatencyDistribution>,
 }
 
 impl Sample {
@@ -247,12 +278,18 @@ impl Sample {
         let mut row_count = 0;
         let mut errors = HashSet::new();
         let mut req_error_count = 0;
+        let mut error_counts = ErrorCounts::default();
         let mut mean_queue_len = 0.0;
         let mut duration_s = 0.0;
 
         let mut request_latency = LatencyDistributionRecorder::default();
+        let mut request_latency_by_node = HashMap::<String, LatencyDistributionRecorder>::new();
+        let mut request_latency_by_shard = HashMap::<String, LatencyDistributionRecorder>::new();
         let mut cycle_latency = LatencyDistributionRecorder::default();
         let mut cycle_latency_per_fn = HashMap::<String, LatencyDistributionRecorder>::new();
+        let mut counts_by_fn = HashMap::<String, FnCounts>::new();
+        let mut start_delay = LatencyDistributionRecorder::default();
+        let mut late_cycle_count = 0;
 
         for s in stats {
             let ss = &s.session_stats;
@@ -263,19 +300,38 @@ impl Sample {
             }
             req_retry_errors.extend(ss.req_retry_errors.iter().cloned());
             req_error_count += ss.req_error_count;
+            error_counts.add(&ss.error_counts);
             req_retry_count += ss.req_retry_count;
             mean_queue_len += ss.mean_queue_length / stats.len() as f32;
             duration_s += (s.end_time - s.start_time).as_secs_f32() / stats.len() as f32;
             request_latency.add(&ss.resp_times_ns);
+            for (node, stats) in &ss.nodes {
+                request_latency_by_node
+                    .entry(node.clone())
+                    .or_default()
+                    .add(&stats.resp_times_ns);
+            }
+            for (shard, stats) in &ss.shards {
+                request_latency_by_shard
+                    .entry(shard.clone())
+                    .or_default()
+                    .add(&stats.resp_times_ns);
+            }
 
             for fs in &s.function_stats {
                 cycle_count += fs.call_count;
-                cycle_error_count = fs.error_count;
+                cycle_error_count += fs.error_count;
                 cycle_latency.add(&fs.call_latency);
                 cycle_latency_per_fn
                     .entry(fs.function.name.clone())
                     .or_default()
                     .add(&fs.call_latency);
+                start_delay.add(&fs.start_delay);
+                late_cycle_count += fs.late_count;
+                counts_by_fn
+                    .entry(fs.function.name.clone())
+                    .or_default()
+                    .add(&FnCounts::from(fs));
             }
         }
 
@@ -289,24 +345,106 @@ impl Sample {
             req_retry_count,
             req_errors: errors,
             req_error_count,
+            error_counts,
             row_count,
             mean_queue_len: not_nan_f32(mean_queue_len).unwrap_or(0.0),
 
             cycle_throughput: cycle_count as f32 / duration_s,
             req_throughput: request_count as f32 / duration_s,
             row_throughput: row_count as f32 / duration_s,
+            target_rate: None,
+            late_cycle_count,
 
             cycle_latency: cycle_latency.distribution(),
             cycle_latency_by_fn: cycle_latency_per_fn
                 .into_iter()
                 .map(|(k, v)| (k, v.distribution()))
                 .collect(),
+            counts_by_fn,
 
             request_latency: request_latency.distribution(),
+            request_latency_by_node: request_latency_by_node
+                .into_iter()
+                .map(|(k, v)| (k, v.distribution()))
+                .collect(),
+            request_latency_by_shard: request_latency_by_shard
+                .into_iter()
+                .map(|(k, v)| (k, v.distribution()))
+                .collect(),
+            start_delay: Some(start_delay.distribution()),
+        }
+    }
+}
+
+/// Counts of the calls of a single workload function and of the requests made by them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FnCounts {
+    pub call_count: u64,
+    pub error_count: u64,
+    pub request_count: u64,
+    pub retry_count: u64,
+    pub row_count: u64,
+}
+
+impl FnCounts {
+    pub fn add(&mut self, other: &FnCounts) {
+        self.call_count += other.call_count;
+        self.error_count += other.error_count;
+        self.request_count += other.request_count;
+        self.retry_count += other.retry_count;
+        self.row_count += other.row_count;
+    }
+
+    /// Returns the percentage of the failed calls
+    pub fn error_ratio(&self) -> Option<f64> {
+        not_nan(100.0 * self.error_count as f64 / self.call_count as f64)
+    }
+}
+
+impl From<&FnStats> for FnCounts {
+    fn from(fs: &FnStats) -> Self {
+        FnCounts {
+            call_count: fs.call_count,
+            error_count: fs.error_count,
+            request_count: fs.req_count,
+            retry_count: fs.req_retry_count,
+            row_count: fs.row_count,
         }
     }
 }
 
+/// Statistics of the requests made with a single statement during the whole run
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StatementSummary {
+    pub request_count: u64,
+    pub error_count: u64,
+    pub retry_count: u64,
+    pub row_count: u64,
+    /// Number of statements in the successful batches
+    #[serde(default)]
+    pub statement_count: u64,
+    /// Response times of the successful requests; not set if there were none
+    pub latency: Option<LatencyDistribution>,
+}
+
+impl StatementSummary {
+    fn new(stats: StatementStats) -> StatementSummary {
+        StatementSummary {
+            request_count: stats.req_count,
+            error_count: stats.req_error_count,
+            retry_count: stats.req_retry_count,
+            row_count: stats.row_count,
+            statement_count: stats.statement_count,
+            latency: (stats.req_count > 0).then(|| stats.resp_times_ns.distribution_with_errors()),
+        }
+    }
+
+    /// Returns the mean number of statements in a successful batch
+    pub fn mean_size(&self) -> Option<f64> {
+        not_nan(self.statement_count as f64 / self.request_count as f64)
+    }
+}
+
 /// Stores the final statistics of the test run.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BenchmarkStats {
@@ -323,6 +461,9 @@ pub struct BenchmarkStats {
     pub errors: Vec<String>,
     pub error_count: u64,
     pub errors_ratio: Option<f64>,
+    /// Number of failed attempts of requests and validations in each error category
+    #[serde(default)]
+    pub error_counts: ErrorCounts,
     pub row_count: u64,
     pub row_count_per_req: Option<f64>,
     pub cycle_throughput: Mean,
@@ -331,12 +472,46 @@ pub struct BenchmarkStats {
     pub row_throughput: Mean,
     pub cycle_latency: LatencyDistribution,
     pub cycle_latency_by_fn: HashMap<String, LatencyDistribution>,
+    /// Calls, errors and requests of each function
+    #[serde(default)]
+    pub counts_by_fn: HashMap<String, FnCounts>,
     pub request_latency: Option<LatencyDistribution>,
+    /// Requests of each statement, keyed by the prepared statement key or the operation name
+    #[serde(default)]
+    pub statements: BTreeMap<String, StatementSummary>,
+    /// Batches, keyed by the batch type and the statements they contain
+    #[serde(default)]
+    pub batches: BTreeMap<String, StatementSummary>,
+    /// Successful requests served by each coordinator node, keyed by the node address
+    #[serde(default)]
+    pub nodes: BTreeMap<String, StatementSummary>,
+    /// Successful requests served by each shard, keyed by the node address and the shard number
+    #[serde(default)]
+    pub shards: BTreeMap<String, StatementSummary>,
+    /// Delays between the scheduled and the actual start times of the cycles
+    #[serde(default)]
+    pub start_delay: Option<LatencyDistribution>,
+    /// Number of cycles started later than the late threshold after their scheduled time
+    #[serde(default)]
+    pub late_cycle_count: u64,
+    #[serde(default)]
+    pub late_cycle_ratio: Option<f64>,
+    /// Latency percentiles chosen to be reported
+    #[serde(default = "default_reported_percentiles")]
+    pub percentiles: Vec<f64>,
     pub concurrency: Mean,
     pub concurrency_ratio: f64,
     pub log: Vec<Sample>,
 }
 
+impl BenchmarkStats {
+    /// Returns the mean number of calls of the function per second
+    pub fn fn_throughput(&self, function: &str) -> Option<f64> {
+        let counts = self.counts_by_fn.get(function)?;
+        not_nan(self.cycle_throughput.value * counts.call_count as f64 / self.cycle_count as f64)
+    }
+}
+
 /// Stores the statistics of one or two test runs.
 /// If the second run is given, enables comparisons between the runs.
 pub struct BenchmarkCmp<'a> {
@@ -389,9 +564,55 @@ impl BenchmarkCmp<'_> {
     }
 
     // Checks corresponding response time percentiles of two benchmark runs
-    // are statistically different. Returns None if the second benchmark is unset.
-    pub fn cmp_resp_time_percentile(&self, p: Percentile) -> Option<Significance> {
-        self.cmp(|s| s.request_latency.as_ref().map(|r| r.percentiles.get(p)))
+    // are statistically different. Returns None if the second benchmark is unset
+    // or if the percentile has not been computed in any of the runs.
+    pub fn cmp_resp_time_percentile(&self, p: f64) -> Option<Significance> {
+        self.cmp(|s| {
+            s.request_latency
+                .as_ref()
+                .and_then(|r| r.percentiles.find(p))
+        })
+    }
+}
+
+/// Stores the statistics of any number of test runs.
+/// Each run is compared to the reference run.
+/// Runs that lack the compared statistics, e.g. a stage missing in some runs, are set to `None`.
+pub struct BenchmarkMultiCmp<'a> {
+    pub runs: Vec<Option<&'a BenchmarkStats>>,
+    pub reference: usize,
+}
+
+impl<'a> BenchmarkMultiCmp<'a> {
+    /// Returns the comparison of the run at given index to the reference run.
+    /// Returns None for the reference run itself and for the missing runs.
+    pub fn cmp_to_reference(&self, index: usize) -> Option<BenchmarkCmp<'a>> {
+        if index == self.reference {
+            return None;
+        }
+        let v1 = self.runs[index]?;
+        let v2 = self.runs[self.reference]?;
+        Some(BenchmarkCmp { v1, v2: Some(v2) })
+    }
+
+    /// Applies the significance test `f` to each run and the reference run.
+    pub fn significance<F>(&self, f: F) -> Vec<Option<Significance>>
+    where
+        F: Fn(&BenchmarkCmp<'a>) -> Option<Significance>,
+    {
+        (0..self.runs.len())
+            .map(|i| self.cmp_to_reference(i).and_then(|cmp| f(&cmp)))
+            .collect()
+    }
+}
+
+/// Places the second run, if given, first, as the reference.
+impl<'a> From<&BenchmarkCmp<'a>> for BenchmarkMultiCmp<'a> {
+    fn from(cmp: &BenchmarkCmp<'a>) -> Self {
+        BenchmarkMultiCmp {
+            runs: cmp.v2.into_iter().chain([cmp.v1]).map(Some).collect(),
+            reference: 0,
+        }
     }
 }
 
@@ -412,17 +633,33 @@ pub struct Recorder<'a> {
     pub request_error_count: u64,
     pub throughput_meter: ThroughputMeter,
     pub errors: HashSet<String>,
+    pub error_counts: ErrorCounts,
     pub cycle_error_count: u64,
     pub row_count: u64,
     pub cycle_latency: LatencyDistributionRecorder,
     pub cycle_latency_by_fn: HashMap<String, LatencyDistributionRecorder>,
+    pub counts_by_fn: HashMap<String, FnCounts>,
     pub request_latency: LatencyDistributionRecorder,
+    pub statements: HashMap<String, StatementStats>,
+    pub batches: HashMap<String, StatementStats>,
+    pub nodes: HashMap<String, StatementStats>,
+    pub shards: HashMap<String, StatementStats>,
+    pub start_delay: LatencyDistributionRecorder,
+    pub late_cycle_count: u64,
     pub concurrency_meter: TimeSeriesStats,
     log: Vec<Sample>,
     rate_limit: Option<f64>,
+    rate_profile: Option<RateProfile>,
     concurrency_limit: NonZeroUsize,
     keep_log: bool,
-    hdrh_writer: &'a mut Option<Box<dyn HistogramWriter>>,
+    writers: &'a mut SampleWriters,
+}
+
+/// Destinations the data of each sample are written to as soon as the sample is recorded
+#[derive(Default)]
+pub struct SampleWriters {
+    pub hdrh_writer: Option<Box<dyn HistogramWriter>>,
+    pub sample_writer: Option<Box<dyn SampleWriter>>,
 }
 
 impl Recorder<'_> {
@@ -433,7 +670,7 @@ impl Recorder<'_> {
         rate_limit: Option<f64>,
         concurrency_limit: NonZeroUsize,
         keep_log: bool,
-        hdrh_writer: &mut Option<Box<dyn HistogramWriter>>,
+        writers: &mut SampleWriters,
     ) -> Recorder<'_> {
         let start_time = SystemTime::now();
         let start_instant = Instant::now();
@@ -446,6 +683,7 @@ impl Recorder<'_> {
             end_cpu_time: ProcessTime::now(),
             log: Vec::new(),
             rate_limit,
+            rate_profile: None,
             concurrency_limit,
             cycle_count: 0,
             request_count: 0,
@@ -453,32 +691,63 @@ impl Recorder<'_> {
             request_error_count: 0,
             row_count: 0,
             errors: HashSet::new(),
+            error_counts: ErrorCounts::default(),
             cycle_error_count: 0,
             cycle_latency: LatencyDistributionRecorder::default(),
             cycle_latency_by_fn: HashMap::new(),
+            counts_by_fn: HashMap::new(),
             request_latency: LatencyDistributionRecorder::default(),
+            statements: HashMap::new(),
+            batches: HashMap::new(),
+            nodes: HashMap::new(),
+            shards: HashMap::new(),
+            start_delay: LatencyDistributionRecorder::default(),
+            late_cycle_count: 0,
             throughput_meter: ThroughputMeter::default(),
             concurrency_meter: TimeSeriesStats::default(),
             keep_log,
-            hdrh_writer,
+            writers,
         }
     }
 
+    /// Sets the rate changing in time that the rate limiter aims at.
+    /// The target rate is recorded in each sample.
+    pub fn with_rate_profile(mut self, rate_profile: Option<RateProfile>) -> Self {
+        self.rate_profile = rate_profile;
+        self
+    }
+
     /// Adds the statistics of the completed request to the already collected statistics.
     /// Called on completion of each sample.
     pub fn record(&mut self, workload_stats: &[WorkloadStats]) -> &Sample {
         assert!(!workload_stats.is_empty());
         let mut current_sample_latency_recorder: Option<
             HashMap<String, LatencyDistributionRecorder>,
-        > = if self.hdrh_writer.is_some() {
+        > = if self.writers.hdrh_writer.is_some() {
             Some(HashMap::new())
         } else {
             None
         };
         for s in workload_stats.iter() {
             self.request_latency.add(&s.session_stats.resp_times_ns);
+            for (key, statement) in &s.session_stats.statements {
+                self.statements
+                    .entry(key.clone())
+                    .or_default()
+                    .add(statement);
+            }
+            for (key, batch) in &s.session_stats.batches {
+                self.batches.entry(key.clone()).or_default().add(batch);
+            }
+            for (key, node) in &s.session_stats.nodes {
+                self.nodes.entry(key.clone()).or_default().add(node);
+            }
+            for (key, shard) in &s.session_stats.shards {
+                self.shards.entry(key.clone()).or_default().add(shard);
+            }
             for fs in &s.function_stats {
                 self.cycle_latency.add(&fs.call_latency);
+                self.start_delay.add(&fs.start_delay);
                 self.cycle_latency_by_fn
                     .entry(fs.function.name.clone())
                     .or_default()
@@ -491,13 +760,27 @@ impl Recorder<'_> {
                 }
             }
         }
-        let sample = Sample::new(self.start_instant, workload_stats);
+        let mut sample = Sample::new(self.start_instant, workload_stats);
+        sample.target_rate = match &self.rate_profile {
+            Some(profile) => {
+                Some(profile.rate_at((sample.time_s + sample.duration_s / 2.0) as f64) as f32)
+            }
+            None => self.rate_limit.map(|r| r as f32),
+        };
         self.cycle_count += sample.cycle_count;
         self.cycle_error_count += sample.cycle_error_count;
+        self.late_cycle_count += sample.late_cycle_count;
         self.request_count += sample.request_count;
         self.request_retry_count += sample.req_retry_count;
         self.request_error_count += sample.req_error_count;
+        self.error_counts.add(&sample.error_counts);
         self.row_count += sample.row_count;
+        for (name, counts) in &sample.counts_by_fn {
+            self.counts_by_fn
+                .entry(name.clone())
+                .or_default()
+                .add(counts);
+        }
         self.throughput_meter.record(sample.cycle_count);
         self.concurrency_meter
             .record(sample.mean_queue_len as f64, sample.duration_s as f64);
@@ -509,7 +792,7 @@ impl Recorder<'_> {
         }
 
         // Write HDR histogram data
-        if let Some(hdrh_writer) = self.hdrh_writer {
+        if let Some(hdrh_writer) = &mut self.writers.hdrh_writer {
             if let Some(ref recorder) = current_sample_latency_recorder {
                 let interval_start_time = Duration::from_millis((sample.time_s * 1000.0) as u64);
                 let interval_duration = Duration::from_millis((sample.duration_s * 1000.0) as u64);
@@ -526,6 +809,13 @@ impl Recorder<'_> {
             }
         }
 
+        if let Some(sample_writer) = &mut self.writers.sample_writer {
+            if let Err(e) = sample_writer.write_sample(&sample) {
+                eprintln!("error: Failed to write sample log: {e}");
+                self.writers.sample_writer = None;
+            }
+        }
+
         self.log.push(sample);
         self.log.last().unwrap()
     }
@@ -565,6 +855,7 @@ impl Recorder<'_> {
             errors: self.errors.into_iter().collect(),
             error_count: self.cycle_error_count,
             errors_ratio: not_nan(100.0 * self.cycle_error_count as f64 / self.cycle_count as f64),
+            error_counts: self.error_counts,
             request_count: self.request_count,
             request_retry_count: self.request_retry_count,
             request_retry_per_request: not_nan(
@@ -583,11 +874,38 @@ impl Recorder<'_> {
                 .into_iter()
                 .map(|(k, v)| (k, v.distribution_with_errors()))
                 .collect(),
+            counts_by_fn: self.counts_by_fn,
             request_latency: if self.request_count > 0 {
                 Some(self.request_latency.distribution_with_errors())
             } else {
                 None
             },
+            statements: self
+                .statements
+                .into_iter()
+                .map(|(key, s)| (key, StatementSummary::new(s)))
+                .collect(),
+            batches: self
+                .batches
+                .into_iter()
+                .map(|(key, s)| (key, StatementSummary::new(s)))
+                .collect(),
+            nodes: self
+                .nodes
+                .into_iter()
+                .map(|(key, s)| (key, StatementSummary::new(s)))
+                .collect(),
+            shards: self
+                .shards
+                .into_iter()
+                .map(|(key, s)| (key, StatementSummary::new(s)))
+                .collect(),
+            start_delay: Some(self.start_delay.distribution_with_errors()),
+            late_cycle_count: self.late_cycle_count,
+            late_cycle_ratio: not_nan(
+                100.0 * self.late_cycle_count as f64 / self.cycle_count as f64,
+            ),
+            percentiles: reported_percentiles(),
             concurrency,
             concurrency_ratio,
             log: self.log,
@@ -597,7 +915,9 @@ impl Recorder<'_> {
 
 #[cfg(test)]
 mod test {
-    use crate::stats::{ln_gamma, regularized_incomplete_beta, students_t_cdf, t_test, Mean};
+    use super::*;
+    use crate::exec::workload::{FnRef, FnStats};
+    use crate::stats::session::{CallStats, SessionStats};
 
     #[test]
     fn ln_gamma_known_values() {
@@ -806,4 +1126,78 @@ mod test {
             );
         }
     }
+
+    fn workload_stats(start_time: Instant, start_s: u64) -> WorkloadStats {
+        WorkloadStats {
+            start_time: start_time + Duration::from_secs(start_s),
+            end_time: start_time + Duration::from_secs(start_s + 2),
+            function_stats: vec![FnStats::new(FnRef::new("read"))],
+            session_stats: SessionStats::new(),
+        }
+    }
+
+    #[test]
+    fn records_target_rate_in_samples() {
+        let mut writers = SampleWriters::default();
+        let profile: RateProfile = "0,100\n10,1100".parse().unwrap();
+        let mut recorder = Recorder::start(None, NonZeroUsize::MIN, true, &mut writers)
+            .with_rate_profile(Some(profile));
+        let start = recorder.start_instant;
+        let sample = recorder.record(&[workload_stats(start, 4)]);
+        // The rate in the middle of the sample
+        assert_eq!(sample.target_rate, Some(600.0));
+
+        let mut writers = SampleWriters::default();
+        let mut recorder = Recorder::start(Some(50.0), NonZeroUsize::MIN, true, &mut writers);
+        let start = recorder.start_instant;
+        let sample = recorder.record(&[workload_stats(start, 0)]);
+        assert_eq!(sample.target_rate, Some(50.0));
+    }
+
+    #[test]
+    fn counts_late_cycles() {
+        let mut writers = SampleWriters::default();
+        let mut recorder = Recorder::start(None, NonZeroUsize::MIN, true, &mut writers);
+        let start = recorder.start_instant;
+        let mut stats = workload_stats(start, 0);
+        for delay_ms in [1, 2, 3, 50, 100] {
+            stats.function_stats[0].operation_started(Duration::from_millis(delay_ms));
+            stats.function_stats[0].operation_completed(Duration::from_millis(delay_ms + 1));
+        }
+        let sample = recorder.record(&[stats]);
+        assert_eq!(sample.late_cycle_count, 2);
+        let delay = sample.start_delay.as_ref().unwrap();
+        assert!((delay.mean.value - 31.2).abs() < 0.1);
+
+        let stats = recorder.finish();
+        assert_eq!(stats.late_cycle_count, 2);
+        assert_eq!(stats.late_cycle_ratio, Some(40.0));
+    }
+
+    #[test]
+    fn counts_calls_of_each_function() {
+        let mut writers = SampleWriters::default();
+        let mut recorder = Recorder::start(None, NonZeroUsize::MIN, true, &mut writers);
+        let start = recorder.start_instant;
+        let mut stats = workload_stats(start, 0);
+        let mut write = FnStats::new(FnRef::new("write"));
+        write.operation_failed(Duration::from_millis(1));
+        write.requests_completed(CallStats {
+            req_count: 3,
+            req_retry_count: 2,
+            row_count: 1,
+        });
+        stats.function_stats[0].operation_failed(Duration::from_millis(1));
+        stats.function_stats[0].operation_completed(Duration::from_millis(1));
+        stats.function_stats.push(write);
+        let sample = recorder.record(&[stats]);
+        assert_eq!(sample.cycle_error_count, 2);
+        assert_eq!(sample.counts_by_fn["read"].error_count, 1);
+        assert_eq!(sample.counts_by_fn["read"].call_count, 2);
+
+        let stats = recorder.finish();
+        let write = &stats.counts_by_fn["write"];
+        assert_eq!((write.request_count, write.retry_count), (3, 2));
+        assert_eq!(write.error_ratio(), Some(100.0));
+    }
 }
@@ -1,9 +1,10 @@
 use crate::stats::Mean;
 use hdrhistogram::Histogram;
+use itertools::Itertools;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use strum::{EnumCount, EnumIter, IntoEnumIterator};
+use strum::{EnumCount, EnumIter};
 
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, EnumIter, EnumCount)]
@@ -22,6 +23,8 @@ pub enum Percentile {
     P99,
     P99_9,
     P99_99,
+    P99_999,
+    P99_9999,
     Max,
 }
 
@@ -42,53 +45,128 @@ impl Percentile {
             Percentile::P99 => 99.0,
             Percentile::P99_9 => 99.9,
             Percentile::P99_99 => 99.99,
+            Percentile::P99_999 => 99.999,
+            Percentile::P99_9999 => 99.9999,
             Percentile::Max => 100.0,
         }
     }
+}
 
-    pub fn name(&self) -> &'static str {
-        match self {
-            Percentile::Min => "  Min   ",
-            Percentile::P1 => "    1   ",
-            Percentile::P2 => "    2   ",
-            Percentile::P5 => "    5   ",
-            Percentile::P10 => "   10   ",
-            Percentile::P25 => "   25   ",
-            Percentile::P50 => "   50   ",
-            Percentile::P75 => "   75   ",
-            Percentile::P90 => "   90   ",
-            Percentile::P95 => "   95   ",
-            Percentile::P98 => "   98   ",
-            Percentile::P99 => "   99   ",
-            Percentile::P99_9 => "   99.9 ",
-            Percentile::P99_99 => "  99.99",
-            Percentile::Max => "  Max   ",
-        }
+/// Percentiles reported if not configured otherwise
+pub const DEFAULT_PERCENTILES: [Percentile; 13] = [
+    Percentile::Min,
+    Percentile::P25,
+    Percentile::P50,
+    Percentile::P75,
+    Percentile::P90,
+    Percentile::P95,
+    Percentile::P98,
+    Percentile::P99,
+    Percentile::P99_9,
+    Percentile::P99_99,
+    Percentile::P99_999,
+    Percentile::P99_9999,
+    Percentile::Max,
+];
+
+/// Percentiles stored in reports created before the set of percentiles became configurable,
+/// in the order they were stored. Used to tell which percentile each stored value belongs to,
+/// because these reports don't store the percentiles along with the values.
+/// Unlike [`default_reported_percentiles`], includes the percentiles that were stored
+/// but not displayed.
+const STORED_PERCENTILE_ORDER: [f64; 15] = [
+    0.0, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 98.0, 99.0, 99.9, 99.99, 100.0,
+];
+
+/// Returns the percentiles displayed for the reports created before the set of percentiles
+/// became configurable, which don't list the reported percentiles.
+/// A subset of [`STORED_PERCENTILE_ORDER`].
+pub fn default_reported_percentiles() -> Vec<f64> {
+    vec![
+        0.0, 25.0, 50.0, 75.0, 90.0, 95.0, 98.0, 99.0, 99.9, 99.99, 100.0,
+    ]
+}
+
+/// Formats the percentile label so that the labels of different percentiles
+/// are aligned at the decimal point.
+pub fn percentile_name(p: f64) -> String {
+    if p == 0.0 {
+        return "  Min     ".to_string();
+    }
+    if p == 100.0 {
+        return "  Max     ".to_string();
     }
+    let s = p.to_string();
+    let (int, frac) = s.split_at(s.find('.').unwrap_or(s.len()));
+    format!("{int:>5}{frac:<5}")
+}
+
+/// Returns true if both values denote the same percentile
+fn same_percentile(p1: f64, p2: f64) -> bool {
+    (p1 - p2).abs() < 1e-9
+}
+
+/// Value of a single percentile of a distribution
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct PercentileValue {
+    pub percentile: f64,
+    #[serde(flatten)]
+    pub mean: Mean,
+}
+
+/// Percentile value as stored in a report.
+/// Reports created before the set of percentiles became configurable don't store
+/// the percentile, but they always store [`STORED_PERCENTILE_ORDER`] in a fixed order.
+#[derive(Deserialize)]
+struct StoredPercentileValue {
+    #[serde(default)]
+    percentile: Option<f64>,
+    #[serde(flatten)]
+    mean: Mean,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Percentiles([Mean; Percentile::COUNT]);
+#[serde(from = "Vec<StoredPercentileValue>")]
+pub struct Percentiles(Vec<PercentileValue>);
+
+impl From<Vec<StoredPercentileValue>> for Percentiles {
+    fn from(values: Vec<StoredPercentileValue>) -> Self {
+        let values = values
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, v)| {
+                let percentile = v.percentile.or(STORED_PERCENTILE_ORDER.get(i).copied())?;
+                Some(PercentileValue {
+                    percentile,
+                    mean: v.mean,
+                })
+            })
+            .collect();
+        Percentiles(values)
+    }
+}
 
 impl Percentiles {
     const POPULATION_SIZE: usize = 100;
 
-    /// Computes distribution percentiles without errors.
+    /// Computes given distribution percentiles without errors.
     /// Fast.
-    pub fn compute(histogram: &Histogram<u64>, scale: f64) -> Percentiles {
-        let mut result = Vec::with_capacity(Percentile::COUNT);
-        for p in Percentile::iter() {
-            result.push(Mean {
-                n: Self::POPULATION_SIZE as u64,
-                value: histogram.value_at_percentile(p.value()) as f64 * scale,
-                std_err: None,
-            });
-        }
-        assert_eq!(result.len(), Percentile::COUNT);
-        Percentiles(result.try_into().unwrap())
+    pub fn compute(histogram: &Histogram<u64>, scale: f64, percentiles: &[f64]) -> Percentiles {
+        let values = percentiles
+            .iter()
+            .map(|&p| PercentileValue {
+                percentile: p,
+                mean: Mean {
+                    n: Self::POPULATION_SIZE as u64,
+                    value: histogram.value_at_percentile(p) as f64 * scale,
+                    std_err: None,
+                },
+            })
+            .collect();
+        Percentiles(values)
     }
 
-    /// Computes distribution percentiles with errors based on a HDR histogram.
+    /// Computes given distribution percentiles with errors based on a HDR histogram.
     /// Caution: this is slow. Don't use it when benchmark is running!
     /// Errors are estimated by bootstrapping a larger population of histograms from the
     /// distribution determined by the original histogram and computing the standard error.
@@ -96,37 +174,63 @@ impl Percentiles {
         histogram: &Histogram<u64>,
         scale: f64,
         effective_sample_size: u64,
+        percentiles: &[f64],
     ) -> Percentiles {
         let mut rng = SmallRng::from_rng(&mut rand::rng());
 
-        let mut samples: Vec<[f64; Percentile::COUNT]> = Vec::with_capacity(Self::POPULATION_SIZE);
+        let mut samples: Vec<Vec<f64>> = Vec::with_capacity(Self::POPULATION_SIZE);
         for _ in 0..Self::POPULATION_SIZE {
-            samples.push(percentiles(
+            samples.push(percentile_values(
                 &bootstrap(&mut rng, histogram, effective_sample_size),
                 scale,
+                percentiles,
             ))
         }
 
-        let mut result = Vec::with_capacity(Percentile::COUNT);
-        for p in Percentile::iter() {
-            let values: Vec<f64> = samples.iter().map(|s| s[p as usize]).collect();
+        let mut result = Vec::with_capacity(percentiles.len());
+        for (i, &p) in percentiles.iter().enumerate() {
+            let values: Vec<f64> = samples.iter().map(|s| s[i]).collect();
             let n = values.len() as f64;
             let mean = values.iter().sum::<f64>() / n;
             let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
             let std_err = variance.sqrt();
-            result.push(Mean {
-                n: Self::POPULATION_SIZE as u64,
-                value: histogram.value_at_percentile(p.value()) as f64 * scale,
-                std_err: Some(std_err),
+            result.push(PercentileValue {
+                percentile: p,
+                mean: Mean {
+                    n: Self::POPULATION_SIZE as u64,
+                    value: histogram.value_at_percentile(p) as f64 * scale,
+                    std_err: Some(std_err),
+                },
             });
         }
-
-        assert_eq!(result.len(), Percentile::COUNT);
-        Percentiles(result.try_into().unwrap())
+        Percentiles(result)
     }
 
+    /// Returns the value of the percentile.
+    /// If the percentile has not been computed, e.g. because the statistics were loaded from
+    /// an old report, returns NaN.
     pub fn get(&self, percentile: Percentile) -> Mean {
-        self.0[percentile as usize]
+        self.find(percentile.value()).unwrap_or(Mean {
+            n: 0,
+            value: f64::NAN,
+            std_err: None,
+        })
+    }
+
+    /// Returns the value of the percentile given as a number in range 0.0 to 100.0,
+    /// if it has been computed.
+    pub fn find(&self, percentile: f64) -> Option<Mean> {
+        self.0
+            .iter()
+            .find(|v| same_percentile(v.percentile, percentile))
+            .map(|v| v.mean)
+    }
+
+    /// Returns the computed percentiles in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = &PercentileValue> {
+        self.0
+            .iter()
+            .sorted_by(|a, b| a.percentile.total_cmp(&b.percentile))
     }
 }
 
@@ -171,12 +275,11 @@ fn bootstrap(rng: &mut impl Rng, histogram: &Histogram<u64>, effective_n: u64) -
     result
 }
 
-fn percentiles(hist: &Histogram<u64>, scale: f64) -> [f64; Percentile::COUNT] {
-    let mut percentiles = [0.0; Percentile::COUNT];
-    for (i, p) in Percentile::iter().enumerate() {
-        percentiles[i] = hist.value_at_percentile(p.value()) as f64 * scale;
-    }
+fn percentile_values(hist: &Histogram<u64>, scale: f64, percentiles: &[f64]) -> Vec<f64> {
     percentiles
+        .iter()
+        .map(|&p| hist.value_at_percentile(p) as f64 * scale)
+        .collect()
 }
 
 #[cfg(test)]
@@ -186,6 +289,11 @@ mod test {
     use hdrhistogram::Histogram;
     use rand::Rng;
     use rand_distr::Uniform;
+    use strum::IntoEnumIterator;
+
+    fn all_percentiles() -> Vec<f64> {
+        Percentile::iter().map(|p| p.value()).collect()
+    }
 
     #[test]
     fn test_zero_error() {
@@ -194,7 +302,8 @@ mod test {
             histogram.record(1000).unwrap();
         }
 
-        let percentiles = Percentiles::compute_with_errors(&histogram, 1e-6, histogram.len());
+        let percentiles =
+            Percentiles::compute_with_errors(&histogram, 1e-6, histogram.len(), &all_percentiles());
         let median = percentiles.get(Percentile::P50);
         assert_approx_eq!(median.value, 0.001, 0.00001);
         assert_approx_eq!(median.std_err.unwrap(), 0.000, 1e-15);
@@ -211,13 +320,48 @@ mod test {
                 .unwrap();
         }
 
-        let percentiles = Percentiles::compute_with_errors(&histogram, 1e-6, histogram.len());
+        let percentiles =
+            Percentiles::compute_with_errors(&histogram, 1e-6, histogram.len(), &all_percentiles());
         let min = percentiles.get(Percentile::Min);
         let max = percentiles.get(Percentile::Max);
         assert!(min.std_err.unwrap() < max.value / N as f64);
         assert!(max.std_err.unwrap() < max.value / N as f64);
     }
 
+    #[test]
+    fn test_load_legacy_percentiles() {
+        let legacy = (0..15)
+            .map(|i| format!(r#"{{"n": 100, "value": {i}.0, "std_err": null}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let percentiles: Percentiles = serde_json::from_str(&format!("[{legacy}]")).unwrap();
+        assert_eq!(percentiles.get(Percentile::Min).value, 0.0);
+        assert_eq!(percentiles.get(Percentile::P99_99).value, 13.0);
+        assert_eq!(percentiles.get(Percentile::Max).value, 14.0);
+        assert!(percentiles.get(Percentile::P99_999).value.is_nan());
+    }
+
+    #[test]
+    fn test_serialize_configured_percentiles() {
+        let mut histogram = Histogram::<u64>::new(3).unwrap();
+        for i in 1..=1000000 {
+            histogram.record(i).unwrap();
+        }
+        let percentiles = Percentiles::compute(&histogram, 1.0, &[99.95, 99.9999]);
+        let json = serde_json::to_string(&percentiles).unwrap();
+        let percentiles: Percentiles = serde_json::from_str(&json).unwrap();
+        assert_approx_eq!(percentiles.find(99.95).unwrap().value, 999500.0, 1000.0);
+        assert_approx_eq!(percentiles.find(99.9999).unwrap().value, 999999.0, 1000.0);
+        assert!(percentiles.find(50.0).is_none());
+    }
+
+    #[test]
+    fn test_percentile_names() {
+        assert_eq!(super::percentile_name(0.0).trim(), "Min");
+        assert_eq!(super::percentile_name(99.0), "   99     ");
+        assert_eq!(super::percentile_name(99.999), "   99.999 ");
+    }
+
     #[test]
     fn test_bootstrap() {
         use super::bootstrap;
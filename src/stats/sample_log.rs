@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use itertools::Itertools;
+use serde_json::{Map, Value};
+
+use crate::config::SampleLogFormat;
+use crate::stats::errors::ErrorCategory;
+use crate::stats::latency::LatencyDistribution;
+use crate::stats::percentiles::Percentile;
+use crate::stats::{FnCounts, Sample};
+
+/// Receives each sample as soon as it gets recorded.
+pub trait SampleWriter {
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()>;
+}
+
+/// Latency statistics written for each latency distribution of a sample
+const LATENCY_COLUMNS: &[(&str, Option<Percentile>)] = &[
+    ("mean", None),
+    ("p50", Some(Percentile::P50)),
+    ("p75", Some(Percentile::P75)),
+    ("p90", Some(Percentile::P90)),
+    ("p95", Some(Percentile::P95)),
+    ("p99", Some(Percentile::P99)),
+    ("p99_9", Some(Percentile::P99_9)),
+    ("max", Some(Percentile::Max)),
+];
+
+/// Returns the statistics of the sample as a flat list of named values.
+/// Latencies are given in milliseconds.
+pub fn sample_values(sample: &Sample) -> Vec<(String, f64)> {
+    let mut values = vec![
+        ("time_s".to_string(), sample.time_s as f64),
+        ("duration_s".to_string(), sample.duration_s as f64),
+        ("cycle_count".to_string(), sample.cycle_count as f64),
+        (
+            "cycle_error_count".to_string(),
+            sample.cycle_error_count as f64,
+        ),
+        ("request_count".to_string(), sample.request_count as f64),
+        ("req_error_count".to_string(), sample.req_error_count as f64),
+        ("req_retry_count".to_string(), sample.req_retry_count as f64),
+        ("row_count".to_string(), sample.row_count as f64),
+        (
+            "late_cycle_count".to_string(),
+            sample.late_cycle_count as f64,
+        ),
+        ("mean_queue_len".to_string(), sample.mean_queue_len as f64),
+        (
+            "cycle_throughput".to_string(),
+            sample.cycle_throughput as f64,
+        ),
+        (
+            "target_rate".to_string(),
+            sample.target_rate.map_or(f64::NAN, |r| r as f64),
+        ),
+        ("req_throughput".to_string(), sample.req_throughput as f64),
+        ("row_throughput".to_string(), sample.row_throughput as f64),
+    ];
+    values.extend(ErrorCategory::ALL.iter().map(|c| {
+        (
+            format!("{}_error_count", c.name()),
+            sample.error_counts.get(*c) as f64,
+        )
+    }));
+    values.extend(latency_values("cycle_latency_", &sample.cycle_latency));
+    values.extend(latency_values("request_latency_", &sample.request_latency));
+    if let Some(start_delay) = &sample.start_delay {
+        values.extend(latency_values("start_delay_", start_delay));
+    }
+    values
+}
+
+fn latency_values(prefix: &str, latency: &LatencyDistribution) -> Vec<(String, f64)> {
+    LATENCY_COLUMNS
+        .iter()
+        .map(|(name, p)| {
+            let value = match p {
+                Some(p) => latency.percentiles.get(*p).value,
+                None => latency.mean.value,
+            };
+            (format!("{prefix}{name}_ms"), value)
+        })
+        .collect()
+}
+
+/// Returns the counts of the calls of a function and their throughput
+fn fn_count_values(counts: &FnCounts, duration_s: f32) -> Vec<(&'static str, f64)> {
+    vec![
+        ("call_count", counts.call_count as f64),
+        (
+            "call_throughput",
+            counts.call_count as f64 / duration_s as f64,
+        ),
+        ("error_count", counts.error_count as f64),
+        ("request_count", counts.request_count as f64),
+        ("retry_count", counts.retry_count as f64),
+        ("row_count", counts.row_count as f64),
+    ]
+}
+
+/// Returns the number and the latency of the requests served by each node or shard
+fn requests_by_key(latencies: &HashMap<String, LatencyDistribution>) -> Value {
+    let by_key = latencies
+        .iter()
+        .map(|(key, latency)| {
+            let mut values = latency_values("", latency)
+                .into_iter()
+                .map(|(k, v)| (k, Value::from(v)))
+                .collect::<Map<_, _>>();
+            values.insert(
+                "request_count".to_string(),
+                Value::from(latency.histogram.0.len()),
+            );
+            (key.clone(), Value::Object(values))
+        })
+        .collect();
+    Value::Object(by_key)
+}
+
+/// Writes each sample as a separate JSON object in a new line.
+/// Apart from the statistics written by the CSV writer, contains the latency
+/// and the counts of the calls of each function, the latency of the requests served
+/// by each node and the error messages.
+pub struct JsonLinesSampleWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> SampleWriter for JsonLinesSampleWriter<W> {
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        let mut object: Map<String, Value> = sample_values(sample)
+            .into_iter()
+            .map(|(k, v)| (k, Value::from(v)))
+            .collect();
+        let by_fn: Map<String, Value> = sample
+            .cycle_latency_by_fn
+            .iter()
+            .map(|(f, latency)| {
+                let values = latency_values("", latency)
+                    .into_iter()
+                    .map(|(k, v)| (k, Value::from(v)))
+                    .collect::<Map<_, _>>();
+                (f.clone(), Value::Object(values))
+            })
+            .collect();
+        object.insert("cycle_latency_by_fn".to_string(), Value::Object(by_fn));
+        let counts_by_fn: Map<String, Value> = sample
+            .counts_by_fn
+            .iter()
+            .map(|(f, counts)| {
+                let values = fn_count_values(counts, sample.duration_s)
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), Value::from(v)))
+                    .collect::<Map<_, _>>();
+                (f.clone(), Value::Object(values))
+            })
+            .collect();
+        object.insert("counts_by_fn".to_string(), Value::Object(counts_by_fn));
+        object.insert(
+            "request_latency_by_node".to_string(),
+            requests_by_key(&sample.request_latency_by_node),
+        );
+        if !sample.request_latency_by_shard.is_empty() {
+            object.insert(
+                "request_latency_by_shard".to_string(),
+                requests_by_key(&sample.request_latency_by_shard),
+            );
+        }
+        object.insert(
+            "req_errors".to_string(),
+            Value::from(sample.req_errors.iter().sorted().cloned().collect_vec()),
+        );
+        serde_json::to_writer(&mut self.out, &object)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+/// Writes each sample as a CSV row. Writes the header before the first row.
+pub struct CsvSampleWriter<W: Write> {
+    out: W,
+    /// Columns of the header, taken from the first sample
+    columns: Option<Vec<String>>,
+}
+
+impl<W: Write> SampleWriter for CsvSampleWriter<W> {
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        let values = sample_values(sample);
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => {
+                let columns = values.iter().map(|(k, _)| k.clone()).collect_vec();
+                writeln!(self.out, "{}", columns.join(","))?;
+                self.columns.insert(columns)
+            }
+        };
+        // Keep the cells aligned with the header even if the sample lacks some columns
+        let values: HashMap<String, f64> = values.into_iter().collect();
+        let row = columns
+            .iter()
+            .map(|c| values.get(c).map(|v| v.to_string()).unwrap_or_default())
+            .join(",");
+        writeln!(self.out, "{row}")?;
+        self.out.flush()
+    }
+}
+
+/// Creates the file at given path and returns a writer for writing samples into it.
+/// If the format is not given, it is determined by the extension of the file;
+/// `.csv` files get CSV, everything else gets JSON Lines.
+pub fn create_sample_writer(
+    path: &Path,
+    format: Option<SampleLogFormat>,
+) -> io::Result<Box<dyn SampleWriter>> {
+    let format = format.unwrap_or_else(|| {
+        if path.extension().is_some_and(|ext| ext == "csv") {
+            SampleLogFormat::Csv
+        } else {
+            SampleLogFormat::Json
+        }
+    });
+    let out = BufWriter::new(File::create(path)?);
+    Ok(match format {
+        SampleLogFormat::Json => Box::new(JsonLinesSampleWriter { out }),
+        SampleLogFormat::Csv => Box::new(CsvSampleWriter { out, columns: None }),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::workload::{FnRef, FnStats, WorkloadStats};
+    use crate::stats::session::SessionStats;
+    use std::time::{Duration, Instant};
+
+    fn sample() -> Sample {
+        let mut fn_stats = FnStats::new(FnRef::new("read"));
+        fn_stats.operation_completed(Duration::from_millis(2));
+        fn_stats.operation_completed(Duration::from_millis(4));
+        let mut session_stats = SessionStats::new();
+        session_stats.req_count = 2;
+        session_stats.record_error(ErrorCategory::Timeout);
+        let node = "10.0.0.1:9042".parse().unwrap();
+        session_stats.record_coordinator(node, None, Duration::from_millis(1), 1);
+        session_stats.record_coordinator(node, None, Duration::from_millis(3), 1);
+        let start_time = Instant::now();
+        let stats = WorkloadStats {
+            start_time,
+            end_time: start_time + Duration::from_secs(1),
+            function_stats: vec![fn_stats],
+            session_stats,
+        };
+        Sample::new(start_time, &[stats])
+    }
+
+    #[test]
+    fn write_json_lines() {
+        let mut writer = JsonLinesSampleWriter { out: Vec::new() };
+        writer.write_sample(&sample()).unwrap();
+        writer.write_sample(&sample()).unwrap();
+        let output = String::from_utf8(writer.out).unwrap();
+        let lines = output.lines().collect_vec();
+        assert_eq!(lines.len(), 2);
+        let value: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(value["cycle_count"], 2.0);
+        assert_eq!(value["request_count"], 2.0);
+        assert!(
+            value["cycle_latency_by_fn"]["read"]["p99_ms"]
+                .as_f64()
+                .unwrap()
+                > 3.9
+        );
+        assert_eq!(value["counts_by_fn"]["read"]["call_count"], 2.0);
+        assert_eq!(value["timeout_error_count"], 1.0);
+        assert_eq!(value["overloaded_error_count"], 0.0);
+        let node = &value["request_latency_by_node"]["10.0.0.1:9042"];
+        assert_eq!(node["request_count"], 2);
+        assert!(node["max_ms"].as_f64().unwrap() > 2.9);
+        assert!(value.get("request_latency_by_shard").is_none());
+    }
+
+    #[test]
+    fn write_csv() {
+        let mut writer = CsvSampleWriter {
+            out: Vec::new(),
+            columns: None,
+        };
+        writer.write_sample(&sample()).unwrap();
+        let mut no_start_delay = sample();
+        no_start_delay.start_delay = None;
+        writer.write_sample(&no_start_delay).unwrap();
+        let output = String::from_utf8(writer.out).unwrap();
+        let lines = output.lines().collect_vec();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("time_s,duration_s,cycle_count,"));
+        assert!(lines[0].ends_with(",start_delay_max_ms"));
+        let columns = lines[0].split(',').count();
+        assert_eq!(lines[1].split(',').count(), columns);
+        assert_eq!(lines[2].split(',').count(), columns);
+        assert!(lines[2].ends_with(",,"));
+        assert_eq!(lines[1].split(',').nth(2), Some("2"));
+    }
+}
@@ -1,10 +1,50 @@
 use crate::config::PRINT_RETRY_ERROR_LIMIT;
+use crate::stats::errors::{ErrorCategory, ErrorCounts};
 use crate::stats::latency::LatencyDistributionRecorder;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::time::Instant;
 
-#[derive(Clone, Debug)]
+tokio::task_local! {
+    /// Counts the requests made by the workload function call being polled
+    static CALL_STATS: Cell<CallStats>;
+}
+
+/// Requests made by a single call of a workload function
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CallStats {
+    pub req_count: u64,
+    pub req_retry_count: u64,
+    pub row_count: u64,
+}
+
+impl CallStats {
+    /// Runs the future and returns its output together with the requests it made.
+    /// Requests are attributed correctly even if many calls run concurrently in one task.
+    pub async fn track<F: Future>(call: F) -> (F::Output, CallStats) {
+        CALL_STATS
+            .scope(Cell::new(CallStats::default()), async move {
+                let output = call.await;
+                (output, CALL_STATS.with(Cell::get))
+            })
+            .await
+    }
+
+    /// Updates the stats of the tracked call, if any
+    fn update(f: impl FnOnce(&mut CallStats)) {
+        let _ = CALL_STATS.try_with(|stats| {
+            let mut current = stats.get();
+            f(&mut current);
+            stats.set(current);
+        });
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SessionStats {
     pub req_count: u64,
     pub req_errors: HashSet<String>,
@@ -15,6 +55,58 @@ pub struct SessionStats {
     pub queue_length: u64,
     pub mean_queue_length: f32,
     pub resp_times_ns: LatencyDistributionRecorder,
+    /// Requests of each statement, keyed by the prepared statement key or the operation name
+    #[serde(default)]
+    pub statements: HashMap<String, StatementStats>,
+    /// Batches, keyed by the batch type and the statements they contain
+    #[serde(default)]
+    pub batches: HashMap<String, StatementStats>,
+    /// Number of failed attempts of requests and validations in each error category
+    #[serde(default)]
+    pub error_counts: ErrorCounts,
+    /// Successful requests served by each coordinator node, keyed by the node address
+    #[serde(default)]
+    pub nodes: HashMap<String, StatementStats>,
+    /// Successful requests served by each shard, keyed by the node address and the shard number
+    #[serde(default)]
+    pub shards: HashMap<String, StatementStats>,
+}
+
+/// Requests made with a single statement or batch
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StatementStats {
+    pub req_count: u64,
+    pub req_error_count: u64,
+    pub req_retry_count: u64,
+    pub row_count: u64,
+    /// Number of statements in the successful batches
+    #[serde(default)]
+    pub statement_count: u64,
+    pub resp_times_ns: LatencyDistributionRecorder,
+}
+
+impl StatementStats {
+    fn complete(&mut self, duration: Duration, row_count: u64) {
+        self.resp_times_ns.record(duration);
+        self.req_count += 1;
+        self.row_count += row_count;
+    }
+
+    fn fail(&mut self, retried: bool) {
+        self.req_error_count += 1;
+        if retried {
+            self.req_retry_count += 1;
+        }
+    }
+
+    pub fn add(&mut self, other: &StatementStats) {
+        self.req_count += other.req_count;
+        self.req_error_count += other.req_error_count;
+        self.req_retry_count += other.req_retry_count;
+        self.row_count += other.row_count;
+        self.statement_count += other.statement_count;
+        self.resp_times_ns.add(&other.resp_times_ns);
+    }
 }
 
 impl SessionStats {
@@ -31,15 +123,83 @@ impl SessionStats {
         Instant::now()
     }
 
-    pub fn complete_request(&mut self, duration: Duration, row_count: u64) {
+    pub fn complete_request(&mut self, statement: &str, duration: Duration, row_count: u64) {
+        self.complete(duration, row_count);
+        entry_mut(&mut self.statements, statement).complete(duration, row_count);
+    }
+
+    /// Records a failed request of the statement.
+    /// The request is counted as retried if another attempt is going to be made.
+    pub fn fail_request(&mut self, statement: &str, retried: bool) {
+        self.queue_length -= 1;
+        entry_mut(&mut self.statements, statement).fail(retried);
+    }
+
+    /// Records a successful batch of `statement_count` statements.
+    /// The batch is counted as a single request.
+    pub fn complete_batch(
+        &mut self,
+        batch: &str,
+        duration: Duration,
+        statement_count: u64,
+        row_count: u64,
+    ) {
+        self.complete(duration, row_count);
+        let stats = entry_mut(&mut self.batches, batch);
+        stats.complete(duration, row_count);
+        stats.statement_count += statement_count;
+    }
+
+    /// Records a failed batch.
+    /// The batch is counted as retried if another attempt is going to be made.
+    pub fn fail_batch(&mut self, batch: &str, retried: bool) {
+        self.queue_length -= 1;
+        entry_mut(&mut self.batches, batch).fail(retried);
+    }
+
+    /// Records a response received from the coordinator node and, if given, from its shard.
+    /// Each page of a multi-page request counts as a separate request of its coordinator.
+    pub fn record_coordinator(
+        &mut self,
+        node: SocketAddr,
+        shard: Option<u32>,
+        duration: Duration,
+        row_count: u64,
+    ) {
+        let node = node.to_string();
+        if let Some(shard) = shard {
+            entry_mut(&mut self.shards, &format!("{node}/{shard}")).complete(duration, row_count);
+        }
+        entry_mut(&mut self.nodes, &node).complete(duration, row_count);
+    }
+
+    fn complete(&mut self, duration: Duration, row_count: u64) {
         self.queue_length -= 1;
         self.resp_times_ns.record(duration);
         self.req_count += 1;
         self.row_count += row_count;
+        CallStats::update(|s| {
+            s.req_count += 1;
+            s.row_count += row_count;
+        });
+    }
+
+    /// Counts a failed attempt of a request or of a response validation
+    pub fn record_error(&mut self, category: ErrorCategory) {
+        self.error_counts.record(category);
+    }
+
+    /// Records an error of a request that is not going to be retried
+    pub fn store_error(&mut self, error_str: String) {
+        self.req_error_count += 1;
+        if self.req_error_count <= PRINT_RETRY_ERROR_LIMIT {
+            self.req_errors.insert(error_str);
+        }
     }
 
     pub fn store_retry_error(&mut self, error_str: String) {
         self.req_retry_count += 1;
+        CallStats::update(|s| s.req_retry_count += 1);
         if self.req_retry_count <= PRINT_RETRY_ERROR_LIMIT {
             self.req_retry_errors.insert(error_str);
         }
@@ -55,12 +215,28 @@ impl SessionStats {
         self.req_errors.clear();
         self.req_retry_errors.clear();
         self.resp_times_ns.clear();
+        self.statements.clear();
+        self.batches.clear();
+        self.error_counts.clear();
+        self.nodes.clear();
+        self.shards.clear();
 
         // note that current queue_length is *not* reset to zero because there
         // might be pending requests and if we set it to zero, that would underflow
     }
 }
 
+fn entry_mut<'a>(
+    stats: &'a mut HashMap<String, StatementStats>,
+    key: &str,
+) -> &'a mut StatementStats {
+    // Avoid allocating the key on every request
+    if !stats.contains_key(key) {
+        stats.insert(key.to_string(), StatementStats::default());
+    }
+    stats.get_mut(key).unwrap()
+}
+
 impl Default for SessionStats {
     fn default() -> Self {
         SessionStats {
@@ -73,6 +249,114 @@ impl Default for SessionStats {
             queue_length: 0,
             mean_queue_length: 0.0,
             resp_times_ns: LatencyDistributionRecorder::default(),
+            statements: HashMap::new(),
+            batches: HashMap::new(),
+            error_counts: ErrorCounts::default(),
+            nodes: HashMap::new(),
+            shards: HashMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+
+    #[tokio::test]
+    async fn attributes_requests_to_concurrent_calls() {
+        let stats = std::sync::Mutex::new(SessionStats::new());
+        let call = |rows: u64| {
+            let stats = &stats;
+            CallStats::track(async move {
+                for _ in 0..rows {
+                    tokio::task::yield_now().await;
+                    let mut stats = stats.lock().unwrap();
+                    stats.start_request();
+                    stats.complete_request("read", Duration::ZERO, 1);
+                }
+            })
+        };
+        let ((_, a), (_, b)) = tokio::join!(call(2), call(5));
+        assert_eq!((a.req_count, a.row_count), (2, 2));
+        assert_eq!((b.req_count, b.row_count), (5, 5));
+        assert_eq!(stats.lock().unwrap().req_count, 7);
+    }
+
+    #[test]
+    fn records_requests_of_each_statement() {
+        let mut stats = SessionStats::new();
+        for (key, ok) in [
+            ("read", true),
+            ("write", false),
+            ("write", true),
+            ("read", true),
+        ] {
+            stats.start_request();
+            if ok {
+                stats.complete_request(key, Duration::from_millis(1), 2);
+            } else {
+                stats.fail_request(key, true);
+            }
+        }
+        assert_eq!(stats.queue_length, 0);
+        assert_eq!(stats.req_count, 3);
+        let read = &stats.statements["read"];
+        assert_eq!(
+            (read.req_count, read.row_count, read.req_error_count),
+            (2, 4, 0)
+        );
+        let write = &stats.statements["write"];
+        assert_eq!(
+            (
+                write.req_count,
+                write.req_error_count,
+                write.req_retry_count
+            ),
+            (1, 1, 1)
+        );
+        stats.reset();
+        assert!(stats.statements.is_empty());
+    }
+
+    #[test]
+    fn records_batches_apart_from_statements() {
+        let mut stats = SessionStats::new();
+        stats.start_request();
+        stats.complete_batch("unlogged(write)", Duration::from_millis(1), 10, 0);
+        stats.start_request();
+        stats.fail_batch("unlogged(write)", false);
+        assert_eq!(stats.queue_length, 0);
+        assert_eq!(stats.req_count, 1);
+        assert!(stats.statements.is_empty());
+        let batch = &stats.batches["unlogged(write)"];
+        assert_eq!(
+            (
+                batch.req_count,
+                batch.req_error_count,
+                batch.statement_count
+            ),
+            (1, 1, 10)
+        );
+    }
+
+    #[test]
+    fn records_requests_of_each_coordinator() {
+        let mut stats = SessionStats::new();
+        let node1: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let node2: SocketAddr = "10.0.0.2:9042".parse().unwrap();
+        let duration = Duration::from_millis(1);
+        stats.record_coordinator(node1, Some(0), duration, 1);
+        stats.record_coordinator(node1, Some(3), duration, 2);
+        stats.record_coordinator(node2, None, duration, 0);
+        let node = &stats.nodes["10.0.0.1:9042"];
+        assert_eq!((node.req_count, node.row_count), (2, 3));
+        assert_eq!(stats.nodes["10.0.0.2:9042"].req_count, 1);
+        assert_eq!(
+            stats.shards.keys().sorted().collect_vec(),
+            ["10.0.0.1:9042/0", "10.0.0.1:9042/3"]
+        );
+        stats.reset();
+        assert!(stats.nodes.is_empty() && stats.shards.is_empty());
+    }
+}